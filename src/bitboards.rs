@@ -1,6 +1,7 @@
 use crate::{piece::PieceType, bitmethods::Bithackable};
 use crate::magicnumbers::{BB_B1, BB_B8, BB_C1, BB_C8, BB_CORNERS, BB_D1, BB_D8, BB_E1, BB_E8, BB_EMPTY, BB_F1, BB_F8, BB_G1, BB_G8, BB_RANK_1, BB_RANK_2, BB_RANK_7, BB_RANK_8};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Bitboard {
     pub pawns: u64,
@@ -11,6 +12,7 @@ pub struct Bitboard {
     pub kings: u64,
     pub castling_rights: u64,
     pub ep_square: u64,
+    pub promoted: u64,
     pub occupied_co: [u64; 2],
 }
 
@@ -24,6 +26,7 @@ impl Bitboard {
         self.kings = BB_E1 | BB_E8;
         self.castling_rights = BB_CORNERS;
         self.ep_square = BB_EMPTY;
+        self.promoted = BB_EMPTY;
         self.occupied_co[0] = BB_RANK_1 | BB_RANK_2;
         self.occupied_co[1] = BB_RANK_7 | BB_RANK_8;
     }
@@ -38,6 +41,7 @@ impl Bitboard {
             kings: BB_E1 | BB_E8,
             castling_rights: BB_CORNERS,
             ep_square: BB_EMPTY,
+            promoted: BB_EMPTY,
             occupied_co: [BB_RANK_1 | BB_RANK_2, BB_RANK_7 | BB_RANK_8],
         }
     }
@@ -52,6 +56,7 @@ impl Bitboard {
             kings: BB_EMPTY,
             castling_rights: BB_EMPTY,
             ep_square: BB_EMPTY,
+            promoted: BB_EMPTY,
             occupied_co: [BB_EMPTY, BB_EMPTY],
         }
     }
@@ -75,8 +80,39 @@ impl Bitboard {
         }
     }
 
+    /// The pieces of type `piece_type`, of either colour.
+    pub const fn pieces(&self, piece_type: PieceType) -> u64 {
+        match piece_type {
+            PieceType::None => !self.occupied(),
+            PieceType::Pawn => self.pawns,
+            PieceType::Knight => self.knights,
+            PieceType::Bishop => self.bishops,
+            PieceType::Rook => self.rooks,
+            PieceType::Queen => self.queens,
+            PieceType::King => self.kings,
+        }
+    }
+
     #[inline]
     pub const fn occupied(&self) -> u64 {
         self.occupied_co[0] | self.occupied_co[1]
     }
+
+    /// A key for the material alone: four bits for the number of each kind
+    /// of piece, from white's pawns up to black's king.
+    pub const fn material_key(&self) -> u64 {
+        let pieces = [self.pawns, self.knights, self.bishops, self.rooks, self.queens, self.kings];
+        let mut key = 0;
+        let mut colour = 0;
+        while colour < 2 {
+            let mut i = 0;
+            while i < 6 {
+                let count = (pieces[i] & self.occupied_co[colour]).count_ones() as u64;
+                key |= count << (4 * (6 * colour + i));
+                i += 1;
+            }
+            colour += 1;
+        }
+        key
+    }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
 #![allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
 
 use std::fmt::{Debug, Display, Error, Formatter};
+#[cfg(feature = "nnue")]
+use std::sync::Arc;
 use crate::squares::SquareTrait;
 use regex::Regex;
 
@@ -8,12 +10,17 @@ use crate::magicnumbers::{BB_A1, BB_A8, BB_C1, BB_C8, BB_D1, BB_D8, BB_E1, BB_E8
 
 use crate::bitmethods::{Bithackable, into_bb};
 use crate::bitboards::Bitboard;
-use crate::cmove::{Move, MoveUndoInfo};
+use crate::cmove::{Move, MoveType, MoveUndoInfo};
+use crate::evaluation::Psqt;
+#[cfg(feature = "nnue")]
+use crate::nnue::{Accumulators, Network};
 use crate::colour::Colour;
 use crate::movebuffer::MoveBuf;
-use crate::movegen::generate_pseudo_legal_moves;
+use crate::packed::PackedBoard;
+use crate::movegen::{attackers_mask, generate_legal_moves, generate_pseudo_legal_ep, generate_pseudo_legal_moves};
 use crate::piece::{Piece, PieceType};
 use crate::squares::Square;
+use crate::zobrist::{self, castling_key, ep_key, piece_key};
 
 const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 const STARTING_BOARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
@@ -25,12 +32,29 @@ lazy_static! {
     static ref SAN_REGEX: Regex = Regex::new(SAN_REGEX_TEXT).unwrap();
 }
 
+/// Controls when the en passant square is written out in FEN and EPD strings.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnPassantMode {
+    /// Only if there is a fully legal en passant capture.
+    Legal,
+    /// Whenever the last move was a double pawn push.
+    Fen,
+    /// Only if there is a pseudo-legal en passant capture, as in X-FEN.
+    XFen,
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Board {
     bitboard: Bitboard,
     halfmove_clock: u8,
     fullmove_number: u16,
     moves_played: u16,
+    key: u64,
+    pawn_key: u64,
+    psqt: Psqt,
+    #[cfg(feature = "nnue")]
+    accumulators: Accumulators,
     stack: Vec<(Move, MoveUndoInfo)>,
 }
 
@@ -41,6 +65,11 @@ impl Board {
             halfmove_clock: 0,
             fullmove_number: 1,
             moves_played: 0,
+            key: zobrist::hash(&Bitboard::new(), 0),
+            pawn_key: zobrist::pawn_hash(&Bitboard::new()),
+            psqt: Psqt::new(&Bitboard::new()),
+            #[cfg(feature = "nnue")]
+            accumulators: Accumulators::new(),
             stack: Vec::new(),
         }
     }
@@ -51,6 +80,11 @@ impl Board {
             halfmove_clock: 0,
             fullmove_number: 1,
             moves_played: 0,
+            key: zobrist::hash(&Bitboard::clear(), 0),
+            pawn_key: zobrist::pawn_hash(&Bitboard::clear()),
+            psqt: Psqt::new(&Bitboard::clear()),
+            #[cfg(feature = "nnue")]
+            accumulators: Accumulators::new(),
             stack: Vec::new(),
         }
     }
@@ -60,6 +94,11 @@ impl Board {
         self.moves_played = 0;
         self.halfmove_clock = 0;
         self.fullmove_number = 1;
+        self.key = zobrist::hash(&self.bitboard, 0);
+        self.pawn_key = zobrist::pawn_hash(&self.bitboard);
+        self.psqt = Psqt::new(&self.bitboard);
+        #[cfg(feature = "nnue")]
+        self.accumulators.restart(&self.bitboard);
         self.stack.clear();
     }
 
@@ -73,12 +112,18 @@ impl Board {
         }
     }
 
+    /// The moves played since the root position, oldest first.
+    pub fn move_history(&self) -> impl Iterator<Item = Move> + '_ {
+        self.stack.iter().map(|&(m, _)| m)
+    }
+
     fn unplay_all(&mut self) {
         while !self.stack.is_empty() {
             self.unmake();
         }
     }
 
+    #[allow(clippy::too_many_lines)]
     pub fn make(&mut self, m: Move) {
         use PieceType::{King, Pawn, Rook};
 
@@ -88,15 +133,32 @@ impl Board {
         let undo_info = MoveUndoInfo::new(
             self.bitboard.ep_square, 
             self.bitboard.castling_rights,
+            self.bitboard.promoted,
             self.halfmove_clock,
-            captured
+            captured,
+            self.key,
+            self.pawn_key,
+            self.psqt,
         );
+        #[cfg(feature = "nnue")]
+        self.accumulators.push();
 
         let from = m.from_sq();
         let from_bb = into_bb(from as usize);
         let to_bb = into_bb(to as usize);
         let from_to_bb = from_bb | to_bb;
         let piece = self.bitboard.piece_type_at(from as usize);
+        let us = self.turn_as_idx();
+
+        // the castling rights and en passant square are hashed back in
+        // once they have been updated.
+        self.key ^= castling_key(self.bitboard.castling_rights) ^ ep_key(self.bitboard.ep_square);
+        self.key ^= piece_key(us, piece, from) ^ piece_key(us, piece, to);
+        self.remove_features(us, piece, from);
+        self.add_features(us, piece, to);
+        if piece == Pawn {
+            self.pawn_key ^= piece_key(us, Pawn, from) ^ piece_key(us, Pawn, to);
+        }
 
         // clear the from_square and set the to_square in the colour bb
         self.bitboard.occupied_co[self.turn_as_idx()] ^= from_to_bb;
@@ -111,18 +173,25 @@ impl Board {
             let bb = self.get_bb_mut(captured);
             // clear the piece_bb
             *bb ^= to_bb;
+            self.key ^= piece_key(1 - us, captured, to);
+            self.remove_features(1 - us, captured, to);
+            if captured == Pawn {
+                self.pawn_key ^= piece_key(1 - us, Pawn, to);
+            }
         }
         
         // castling
-        if piece == King {            
-            if from_bb == BB_E1 && to_bb == BB_G1 {
-                self.bitboard.rooks ^= BB_H1 | BB_F1;
-            } else if from_bb == BB_E1 && to_bb == BB_C1 {
-                self.bitboard.rooks ^= BB_A1 | BB_D1;
-            } else if from_bb == BB_E8 && to_bb == BB_G8 {
-                self.bitboard.rooks ^= BB_H8 | BB_F8;
-            } else if from_bb == BB_E8 && to_bb == BB_C8 {
-                self.bitboard.rooks ^= BB_A8 | BB_D8;
+        if piece == King {
+            let rook_from_to_bb = Self::castling_rook_from_to(from_bb, to_bb);
+            self.bitboard.rooks ^= rook_from_to_bb;
+            self.bitboard.occupied_co[self.turn_as_idx()] ^= rook_from_to_bb;
+            for rook_square in rook_from_to_bb.iter_bits() {
+                self.key ^= piece_key(us, Rook, rook_square);
+                if self.bitboard.rooks.test(rook_square) {
+                    self.add_features(us, Rook, rook_square);
+                } else {
+                    self.remove_features(us, Rook, rook_square);
+                }
             }
         }
 
@@ -153,7 +222,8 @@ impl Board {
             0
         };
 
-        self.bitboard.castling_rights &= !castling_rights_mask;
+        // moving onto a corner captures the rook there, if any.
+        self.bitboard.castling_rights &= !(castling_rights_mask | to_bb);
 
         // en passant capture
         if piece == Pawn && (to_bb & self.bitboard.ep_square).any_set() {
@@ -165,6 +235,9 @@ impl Board {
             self.bitboard.pawns ^= captured_pawn_loc;
             // remove from the colour mask
             self.bitboard.occupied_co[1 - self.turn_as_idx()] ^= captured_pawn_loc;
+            self.key ^= piece_key(1 - us, Pawn, captured_pawn_loc.lsb());
+            self.remove_features(1 - us, Pawn, captured_pawn_loc.lsb());
+            self.pawn_key ^= piece_key(1 - us, Pawn, captured_pawn_loc.lsb());
         }
 
         // en passant square generation / removal
@@ -178,8 +251,8 @@ impl Board {
         }
 
         // promotions
-        let promotion_piece_type = m.promotion();
-        if promotion_piece_type != PieceType::None {
+        if m.move_type() == MoveType::Promotion {
+            let promotion_piece_type = m.promotion();
             let promo_bb = match promotion_piece_type {
                 PieceType::Knight => &mut self.bitboard.knights,
                 PieceType::Bishop => &mut self.bitboard.bishops,
@@ -189,30 +262,95 @@ impl Board {
             };
             *promo_bb |= to_bb;
             self.bitboard.pawns ^= to_bb;
+            self.key ^= piece_key(us, Pawn, to) ^ piece_key(us, promotion_piece_type, to);
+            self.remove_features(us, Pawn, to);
+            self.add_features(us, promotion_piece_type, to);
+            self.pawn_key ^= piece_key(us, Pawn, to);
+        }
+
+        // promoted pieces: a captured piece loses its mark, a moving piece
+        // carries it along, and a freshly promoted piece gains one.
+        self.bitboard.promoted &= !to_bb;
+        if (self.bitboard.promoted & from_bb).any_set() {
+            self.bitboard.promoted ^= from_to_bb;
+        }
+        if m.move_type() == MoveType::Promotion {
+            self.bitboard.promoted |= to_bb;
         }
 
         // halfmove clock
-        if piece == Pawn {
+        if piece == Pawn || captured != PieceType::None {
             self.halfmove_clock = 0;
         } else {
             self.halfmove_clock += 1;
         }
 
         // fullmove number
-        if self.turn() == Colour::White {
+        if self.turn() == Colour::Black {
             self.fullmove_number += 1;
         }
 
         // move count
         self.moves_played += 1;
 
+        self.key ^= castling_key(self.bitboard.castling_rights)
+            ^ ep_key(self.bitboard.ep_square)
+            ^ zobrist::KEYS.black_to_move;
+
+        debug_assert_eq!(self.psqt, Psqt::new(&self.bitboard), "{}", self.fen());
+        #[cfg(feature = "nnue")]
+        debug_assert!(self.accumulators.is_fresh(&self.bitboard), "{}", self.fen());
+
         // push the move and info onto the stack
         self.stack.push((m, undo_info));
     }
 
+    /// Passes the turn without moving, for null move pruning. Undone by
+    /// `unmake` like any other move.
+    pub fn make_null(&mut self) {
+        let undo_info = MoveUndoInfo::new(
+            self.bitboard.ep_square,
+            self.bitboard.castling_rights,
+            self.bitboard.promoted,
+            self.halfmove_clock,
+            PieceType::None,
+            self.key,
+            self.pawn_key,
+            self.psqt,
+        );
+        #[cfg(feature = "nnue")]
+        self.accumulators.push();
+
+        self.key ^= ep_key(self.bitboard.ep_square) ^ zobrist::KEYS.black_to_move;
+        self.bitboard.ep_square = 0;
+        self.halfmove_clock += 1;
+        if self.turn() == Colour::Black {
+            self.fullmove_number += 1;
+        }
+        self.moves_played += 1;
+
+        self.stack.push((Move::null(), undo_info));
+    }
+
     pub fn unmake(&mut self) {
         let (last_move, info) = self.stack.pop().unwrap();
-        self.unmake_unchecked(last_move, info);
+        if last_move == Move::null() {
+            self.unmake_null(&info);
+        } else {
+            self.unmake_unchecked(last_move, info);
+        }
+    }
+
+    fn unmake_null(&mut self, info: &MoveUndoInfo) {
+        self.moves_played -= 1;
+        if self.turn() == Colour::Black {
+            self.fullmove_number -= 1;
+        }
+        self.bitboard.ep_square = info.ep_square;
+        self.halfmove_clock = info.halfmove_clock;
+        self.key = info.key;
+        #[cfg(feature = "nnue")]
+        self.accumulators.pop();
     }
 
     fn unmake_unchecked(&mut self, last_move: Move, info: MoveUndoInfo) {
@@ -221,8 +359,12 @@ impl Board {
         let MoveUndoInfo {
             ep_square: old_ep_square,
             castling_rights: old_castling_rights,
+            promoted: old_promoted,
             halfmove_clock: old_halfmove_clock,
-            captured_piece: captured
+            captured_piece: captured,
+            key: old_key,
+            pawn_key: old_pawn_key,
+            psqt: old_psqt,
         } = info;
 
         let from = last_move.from_sq();
@@ -230,11 +372,9 @@ impl Board {
         let from_bb = into_bb(from as usize);
         let to_bb = into_bb(to as usize);
         let from_to_bb = from_bb | to_bb;
-        let piece = self.bitboard.piece_type_at(to as usize);
-
         // promotions
-        let promotion_piece_type = last_move.promotion();
-        if promotion_piece_type != PieceType::None {
+        let piece = if last_move.move_type() == MoveType::Promotion {
+            let promotion_piece_type = last_move.promotion();
             // determine the piece type to remove
             let promo_bb = match promotion_piece_type {
                 Knight => &mut self.bitboard.knights,
@@ -245,8 +385,11 @@ impl Board {
             };
             *promo_bb ^= to_bb;
             // add the pawn back in (on the last rank, we move it later)
-            self.bitboard.pawns ^= from_bb;
-        }
+            self.bitboard.pawns ^= to_bb;
+            Pawn
+        } else {
+            self.bitboard.piece_type_at(to as usize)
+        };
 
         // clear the to_square and set the from_square in the piece bb
         // this cleans up pawns that appear on the last rank
@@ -266,15 +409,9 @@ impl Board {
 
         // castling
         if piece == King {
-            if from_bb == BB_E1 && to_bb == BB_G1 {
-                self.bitboard.rooks ^= BB_H1 | BB_F1;
-            } else if from_bb == BB_E1 && to_bb == BB_C1 {
-                self.bitboard.rooks ^= BB_A1 | BB_D1;
-            } else if from_bb == BB_E8 && to_bb == BB_G8 {
-                self.bitboard.rooks ^= BB_H8 | BB_F8;
-            } else if from_bb == BB_E8 && to_bb == BB_C8 {
-                self.bitboard.rooks ^= BB_A8 | BB_D8;
-            }
+            let rook_from_to_bb = Self::castling_rook_from_to(from_bb, to_bb);
+            self.bitboard.rooks ^= rook_from_to_bb;
+            self.bitboard.occupied_co[1 - self.turn_as_idx()] ^= rook_from_to_bb;
         }
 
         // en passant
@@ -295,11 +432,24 @@ impl Board {
         // en passant square
         self.bitboard.ep_square = old_ep_square;
 
+        // promoted pieces
+        self.bitboard.promoted = old_promoted;
+
         // halfmove clock
         self.halfmove_clock = old_halfmove_clock;
 
+        self.key = old_key;
+        self.pawn_key = old_pawn_key;
+        self.psqt = old_psqt;
+        debug_assert_eq!(self.psqt, Psqt::new(&self.bitboard), "{}", self.fen());
+        #[cfg(feature = "nnue")]
+        {
+            self.accumulators.pop();
+            debug_assert!(self.accumulators.is_fresh(&self.bitboard), "{}", self.fen());
+        }
+
         // fullmove number
-        if self.turn() == Colour::Black {
+        if self.turn() == Colour::White {
             self.fullmove_number -= 1;
         }
 
@@ -307,6 +457,36 @@ impl Board {
         self.moves_played -= 1;
     }
 
+    /// Adds a piece to the terms of the evaluation kept up to date as moves
+    /// are made.
+    fn add_features(&mut self, colour: usize, piece_type: PieceType, square: Square) {
+        self.psqt.add(colour, piece_type, square);
+        #[cfg(feature = "nnue")]
+        self.accumulators.add(colour, piece_type, square);
+    }
+
+    fn remove_features(&mut self, colour: usize, piece_type: PieceType, square: Square) {
+        self.psqt.remove(colour, piece_type, square);
+        #[cfg(feature = "nnue")]
+        self.accumulators.remove(colour, piece_type, square);
+    }
+
+    /// The squares the rook moves between when the king moves from `from_bb`
+    /// to `to_bb`, or nothing if the king move is not castling.
+    const fn castling_rook_from_to(from_bb: u64, to_bb: u64) -> u64 {
+        if from_bb == BB_E1 && to_bb == BB_G1 {
+            BB_H1 | BB_F1
+        } else if from_bb == BB_E1 && to_bb == BB_C1 {
+            BB_A1 | BB_D1
+        } else if from_bb == BB_E8 && to_bb == BB_G8 {
+            BB_H8 | BB_F8
+        } else if from_bb == BB_E8 && to_bb == BB_C8 {
+            BB_A8 | BB_D8
+        } else {
+            0
+        }
+    }
+
     fn get_bb_mut(&mut self, p: PieceType) -> &mut u64 {
         match p {
             PieceType::Pawn => &mut self.bitboard.pawns,
@@ -378,7 +558,12 @@ impl Board {
         self.fullmove_number = fullmove_part as u16;
         self.stack.clear();
         self.moves_played = (fullmove_part as u16 - 1) * 2 + (turn_part == Colour::Black) as u16;
-        
+        self.key = zobrist::hash(&self.bitboard, self.turn_as_idx());
+        self.pawn_key = zobrist::pawn_hash(&self.bitboard);
+        self.psqt = Psqt::new(&self.bitboard);
+        #[cfg(feature = "nnue")]
+        self.accumulators.restart(&self.bitboard);
+
         Ok(())
     }
 
@@ -441,8 +626,8 @@ impl Board {
                 self.set_piece_at(square as usize, piece);
                 square_index += 1;
             } else if c == '~' {
-                todo!();
-                // self.promoted |= into_bb(Square::from_index_180(square_index - 1) as usize);
+                let square = (square_index - 1).flip_180();
+                self.bitboard.promoted.set(square);
             }
         }
 
@@ -464,7 +649,7 @@ impl Board {
         }
     }
 
-    fn board_fen(&self) -> String {
+    fn board_fen(&self, promoted: bool) -> String {
         let mut fen = String::new();
         let mut accumulator = 0;
         for rank in (0..8).rev() {
@@ -487,6 +672,9 @@ impl Board {
                         accumulator = 0;
                     }
                     fen.push(p.symbol());
+                    if promoted && self.bitboard.promoted.test(sq) {
+                        fen.push('~');
+                    }
                 } else {
                     accumulator += 1;
                 }
@@ -498,34 +686,207 @@ impl Board {
         fen
     }
 
-    fn epd(&self) -> String {
-        let ep_square = if self.bitboard.ep_square.any_set() { 
-            Some(self.bitboard.ep_square.lsb()) 
-        } else { 
-            None 
+    /// The en passant square to emit in a FEN/EPD, according to `mode`.
+    fn ep_square_for(&self, mode: EnPassantMode) -> Option<Square> {
+        if self.bitboard.ep_square.none_set() {
+            return None;
+        }
+        let emit = match mode {
+            EnPassantMode::Fen => true,
+            EnPassantMode::XFen => self.en_passant_moves().len() > 0,
+            EnPassantMode::Legal => self.has_legal_en_passant(),
         };
+        if emit {
+            Some(self.bitboard.ep_square.lsb())
+        } else {
+            None
+        }
+    }
+
+    /// Pseudo-legal en passant captures in the current position.
+    fn en_passant_moves(&self) -> MoveBuf {
+        let mut buffer = MoveBuf::new();
+        generate_pseudo_legal_ep(
+            &mut buffer,
+            &self.bitboard,
+            self.turn_as_idx(),
+            BB_ALL,
+            BB_ALL);
+        buffer
+    }
+
+    fn has_legal_en_passant(&self) -> bool {
+        let us = self.turn();
+        self.en_passant_moves().into_iter().any(|&m| {
+            let mut board = self.clone();
+            board.make(m);
+            !board.is_attacked_king(us)
+        })
+    }
+
+    /// Whether the king of colour `colour` is attacked by the opponent.
+    fn is_attacked_king(&self, colour: Colour) -> bool {
+        self.king(colour).is_some_and(|king| self.attackers(colour.flip(), king).any_set())
+    }
+
+    /// Whether the side to move is in check.
+    pub fn is_check(&self) -> bool {
+        self.is_attacked_king(self.turn())
+    }
+
+    /// The square of the king of colour `colour`, if there is one.
+    pub fn king(&self, colour: Colour) -> Option<Square> {
+        let king = self.bitboard.kings & self.bitboard.occupied_co[colour as usize];
+        if king.any_set() {
+            Some(king.lsb())
+        } else {
+            None
+        }
+    }
+
+    /// The set of pieces of colour `colour` that attack `square`.
+    pub fn attackers(&self, colour: Colour, square: Square) -> u64 {
+        attackers_mask(&self.bitboard, colour as usize, square, self.bitboard.occupied())
+    }
+
+    /// The EPD of the position. If `promoted` is set, promoted pieces are
+    /// marked with a trailing `~`, as used by crazyhouse and bughouse.
+    pub fn epd_with(&self, en_passant: EnPassantMode, promoted: bool) -> String {
         let turn_char = if self.turn() == Colour::White { "w" } else { "b" };
-        let castling = if self.bitboard.castling_rights.any_set() { 
-            format!("{}{}{}{}", 
-                if (self.bitboard.castling_rights & BB_A1).any_set() { "K" } else { "" },
-                if (self.bitboard.castling_rights & BB_H1).any_set() { "Q" } else { "" },
-                if (self.bitboard.castling_rights & BB_A8).any_set() { "k" } else { "" },
-                if (self.bitboard.castling_rights & BB_H8).any_set() { "q" } else { "" },
+        let castling = if self.bitboard.castling_rights.any_set() {
+            format!("{}{}{}{}",
+                if (self.bitboard.castling_rights & BB_H1).any_set() { "K" } else { "" },
+                if (self.bitboard.castling_rights & BB_A1).any_set() { "Q" } else { "" },
+                if (self.bitboard.castling_rights & BB_H8).any_set() { "k" } else { "" },
+                if (self.bitboard.castling_rights & BB_A8).any_set() { "q" } else { "" },
             )
         } else {
             "-".to_string()
         };
-        let ep = ep_square.map_or_else(|| "-".to_string(), |ep_square| {
-            format!("{}", ep_square) 
-        });
-        format!("{} {} {} {}", self.board_fen(), turn_char, castling, ep)
+        let ep = self.ep_square_for(en_passant).map_or("-", SquareTrait::name);
+        format!("{} {} {} {}", self.board_fen(promoted), turn_char, castling, ep)
+    }
+
+    pub fn epd(&self) -> String {
+        self.epd_with(EnPassantMode::Legal, false)
+    }
+
+    pub fn fen_with(&self, en_passant: EnPassantMode, promoted: bool) -> String {
+        format!("{} {} {}", self.epd_with(en_passant, promoted), self.halfmove_clock, self.fullmove_number)
     }
 
     pub fn fen(&self) -> String {
-        format!("{} {} {}", self.epd(), self.halfmove_clock, self.fullmove_number)
+        self.fen_with(EnPassantMode::Legal, false)
+    }
+
+    pub fn to_packed(&self) -> PackedBoard {
+        PackedBoard::pack(&self.bitboard, self.turn(), self.halfmove_clock, self.fullmove_number)
+    }
+
+    pub fn from_packed(packed: &PackedBoard) -> Result<Self, &'static str> {
+        let (bitboard, turn, halfmove_clock, fullmove_number) = packed.unpack()?;
+        let fullmove_number = std::cmp::max(1, fullmove_number);
+        Ok(Self {
+            key: zobrist::hash(&bitboard, turn as usize),
+            pawn_key: zobrist::pawn_hash(&bitboard),
+            psqt: Psqt::new(&bitboard),
+            #[cfg(feature = "nnue")]
+            accumulators: Accumulators::new(),
+            bitboard,
+            halfmove_clock,
+            fullmove_number,
+            moves_played: (fullmove_number - 1) * 2 + u16::from(turn == Colour::Black),
+            stack: Vec::new(),
+        })
     }
 
     pub fn legal_moves(&self) -> MoveBuf {
+        let mut buffer = MoveBuf::new();
+        generate_legal_moves(
+            &mut buffer, 
+            &self.bitboard, 
+            self.turn_as_idx(), 
+            BB_ALL, 
+            BB_ALL);
+        buffer
+    }
+
+    /// The type of the piece `m` captures, `PieceType::None` if it is not
+    /// a capture.
+    pub fn captured_piece(&self, m: Move) -> PieceType {
+        if m.move_type() == MoveType::EnPassant {
+            PieceType::Pawn
+        } else if m.move_type() == MoveType::Castling {
+            PieceType::None
+        } else {
+            self.bitboard.piece_type_at(m.to_sq())
+        }
+    }
+
+    pub fn is_capture(&self, m: Move) -> bool {
+        self.captured_piece(m) != PieceType::None
+    }
+
+    /// The legal captures, including en passant, and promotions.
+    pub fn noisy_moves(&self) -> MoveBuf {
+        let us = self.turn_as_idx();
+        let them = self.bitboard.occupied_co[1 ^ us];
+        let promotion_rank = if us == 0 { BB_RANK_8 } else { BB_RANK_1 };
+
+        let mut moves = MoveBuf::new();
+        generate_legal_moves(
+            &mut moves,
+            &self.bitboard,
+            us,
+            BB_ALL,
+            them | self.bitboard.ep_square | promotion_rank);
+
+        let mut buffer = MoveBuf::new();
+        for &m in &moves {
+            if m.is_promotion() || them.test(m.to_sq()) || m.move_type() == MoveType::EnPassant {
+                buffer.push(m);
+            }
+        }
+        buffer
+    }
+
+    /// The legal captures, including en passant, and queen promotions.
+    pub fn tactical_moves(&self) -> MoveBuf {
+        let mut buffer = MoveBuf::new();
+        for &m in &self.noisy_moves() {
+            if !m.is_promotion() || m.promotion() == PieceType::Queen {
+                buffer.push(m);
+            }
+        }
+        buffer
+    }
+
+    /// The legal moves that `noisy_moves` leaves out.
+    pub fn quiet_moves(&self) -> MoveBuf {
+        let us = self.turn_as_idx();
+        let them = self.bitboard.occupied_co[1 ^ us];
+
+        let mut moves = MoveBuf::new();
+        generate_legal_moves(&mut moves, &self.bitboard, us, BB_ALL, !them);
+
+        let mut buffer = MoveBuf::new();
+        for &m in &moves {
+            if !m.is_promotion() && m.move_type() != MoveType::EnPassant {
+                buffer.push(m);
+            }
+        }
+        buffer
+    }
+
+    /// Whether `m` is legal here, with the move type the generator gives
+    /// it. Only the moves of the piece on its from-square are generated.
+    pub fn is_legal(&self, m: Move) -> bool {
+        let mut moves = MoveBuf::new();
+        generate_legal_moves(&mut moves, &self.bitboard, self.turn_as_idx(), into_bb(m.from_sq()), BB_ALL);
+        moves.into_iter().any(|&legal| legal == m)
+    }
+
+    pub fn pseudo_legal_moves(&self) -> MoveBuf {
         let mut buffer = MoveBuf::new();
         generate_pseudo_legal_moves(
             &mut buffer, 
@@ -568,6 +929,78 @@ impl Board {
         };
 
         piece_bb.set(square);
+        self.key = zobrist::hash(&self.bitboard, self.turn_as_idx());
+        self.pawn_key = zobrist::pawn_hash(&self.bitboard);
+        self.psqt = Psqt::new(&self.bitboard);
+        #[cfg(feature = "nnue")]
+        self.accumulators.refresh(&self.bitboard);
+    }
+
+    /// The material and piece-square scores of the position, kept up to
+    /// date as moves are made.
+    pub const fn psqt(&self) -> Psqt {
+        self.psqt
+    }
+
+    /// The first layer of the network evaluating the position, if any.
+    #[cfg(feature = "nnue")]
+    pub const fn accumulators(&self) -> &Accumulators {
+        &self.accumulators
+    }
+
+    /// Evaluates positions with `network` from now on, or with the
+    /// handcrafted evaluation if there is none. The moves played so far can
+    /// still be unmade.
+    #[cfg(feature = "nnue")]
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        let moves = self.move_history().collect::<Vec<_>>();
+        self.unplay_all();
+        self.accumulators.set_network(network, &self.bitboard);
+        for m in moves {
+            if m == Move::null() {
+                self.make_null();
+            } else {
+                self.make(m);
+            }
+        }
+    }
+
+    /// The underlying piece placement and castling/en passant state.
+    pub const fn state(&self) -> &Bitboard {
+        &self.bitboard
+    }
+
+    pub const fn halfmove_clock(&self) -> u8 {
+        self.halfmove_clock
+    }
+
+    /// The Zobrist key of the position.
+    pub const fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// The Zobrist key of the pawns alone.
+    pub const fn pawn_key(&self) -> u64 {
+        self.pawn_key
+    }
+
+    /// A key for the material alone, as `Bitboard::material_key`.
+    pub const fn material_key(&self) -> u64 {
+        self.bitboard.material_key()
+    }
+
+    /// Whether the position has occurred before since the last capture or
+    /// pawn move. Positions before a null move do not count, as they were
+    /// never reached in the game.
+    pub fn is_repetition(&self) -> bool {
+        self.stack
+            .iter()
+            .rev()
+            .take(usize::from(self.halfmove_clock))
+            .take_while(|(m, _)| *m != Move::null())
+            .skip(1)
+            .step_by(2)
+            .any(|(_, info)| info.key == self.key)
     }
 
     pub fn turn(&self) -> Colour {
@@ -609,8 +1042,85 @@ impl Board {
         todo!();
     }
 
-    pub fn make_uci(&mut self, uci: &str) -> Result<(), &'static str> {
+    /// Parses a move in UCI notation, checking that it is legal here.
+    /// The returned move carries the right type for castling, en passant
+    /// and promotions, unlike `Move::from_uci`. The null move `0000` is
+    /// rejected, since it can't be made with `make`.
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, &'static str> {
         let m = Move::from_uci(uci)?;
+        if m == Move::null() {
+            return Err("null move");
+        }
+        let promotion = if m.move_type() == MoveType::Promotion { m.promotion() } else { PieceType::None };
+
+        self.legal_moves()
+            .into_iter()
+            .find(|lm| {
+                let lm_promotion = if lm.move_type() == MoveType::Promotion { lm.promotion() } else { PieceType::None };
+                lm.from_sq() == m.from_sq() && lm.to_sq() == m.to_sq() && lm_promotion == promotion
+            })
+            .copied()
+            .ok_or("illegal uci move")
+    }
+
+    /// Writes `m`, a legal move here, in standard algebraic notation.
+    pub fn san(&mut self, m: Move) -> String {
+        if m == Move::null() {
+            return "--".to_string();
+        }
+
+        let from = m.from_sq();
+        let to = m.to_sq();
+        let piece = self.bitboard.piece_type_at(from);
+        let mut san = String::new();
+        if m.move_type() == MoveType::Castling {
+            san.push_str(if to.file() > from.file() { "O-O" } else { "O-O-O" });
+        } else {
+            let capture = self.is_capture(m);
+            if piece == PieceType::Pawn {
+                if capture {
+                    san.push_str(&from.name()[..1]);
+                }
+            } else {
+                san.push(Piece::new(piece, Colour::White).symbol());
+                // name the file, rank or both, whichever tells the piece
+                // apart from others of its kind that could move there.
+                let others = self
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|o| o.to_sq() == to && o.from_sq() != from && self.bitboard.piece_type_at(o.from_sq()) == piece)
+                    .map(|o| o.from_sq())
+                    .collect::<Vec<_>>();
+                if !others.is_empty() {
+                    if others.iter().all(|&sq| sq.file() != from.file()) {
+                        san.push_str(&from.name()[..1]);
+                    } else if others.iter().all(|&sq| sq.rank() != from.rank()) {
+                        san.push_str(&from.name()[1..]);
+                    } else {
+                        san.push_str(from.name());
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(to.name());
+            if m.move_type() == MoveType::Promotion {
+                san.push('=');
+                san.push(Piece::new(m.promotion(), Colour::White).symbol());
+            }
+        }
+
+        self.make(m);
+        if self.is_check() {
+            san.push(if self.legal_moves().len() == 0 { '#' } else { '+' });
+        }
+        self.unmake();
+        san
+    }
+
+    pub fn make_uci(&mut self, uci: &str) -> Result<(), &'static str> {
+        let m = self.parse_uci(uci)?;
 
         self.make(m);
 
@@ -618,6 +1128,51 @@ impl Board {
     }
 }
 
+/// Boards are serialised as the FEN of their root position, followed by
+/// the moves played from it. A bare FEN string is also accepted.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum BoardRepr {
+    Fen(String),
+    History {
+        fen: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        moves: Vec<Move>,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BoardRepr::History {
+            fen: self.root().fen_with(EnPassantMode::Fen, true),
+            moves: self.move_history().collect(),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let (fen, moves) = match BoardRepr::deserialize(deserializer)? {
+            BoardRepr::Fen(fen) => (fen, Vec::new()),
+            BoardRepr::History { fen, moves } => (fen, moves),
+        };
+        let mut board = Self::from_fen(&fen).map_err(D::Error::custom)?;
+        // the moves may come from anywhere, so each must be legal where it
+        // is played.
+        for m in moves {
+            let legal = board.parse_uci(&m.to_string()).ok();
+            let m = legal.ok_or_else(|| D::Error::custom(format!("illegal move {m} in {}", board.fen())))?;
+            board.make(m);
+        }
+        Ok(board)
+    }
+}
+
 impl Display for Board {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         let mut board = String::with_capacity(8 * 8 * 2 + 8); // ranks * files * 2 for each piece + 8 newlines.
@@ -652,9 +1207,10 @@ impl Debug for Board {
             self.bitboard.occupied_co[1],
             self.bitboard.castling_rights,
             self.bitboard.ep_square,
+            self.bitboard.promoted,
         ];
         let names = [
-            "pawns", "knights", "bishops", "rooks", "queens", "kings", "white", "black", "castling rights", "en passant target square"
+            "pawns", "knights", "bishops", "rooks", "queens", "kings", "white", "black", "castling rights", "en passant target square", "promoted"
         ];
         for (name, bb) in names.iter().zip(bitboards.iter()) {
             writeln!(f, "bb: {}", name)?;
@@ -674,7 +1230,8 @@ impl Debug for Board {
 
 #[cfg(test)]
 mod board_ops {
-    use crate::board::Board;
+    use crate::board::{Board, EnPassantMode};
+    use crate::cmove::Move;
 
     #[test]
     fn board_init_to_fen() {
@@ -682,6 +1239,95 @@ mod board_ops {
         assert_eq!(b.fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
     }
 
+    #[test]
+    fn tactical_moves() {
+        let b = Board::from_fen("1n2k3/P7/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let mut moves = b.tactical_moves().into_iter().map(ToString::to_string).collect::<Vec<_>>();
+        moves.sort();
+        assert_eq!(moves, ["a7a8q", "a7b8q", "e5d6"]);
+    }
+
+    #[test]
+    fn noisy_and_quiet_moves() {
+        for fen in [
+            "r3k2r/1P6/8/3pP3/8/2N3N1/8/R3K2R w KQkq d6 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/8/8/2k5/3Pp3/8/8/4K2R b K d3 0 1",
+            "4k3/8/8/8/8/8/6p1/4K2R w K - 0 1",
+            "3rk3/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+        ] {
+            let b = Board::from_fen(fen).unwrap();
+            let noisy = b.noisy_moves();
+            let quiet = b.quiet_moves();
+            let mut split = noisy.into_iter().chain(&quiet).map(ToString::to_string).collect::<Vec<_>>();
+            let mut legal = b.legal_moves().into_iter().map(ToString::to_string).collect::<Vec<_>>();
+            split.sort();
+            legal.sort();
+            assert_eq!(split, legal, "{fen}");
+            assert!(noisy.into_iter().all(|&m| b.is_capture(m) || m.is_promotion()), "{fen}");
+            assert!(quiet.into_iter().all(|&m| !b.is_capture(m) && !m.is_promotion()), "{fen}");
+            assert!(b.legal_moves().into_iter().all(|&m| b.is_legal(m)), "{fen}");
+        }
+
+        let b = Board::new();
+        assert!(b.is_legal(b.parse_uci("e2e4").unwrap()));
+        assert!(!b.is_legal(Move::from_uci("e2e5").unwrap()));
+        assert!(!b.is_legal(Move::from_uci("e7e5").unwrap()));
+        assert!(!b.is_legal(Move::null()));
+        // the same squares, but not a castling move.
+        let b = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(b.is_legal(b.parse_uci("e1g1").unwrap()));
+        assert!(!b.is_legal(Move::new(4, 6)));
+    }
+
+    #[test]
+    fn san() {
+        let mut b = Board::from_fen("r3k2r/1P6/8/3pP3/8/2N3N1/8/R3K2R w KQkq d6 0 1").unwrap();
+        let cases = [
+            ("e1g1", "O-O"),
+            ("e1c1", "O-O-O"),
+            ("e5d6", "exd6"),
+            ("e5e6", "e6"),
+            ("b7a8q", "bxa8=Q+"),
+            ("b7b8n", "b8=N"),
+            ("c3e4", "Nce4"),
+            ("g3e4", "Nge4"),
+            ("c3d5", "Nxd5"),
+            ("a1a8", "Rxa8+"),
+        ];
+        for (uci, san) in cases {
+            let m = b.parse_uci(uci).unwrap();
+            assert_eq!(b.san(m), san, "{uci}");
+        }
+
+        let mut b = Board::from_fen("6k1/5ppp/8/8/8/8/8/R4RK1 w - - 0 1").unwrap();
+        assert_eq!(b.san(b.parse_uci("a1a8").unwrap()), "Ra8#");
+        assert_eq!(b.san(b.parse_uci("a1e1").unwrap()), "Rae1");
+        let mut b = Board::from_fen("4k3/8/1Q6/8/8/8/1Q3Q2/4K3 w - - 0 1").unwrap();
+        assert_eq!(b.san(b.parse_uci("f2d4").unwrap()), "Qfd4");
+        assert_eq!(b.san(b.parse_uci("b6d4").unwrap()), "Q6d4");
+        assert_eq!(b.san(b.parse_uci("b2d4").unwrap()), "Qb2d4");
+    }
+
+    #[test]
+    fn null_move() {
+        let mut b = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        b.make_null();
+        assert_eq!(b.fen(), "4k3/8/8/3pP3/8/8/8/4K3 b - - 1 1");
+        assert_eq!(b.key(), crate::zobrist::hash(b.state(), b.turn_as_idx()));
+        b.unmake();
+        assert_eq!(b, Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap());
+
+        // returning to the position before a null move is not a repetition.
+        let mut b = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        b.make_null();
+        for uci in ["e8d8", "e1d1", "d8d7", "d1e1", "d7e8"] {
+            b.make_uci(uci).unwrap();
+        }
+        assert_eq!(b.fen(), "4k3/8/8/8/8/8/8/4K3 w - - 6 4");
+        assert!(!b.is_repetition());
+    }
+
     #[test]
     fn from_fen() {
         let b = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
@@ -699,10 +1345,128 @@ mod board_ops {
             assert_eq!(b.fen(), fen, "FAIL - FEN: {}", fen);
         }
     }
+
+    #[test]
+    fn castling_round_trip() {
+        let boards = [
+            "r3k2r/8/8/8/8/8/8/R3K2R",
+            "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+        ];
+        for board_fen in boards {
+            for turn in ["w", "b"] {
+                // every subset of "KQkq", in FEN order.
+                for mask in 0..16 {
+                    let castling = "KQkq"
+                        .chars()
+                        .enumerate()
+                        .filter(|(i, _)| mask & (1 << i) != 0)
+                        .map(|(_, c)| c)
+                        .collect::<String>();
+                    let castling = if castling.is_empty() { "-".to_string() } else { castling };
+                    let fen = format!("{board_fen} {turn} {castling} - 0 1");
+                    let b = Board::from_fen(&fen).unwrap();
+                    assert_eq!(b.fen(), fen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn en_passant_round_trip() {
+        let fens = [
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbqkbnr/ppp1pppp/8/8/2Pp4/5N2/PP1PPPPP/RNBQKB1R b KQkq c3 0 3",
+            "4k3/8/8/8/1pP5/8/8/4K3 b - c3 0 1",
+            "4k3/8/8/2PpP3/8/8/8/4K3 w - d6 0 1",
+        ];
+        for fen in fens {
+            let b = Board::from_fen(fen).unwrap();
+            assert_eq!(b.fen_with(EnPassantMode::Legal, false), fen);
+            assert_eq!(b.fen_with(EnPassantMode::Fen, false), fen);
+            assert_eq!(b.fen_with(EnPassantMode::XFen, false), fen);
+        }
+    }
+
+    #[test]
+    fn en_passant_modes() {
+        // after 1. e4, there is no pawn that can capture on e3.
+        let mut b = Board::new();
+        b.make_uci("e2e4").unwrap();
+        assert_eq!(b.fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        assert_eq!(b.fen_with(EnPassantMode::XFen, false), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+        assert_eq!(b.fen_with(EnPassantMode::Fen, false), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+
+        // exd3 is pseudo-legal, but would expose the king on a4 to the queen on h4.
+        let fen = "8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1";
+        let b = Board::from_fen(fen).unwrap();
+        assert_eq!(b.fen(), "8/8/8/8/k2Pp2Q/8/8/3K4 b - - 0 1");
+        assert_eq!(b.fen_with(EnPassantMode::XFen, false), fen);
+        assert_eq!(b.fen_with(EnPassantMode::Fen, false), fen);
+        assert_eq!(b.epd(), "8/8/8/8/k2Pp2Q/8/8/3K4 b - -");
+        assert_eq!(b.epd_with(EnPassantMode::XFen, false), "8/8/8/8/k2Pp2Q/8/8/3K4 b - d3");
+    }
+
+    #[test]
+    fn promoted_round_trip() {
+        let fen = "r2q~k2r/8/8/8/8/8/8/R1B~1K1N~R w KQkq - 0 1";
+        let b = Board::from_fen(fen).unwrap();
+        assert_eq!(b.fen_with(EnPassantMode::Legal, true), fen);
+        assert_eq!(b.fen(), "r2qk2r/8/8/8/8/8/8/R1B1K1NR w KQkq - 0 1");
+        assert_ne!(b, Board::from_fen("r2qk2r/8/8/8/8/8/8/R1B1K1NR w KQkq - 0 1").unwrap());
+    }
+
+    #[test]
+    fn promoted_invalid() {
+        assert!(Board::from_fen("~7/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Board::from_fen("1~6/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Board::from_fen("Q~~6/8/8/8/8/8/8/8 w - - 0 1").is_err());
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use crate::board::Board;
+
+    #[test]
+    fn board_json() {
+        let board = Board::new();
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, r#"{"fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"}"#);
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+
+        let fen = r#""r3k2r/8/8/8/8/8/8/R3K2R b Kq - 3 20""#;
+        let board = serde_json::from_str::<Board>(fen).unwrap();
+        assert_eq!(board.fen(), "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 3 20");
+    }
+
+    #[test]
+    fn board_json_history() {
+        let mut board = Board::new();
+        for m in ["e2e4", "d7d5", "e4d5", "g8f6"] {
+            board.make_uci(m).unwrap();
+        }
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(
+            json,
+            r#"{"fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1","moves":["e2e4","d7d5","e4d5","g8f6"]}"#
+        );
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+
+        let json = r#"{"fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1","moves":["e7e5"]}"#;
+        assert!(serde_json::from_str::<Board>(json).is_err());
+        // a piece of the side to move, but not a legal move.
+        for moves in [r#"["e1e2"]"#, r#"["e2e5"]"#, r#"["e2e4","e7e5","e1g1"]"#, r#"["0000"]"#] {
+            let json = format!(r#"{{"fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1","moves":{moves}}}"#);
+            let error = serde_json::from_str::<Board>(&json).unwrap_err().to_string();
+            assert!(error.starts_with("illegal move"), "{error}");
+        }
+    }
 }
 
 #[cfg(test)]
 mod move_make {
+    use crate::board::EnPassantMode;
     use crate::cmove::Move;
     use crate::colour::Colour;
     use crate::piece::{PieceType, Piece};
@@ -744,6 +1508,26 @@ mod move_make {
         assert_eq!(board.get_piece_at(G7 as usize), None);
     }
 
+    #[test]
+    fn make_unmake_promoted() {
+        let mut board = Board::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let root = board.clone();
+        board.make_uci("a7a8q").unwrap();
+        assert_eq!(board.fen_with(EnPassantMode::Legal, true), "Q~r2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        board.make_uci("e8d7").unwrap();
+        board.make_uci("a8b8").unwrap();
+        assert_eq!(board.fen_with(EnPassantMode::Legal, true), "1Q~6/3k4/8/8/8/8/8/4K3 b - - 0 2");
+        board.make_uci("d7c6").unwrap();
+        board.make_uci("b8b7").unwrap();
+        assert_eq!(board.fen_with(EnPassantMode::Legal, true), "8/1Q~6/2k5/8/8/8/8/4K3 b - - 2 3");
+        board.make_uci("c6b7").unwrap();
+        assert_eq!(board.fen_with(EnPassantMode::Legal, true), "8/1k6/8/8/8/8/8/4K3 w - - 0 4");
+        for _ in 0..6 {
+            board.unmake();
+        }
+        assert_eq!(board, root);
+    }
+
     #[test]
     fn make_unmake() {
         let moves = vec![
@@ -3,6 +3,7 @@
 use std::fmt::{Display, Error, Formatter};
 
 use crate::{squares::Square, piece::PieceType};
+use crate::evaluation::Psqt;
 
 const VALID_UCI_CHARS: [u8; 8] = *b"abcdefgh";
 const VALID_UCI_NUMS: [u8; 8] = *b"12345678";
@@ -17,6 +18,7 @@ const VALID_UCI_PROMOTIONS: [u8; 4] = *b"qrbn";
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Move(u16);
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u16)]
 pub enum MoveType {
@@ -26,21 +28,34 @@ pub enum MoveType {
     Castling  = 3 << 14
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MoveUndoInfo {
     pub ep_square: u64,
     pub castling_rights: u64,
+    pub promoted: u64,
     pub halfmove_clock: u8,
     pub captured_piece: PieceType,
+    /// The Zobrist key of the position before the move.
+    pub key: u64,
+    /// The key of the pawns before the move.
+    pub pawn_key: u64,
+    /// The material and piece-square scores before the move.
+    pub psqt: Psqt,
 }
 
 impl MoveUndoInfo {
-    pub const fn new(ep_square: u64, castling_rights: u64, halfmove_clock: u8, captured_piece: PieceType) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(ep_square: u64, castling_rights: u64, promoted: u64, halfmove_clock: u8, captured_piece: PieceType, key: u64, pawn_key: u64, psqt: Psqt) -> Self {
         Self {
             ep_square,
             castling_rights,
+            promoted,
             halfmove_clock,
-            captured_piece
+            captured_piece,
+            key,
+            pawn_key,
+            psqt,
         }
     }
 }
@@ -68,6 +83,15 @@ impl Move {
         Self(0)
     }
 
+    /// The move in its packed 16-bit form.
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    pub const fn from_u16(packed: u16) -> Self {
+        Self(packed)
+    }
+
     #[allow(clippy::wrong_self_convention)]
     pub const fn from_sq(self) -> Square {
         ((self.0 >> 6) & 0x3F) as Square
@@ -111,10 +135,13 @@ impl Move {
     }
 
     pub const fn is_promotion(self) -> bool {
-        (self.0 & (3 << 14)) != MoveType::Promotion as u16
+        (self.0 & (3 << 14)) == MoveType::Promotion as u16
     }
 
     pub fn from_uci(uci: &str) -> Result<Self, &'static str> {
+        if uci == "0000" {
+            return Ok(Self::null());
+        }
         if !(uci.len() == 4 || uci.len() == 5) {
             return Err("uci is of an invalid length");
         }
@@ -142,19 +169,18 @@ impl Move {
         let from = (from_file as usize) + (from_rank as usize) * 8;
         let to = (to_file as usize) + (to_rank as usize) * 8;
         
-        let promotion = if uci.len() == 5 {
-            if to_rank != 0 && to_rank != 7 {
-                return Err("uci contains invalid promotion");
-            }
-            match uci[4] {
-                b'n' => PieceType::Knight,
-                b'b' => PieceType::Bishop,
-                b'r' => PieceType::Rook,
-                b'q' => PieceType::Queen,
-                _ => unreachable!(),
-            }
-        } else {
-            PieceType::None
+        if uci.len() == 4 {
+            return Ok(Self::new(from, to));
+        }
+        if to_rank != 0 && to_rank != 7 {
+            return Err("uci contains invalid promotion");
+        }
+        let promotion = match uci[4] {
+            b'n' => PieceType::Knight,
+            b'b' => PieceType::Bishop,
+            b'r' => PieceType::Rook,
+            b'q' => PieceType::Queen,
+            _ => unreachable!(),
         };
         Ok(Self::new_promotion(from, to, promotion))
     }
@@ -168,6 +194,12 @@ impl Display for Move {
         let from_rank = RANKS[self.from_sq() as usize / 8] as char;
         let to_file = FILES[self.to_sq() as usize % 8] as char;
         let to_rank = RANKS[self.to_sq() as usize / 8] as char;
+        if *self == Self::null() {
+            return write!(f, "0000");
+        }
+        if self.move_type() != MoveType::Promotion {
+            return write!(f, "{from_file}{from_rank}{to_file}{to_rank}");
+        }
         let promo = match self.promotion() {
             PieceType::Knight => 'n',
             PieceType::Bishop => 'b',
@@ -179,6 +211,21 @@ impl Display for Move {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Move {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Move {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let uci = String::deserialize(deserializer)?;
+        Self::from_uci(&uci).map_err(serde::de::Error::custom)
+    }
+}
+
 impl std::fmt::Debug for Move {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(f, "{:?} -> {:?}, type: {:?}, promo: {:?}", self.from_sq(), self.to_sq(), self.move_type(), self.promotion())
@@ -224,4 +271,21 @@ mod move_tests {
         let m = Move::from_uci("j2e4");
         assert!(m.is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn uci_display() {
+        for uci in ["e2e4", "a7a8q", "h2h1n", "0000"] {
+            assert_eq!(Move::from_uci(uci).unwrap().to_string(), uci);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn uci_serde() {
+        let moves = vec![Move::from_uci("e2e4").unwrap(), Move::from_uci("b7b8r").unwrap()];
+        let json = serde_json::to_string(&moves).unwrap();
+        assert_eq!(json, r#"["e2e4","b7b8r"]"#);
+        assert_eq!(serde_json::from_str::<Vec<Move>>(&json).unwrap(), moves);
+        assert!(serde_json::from_str::<Move>(r#""e2e9""#).is_err());
+    }
+}
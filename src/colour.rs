@@ -1,5 +1,6 @@
 #![allow(clippy::cast_possible_truncation)]
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Colour {
@@ -10,6 +11,15 @@ pub enum Colour {
 pub const WHITE: usize = 0;
 pub const BLACK: usize = 1;
 
+impl Colour {
+    pub const fn flip(self) -> Self {
+        match self {
+            Self::White => Self::Black,
+            Self::Black => Self::White,
+        }
+    }
+}
+
 impl From<u8> for Colour {
     fn from(colour: u8) -> Self {
         unsafe { std::mem::transmute(colour & 1) }
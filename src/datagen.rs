@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::board::Board;
+use crate::colour::WHITE;
+use crate::packed::{GameResult, PackedRecord, RecordWriter};
+use crate::search::{self, is_mate_score, SearchLimits};
+use crate::transposition::TranspositionTable;
+use crate::zobrist::splitmix64;
+
+const DEFAULT_GAMES: usize = 100;
+const DEFAULT_NODES: u64 = 5000;
+const DEFAULT_RANDOM_PLIES: usize = 8;
+/// Each thread has a small table of its own, cleared for every game.
+const HASH_MB: usize = 8;
+/// Openings whose random plies leave them this far from level are played
+/// again, up to `OPENING_ATTEMPTS` times.
+const MAX_OPENING_SCORE: i32 = 1000;
+const OPENING_ATTEMPTS: usize = 1000;
+/// A game is adjudicated as won once the scores have been at least
+/// `WIN_SCORE` for the same side for `WIN_PLIES` plies in a row.
+const WIN_SCORE: i32 = 2500;
+const WIN_PLIES: usize = 6;
+/// A game is adjudicated as drawn once the scores have been within
+/// `DRAW_SCORE` of level for `DRAW_PLIES` plies in a row, from `DRAW_PLY`
+/// on.
+const DRAW_SCORE: i32 = 10;
+const DRAW_PLIES: usize = 12;
+const DRAW_PLY: usize = 80;
+
+/// How `datagen` plays its games.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatagenOptions {
+    pub games: usize,
+    /// The nodes searched for every move.
+    pub nodes: u64,
+    pub threads: usize,
+    pub seed: u64,
+    /// The random moves played at the start of every game, from the
+    /// starting position or a position picked from the book.
+    pub random_plies: usize,
+    pub book: Vec<String>,
+}
+
+impl Default for DatagenOptions {
+    fn default() -> Self {
+        Self {
+            games: DEFAULT_GAMES,
+            nodes: DEFAULT_NODES,
+            threads: 1,
+            seed: 0,
+            random_plies: DEFAULT_RANDOM_PLIES,
+            book: Vec::new(),
+        }
+    }
+}
+
+/// A small random number generator, so that a seed always gives the same
+/// games.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    const fn next_u64(&mut self) -> u64 {
+        let (state, value) = splitmix64(self.state);
+        self.state = state;
+        value
+    }
+
+    /// A number below `n`, which must not be 0.
+    #[allow(clippy::cast_possible_truncation)]
+    const fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Whether neither side can possibly mate: kings alone, or with a single
+/// knight or bishop between them.
+const fn is_insufficient_material(board: &Board) -> bool {
+    let state = board.state();
+    (state.pawns | state.rooks | state.queens) == 0 && (state.knights | state.bishops).count_ones() <= 1
+}
+
+/// The result of the game if it is over, from the rules alone.
+fn game_over(board: &Board) -> Option<GameResult> {
+    if board.legal_moves().len() == 0 {
+        return Some(if !board.is_check() {
+            GameResult::Draw
+        } else if board.turn_as_idx() == WHITE {
+            GameResult::BlackWin
+        } else {
+            GameResult::WhiteWin
+        });
+    }
+    (board.halfmove_clock() >= 100 || board.is_repetition() || is_insufficient_material(board)).then_some(GameResult::Draw)
+}
+
+/// Plays the random opening of a game, or `None` if it ends the game or
+/// its random plies leave one side too far ahead.
+fn opening(options: &DatagenOptions, rng: &mut Rng, tt: &TranspositionTable) -> Result<Option<Board>, String> {
+    let mut board = if options.book.is_empty() {
+        Board::new()
+    } else {
+        // EPD has no move counters, but a FEN will do too.
+        let fields = options.book[rng.below(options.book.len())].split_whitespace().collect::<Vec<_>>();
+        let counters = fields.iter().skip(4).take(2).take_while(|field| field.parse::<u32>().is_ok()).count();
+        let fen = fields[..fields.len().min(4 + counters)].join(" ");
+        Board::from_fen(&fen).map_err(|e| format!("invalid book position: {e}"))?
+    };
+    for _ in 0..options.random_plies {
+        let moves = board.legal_moves();
+        if moves.len() == 0 {
+            return Ok(None);
+        }
+        let m = moves[rng.below(moves.len())];
+        board.make(m);
+    }
+
+    // a book position is played as it is, however lopsided.
+    if options.random_plies == 0 {
+        return Ok(game_over(&board).is_none().then_some(board));
+    }
+    let score = search::search(&board, SearchLimits::nodes(options.nodes), tt, |_| {}).score;
+    Ok((game_over(&board).is_none() && score.abs() <= MAX_OPENING_SCORE).then_some(board))
+}
+
+/// Plays out a game from a random opening, returning the quiet positions
+/// along the way with the search scores and the result.
+fn play_game(options: &DatagenOptions, rng: &mut Rng, tt: &TranspositionTable) -> Result<Vec<PackedRecord>, String> {
+    tt.clear();
+    let mut board = None;
+    for _ in 0..OPENING_ATTEMPTS {
+        board = opening(options, rng, tt)?;
+        if board.is_some() {
+            break;
+        }
+    }
+    let mut board = board.ok_or("no playable openings")?;
+
+    // positions with their scores from white's point of view.
+    let mut positions = Vec::new();
+    let (mut win_plies, mut draw_plies) = (0, 0);
+    let mut last_score: i32 = 0;
+    let mut ply = 0;
+    let result = loop {
+        if let Some(result) = game_over(&board) {
+            break result;
+        }
+
+        let found = search::search(&board, SearchLimits::nodes(options.nodes), tt, |_| {});
+        let score = if board.turn_as_idx() == WHITE { found.score } else { -found.score };
+
+        win_plies = if score.abs() >= WIN_SCORE && score.signum() == last_score.signum() { win_plies + 1 } else { 0 };
+        draw_plies = if score.abs() <= DRAW_SCORE { draw_plies + 1 } else { 0 };
+        last_score = score;
+        if win_plies >= WIN_PLIES {
+            break if score > 0 { GameResult::WhiteWin } else { GameResult::BlackWin };
+        }
+        if draw_plies >= DRAW_PLIES && ply >= DRAW_PLY {
+            break GameResult::Draw;
+        }
+
+        // the score of a position in check or with a capture to come says
+        // little about the position itself.
+        if !board.is_check() && !board.is_capture(found.best_move) && !is_mate_score(found.score) {
+            positions.push((board.to_packed(), score));
+        }
+        board.make(found.best_move);
+        ply += 1;
+    };
+
+    Ok(positions
+        .into_iter()
+        .map(|(packed, score)| PackedRecord::new(packed, i16::try_from(score).unwrap_or_default(), result))
+        .collect())
+}
+
+/// Plays `options.games` games, writing the positions from them to `out`
+/// in the order the games were started. Returns the number of positions.
+pub fn generate(options: &DatagenOptions, out: impl Write) -> Result<usize, String> {
+    let mut writer = RecordWriter::new(out);
+    let threads = options.threads.clamp(1, options.games.max(1));
+    let (sender, receiver) = mpsc::channel();
+    let mut written = 0;
+
+    thread::scope(|scope| {
+        for thread_id in 0..threads {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                let tt = TranspositionTable::new(HASH_MB);
+                for game in (thread_id..options.games).step_by(threads) {
+                    // every game has a seed of its own, so that the games
+                    // are the same however they are shared out.
+                    let mut rng = Rng::new(splitmix64(options.seed ^ game as u64).1);
+                    if sender.send((game, play_game(options, &mut rng, &tt))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // games finished ahead of their turn wait to be written.
+        let mut finished = BTreeMap::new();
+        let mut next = 0;
+        for (game, records) in receiver {
+            finished.insert(game, records?);
+            while let Some(records) = finished.remove(&next) {
+                for record in &records {
+                    writer.write(record).map_err(|e| e.to_string())?;
+                }
+                written += records.len();
+                next += 1;
+            }
+        }
+        writer.flush().map_err(|e| e.to_string())
+    })?;
+    Ok(written)
+}
+
+/// Runs the `datagen` subcommand: `datagen <output> [--games N] [--nodes N]
+/// [--threads N] [--seed N] [--random-plies N] [--book FILE]`, appending
+/// packed records to the output file.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: datagen <output> [--games N] [--nodes N] [--threads N] [--seed N] [--random-plies N] [--book FILE]";
+    let path = args.first().ok_or(usage)?;
+    let mut options = DatagenOptions {
+        threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        ..DatagenOptions::default()
+    };
+    let mut args = args[1..].iter();
+    while let Some(option) = args.next() {
+        let value = args.next().ok_or(usage)?;
+        let invalid = || format!("invalid value for {option}: {value}");
+        match option.as_str() {
+            "--games" => options.games = value.parse().map_err(|_| invalid())?,
+            "--nodes" => options.nodes = value.parse().map_err(|_| invalid())?,
+            "--threads" => options.threads = value.parse().map_err(|_| invalid())?,
+            "--seed" => options.seed = value.parse().map_err(|_| invalid())?,
+            "--random-plies" => options.random_plies = value.parse().map_err(|_| invalid())?,
+            "--book" => {
+                let book = fs::read_to_string(value).map_err(|e| format!("{value}: {e}"))?;
+                options.book = book.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect();
+            }
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let file = File::options().create(true).append(true).open(path).map_err(|e| format!("{path}: {e}"))?;
+    let positions = generate(&options, BufWriter::new(file))?;
+    eprintln!("{} games, {positions} positions written to {path}", options.games);
+    Ok(())
+}
+
+#[cfg(test)]
+mod datagen_tests {
+    use std::io::Cursor;
+
+    use crate::board::Board;
+    use crate::datagen::{game_over, generate, DatagenOptions};
+    use crate::packed::{GameResult, RecordReader};
+
+    #[test]
+    fn games_over() {
+        for (fen, result) in [
+            ("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", Some(GameResult::WhiteWin)),
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Some(GameResult::Draw)),
+            ("7k/8/6K1/8/8/8/8/5B2 b - - 0 1", Some(GameResult::Draw)),
+            ("7k/8/6K1/8/8/8/8/4BB2 b - - 0 1", None),
+            ("7k/8/6K1/8/8/8/8/5B2 b - - 100 80", Some(GameResult::Draw)),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", None),
+        ] {
+            assert_eq!(game_over(&Board::from_fen(fen).unwrap()), result, "{fen}");
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let options = DatagenOptions { games: 3, nodes: 300, threads: 2, seed: 7, ..DatagenOptions::default() };
+        let mut first = Vec::new();
+        let written = generate(&options, &mut first).unwrap();
+        assert!(written > 0);
+        assert_eq!(first.len(), written * 32);
+
+        // the same games, however the threads share them out.
+        let mut second = Vec::new();
+        generate(&DatagenOptions { threads: 1, ..options.clone() }, &mut second).unwrap();
+        assert!(first == second);
+
+        for record in RecordReader::new(Cursor::new(first)) {
+            let record = record.unwrap();
+            let board = Board::from_packed(&record.board).unwrap();
+            assert!(!board.is_check(), "{}", board.fen());
+        }
+
+        // without random plies, book positions are played however lopsided.
+        for (fen, ops) in [
+            ("4k3/8/8/8/8/8/4P3/4K3 w - -", "bm e4; id \"pawn\";"),
+            ("4k3/8/8/8/8/8/8/QQ2K3 w - -", "id \"queens\";"),
+        ] {
+            let book = vec![format!("{fen} {ops}")];
+            let options = DatagenOptions { games: 1, nodes: 200, random_plies: 0, book, ..DatagenOptions::default() };
+            let mut out = Vec::new();
+            generate(&options, &mut out).unwrap();
+            let first = RecordReader::new(Cursor::new(out)).next().unwrap().unwrap();
+            assert_eq!(Board::from_packed(&first.board).unwrap().fen(), format!("{fen} 0 1"));
+        }
+    }
+}
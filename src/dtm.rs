@@ -0,0 +1,934 @@
+#![allow(clippy::cast_possible_truncation)]
+
+//! Distance-to-mate tablebases for up to five pieces, or four with pawns,
+//! generated here by retrograde analysis and saved in a compact format of
+//! our own. The generator works back from the mates, and from the
+//! positions captures and promotions lead to in the smaller tables, one
+//! ply at a time. Castling is left out, and the fifty-move rule is
+//! ignored. Only one side may have pawns, so en passant never comes up.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::path::Path;
+
+use crate::bitboards::Bitboard;
+use crate::bitmethods::Bithackable;
+use crate::board::Board;
+use crate::cmove::Move;
+use crate::colour::{Colour, BLACK, WHITE};
+use crate::evaluation::PIECE_VALUES;
+use crate::magicnumbers::{BB_ALL, BB_BACKRANKS, BB_PAWN_ATTACKS};
+use crate::movebuffer::MoveBuf;
+use crate::movegen::{attackers_mask, attacks_mask, generate_legal_moves};
+use crate::piece::{Piece, PieceType};
+use crate::squares::{Square, SquareTrait};
+use crate::tablebase::parse_material;
+
+const MAGIC: [u8; 4] = *b"IDTM";
+const VERSION: u8 = 1;
+/// The most pieces a table can have.
+pub const MAX_PIECES: usize = 5;
+/// The most pieces a table with pawns can have. Five would take 1 GiB for
+/// the table, and as much again for the counts kept while generating it.
+const MAX_PAWN_PIECES: usize = 4;
+
+/// The value of a position that is drawn, or not yet known to be won or
+/// lost. Other values are one more than the plies to mate, so odd values
+/// are losses for the side to move and even values wins.
+const DRAW: u8 = 0;
+/// The value of an index that is not a legal position, or not the index
+/// a position is kept under.
+const INVALID: u8 = u8::MAX;
+/// The most plies to mate a value can hold.
+const MAX_PLIES: u32 = INVALID as u32 - 2;
+
+/// The squares of the a1-d1-d4 triangle, one of which the white king is
+/// turned onto in tables without pawns.
+const TRIANGLE: [Square; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+/// The kinds of piece a capture can take, or a pawn promote to.
+const CAPTURABLE: [PieceType; 5] = [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen];
+
+/// The result of a position with best play, in plies to mate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Draw,
+    Loss(u32),
+}
+
+impl Dtm {
+    fn from_value(value: u8) -> Self {
+        match value {
+            DRAW => Self::Draw,
+            value if value.is_multiple_of(2) => Self::Win(u32::from(value) - 1),
+            value => Self::Loss(u32::from(value) - 1),
+        }
+    }
+}
+
+const fn value(plies: u32) -> u8 {
+    plies as u8 + 1
+}
+
+/// The material key of pieces, as `Bitboard::material_key`, with the
+/// colours swapped if `swap` is set.
+fn key_of(pieces: impl Iterator<Item = (usize, PieceType)>, swap: bool) -> u64 {
+    pieces.map(|(colour, piece_type)| 1 << (4 * (6 * (colour ^ usize::from(swap)) + piece_type as usize - 1))).sum()
+}
+
+/// Turns a square with some of the eight symmetries of the board: bit 0
+/// mirrors the files, bit 1 the ranks, and bit 2 the long diagonal.
+const fn transform(symmetry: usize, mut square: Square) -> Square {
+    if symmetry & 4 != 0 {
+        square = square >> 3 | (square & 7) << 3;
+    }
+    if symmetry & 1 != 0 {
+        square ^= 7;
+    }
+    if symmetry & 2 != 0 {
+        square ^= 0x38;
+    }
+    square
+}
+
+/// The pieces of a table: the white king, the black king, then white's
+/// other pieces and black's, strongest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Material {
+    pieces: Vec<(usize, PieceType)>,
+}
+
+impl Material {
+    /// The material with the pieces `white` and `black`, with the colours
+    /// swapped if black is the stronger side.
+    fn new(white: &[PieceType], black: &[PieceType]) -> Self {
+        let side = |pieces: &[PieceType]| {
+            let mut pieces: Vec<_> = pieces.iter().copied().filter(|&piece_type| piece_type != PieceType::King).collect();
+            pieces.sort_by_key(|&piece_type| Reverse(piece_type as usize));
+            pieces
+        };
+        let strength = |pieces: &[PieceType]| {
+            let value = pieces.iter().map(|&piece_type| PIECE_VALUES[piece_type as usize]).sum::<i32>();
+            (value, pieces.iter().map(|&piece_type| piece_type as usize).collect::<Vec<_>>())
+        };
+        let (mut white, mut black) = (side(white), side(black));
+        if strength(&black) > strength(&white) {
+            (white, black) = (black, white);
+        }
+        let mut pieces = vec![(WHITE, PieceType::King), (BLACK, PieceType::King)];
+        pieces.extend(white.into_iter().map(|piece_type| (WHITE, piece_type)));
+        pieces.extend(black.into_iter().map(|piece_type| (BLACK, piece_type)));
+        Self { pieces }
+    }
+
+    /// The material of a table named like `KRPvKR`.
+    fn parse(name: &str) -> Result<Self, String> {
+        let [white, black] = parse_material(name).ok_or_else(|| format!("invalid material: {name}"))?;
+        if white.len() + black.len() > MAX_PIECES {
+            return Err(format!("{name} has more than {MAX_PIECES} pieces"));
+        }
+        let material = Self::new(&white, &black);
+        if material.has_pawns() && material.pieces.len() > MAX_PAWN_PIECES {
+            return Err(format!("{name} has pawns and more than {MAX_PAWN_PIECES} pieces"));
+        }
+        if [WHITE, BLACK].iter().all(|&colour| material.pieces.contains(&(colour, PieceType::Pawn))) {
+            return Err(format!("{name} has pawns on both sides"));
+        }
+        Ok(material)
+    }
+
+    fn name(&self) -> String {
+        let side = |colour: usize| {
+            let colour_of = if colour == WHITE { Colour::White } else { Colour::Black };
+            self.pieces
+                .iter()
+                .filter(|&&(c, _)| c == colour)
+                .map(|&(_, piece_type)| Piece::new(piece_type, colour_of).symbol().to_ascii_uppercase())
+                .collect::<String>()
+        };
+        format!("{}v{}", side(WHITE), side(BLACK))
+    }
+
+    fn key(&self) -> u64 {
+        key_of(self.pieces.iter().copied(), false)
+    }
+
+    /// Whether both sides have the same pieces.
+    fn symmetric(&self) -> bool {
+        self.key() == key_of(self.pieces.iter().copied(), true)
+    }
+
+    /// Whether there is too little material for either side to mate, so
+    /// every position is a draw.
+    fn drawn(&self) -> bool {
+        match self.pieces[2..] {
+            [] => true,
+            [(_, piece_type)] => piece_type == PieceType::Knight || piece_type == PieceType::Bishop,
+            _ => false,
+        }
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|&(_, piece_type)| piece_type == PieceType::Pawn)
+    }
+
+    /// How many indices the table has: the side to move, then the white
+    /// king on one of the triangle's squares, or on the a-d files if there
+    /// are pawns, then any square for each other piece.
+    fn size(&self) -> usize {
+        let king_squares = if self.has_pawns() { 32 } else { TRIANGLE.len() };
+        king_squares * 64usize.pow(self.pieces.len() as u32 - 1) * 2
+    }
+
+    /// The materials one capture or promotion away.
+    fn conversions(&self) -> Vec<Self> {
+        let mut conversions: Vec<Self> = Vec::new();
+        let mut add = |pieces: &[(usize, PieceType)]| {
+            let side = |colour: usize| pieces.iter().filter(|&&(c, _)| c == colour).map(|&(_, piece_type)| piece_type).collect::<Vec<_>>();
+            let material = Self::new(&side(WHITE), &side(BLACK));
+            if !conversions.contains(&material) {
+                conversions.push(material);
+            }
+        };
+        for i in 2..self.pieces.len() {
+            let mut pieces = self.pieces.clone();
+            pieces.remove(i);
+            add(&pieces);
+            if self.pieces[i].1 == PieceType::Pawn {
+                for promotion in &CAPTURABLE[1..] {
+                    pieces.clone_from(&self.pieces);
+                    pieces[i].1 = *promotion;
+                    add(&pieces);
+                }
+            }
+        }
+        conversions
+    }
+}
+
+/// A position as a list of pieces, for moving between tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Position {
+    pieces: Vec<(usize, PieceType, Square)>,
+    stm: usize,
+}
+
+impl Position {
+    fn from_board(board: &Board) -> Self {
+        let state = board.state();
+        let pieces = state
+            .occupied()
+            .iter_bits()
+            .map(|square| {
+                let colour = if state.occupied_co[WHITE].test(square) { WHITE } else { BLACK };
+                (colour, state.piece_type_at(square), square)
+            })
+            .collect();
+        Self { pieces, stm: board.turn_as_idx() }
+    }
+
+    fn bitboard(&self) -> Bitboard {
+        let mut state = Bitboard::clear();
+        for &(colour, piece_type, square) in &self.pieces {
+            let pieces = match piece_type {
+                PieceType::Pawn => &mut state.pawns,
+                PieceType::Knight => &mut state.knights,
+                PieceType::Bishop => &mut state.bishops,
+                PieceType::Rook => &mut state.rooks,
+                PieceType::Queen => &mut state.queens,
+                PieceType::King | PieceType::None => &mut state.kings,
+            };
+            pieces.set(square);
+            state.occupied_co[colour].set(square);
+        }
+        state
+    }
+
+    fn key(&self, swap: bool) -> u64 {
+        key_of(self.pieces.iter().map(|&(colour, piece_type, _)| (colour, piece_type)), swap)
+    }
+
+    /// The same position with the colours swapped and the board turned
+    /// around.
+    fn swapped(&self) -> Self {
+        let pieces = self.pieces.iter().map(|&(colour, piece_type, square)| (colour ^ 1, piece_type, square.flip_180())).collect();
+        Self { pieces, stm: self.stm ^ 1 }
+    }
+
+    fn transformed(&self, symmetry: usize) -> Self {
+        let pieces = self.pieces.iter().map(|&(colour, piece_type, square)| (colour, piece_type, transform(symmetry, square))).collect();
+        Self { pieces, stm: self.stm }
+    }
+
+    fn legal_moves(&self) -> MoveBuf {
+        let mut moves = MoveBuf::new();
+        generate_legal_moves(&mut moves, &self.bitboard(), self.stm, BB_ALL, BB_ALL);
+        moves
+    }
+
+    /// Whether the king of `colour` is in check.
+    fn in_check(&self, colour: usize) -> bool {
+        let state = self.bitboard();
+        let king = (state.kings & state.occupied_co[colour]).lsb();
+        attackers_mask(&state, colour ^ 1, king, state.occupied()).any_set()
+    }
+
+    /// The position after the legal move `m`.
+    fn after(&self, m: Move) -> Self {
+        let (from, to) = (m.from_sq(), m.to_sq());
+        let mut pieces: Vec<_> = self.pieces.iter().copied().filter(|&(_, _, square)| square != to).collect();
+        for piece in &mut pieces {
+            if piece.2 == from {
+                piece.2 = to;
+                if m.is_promotion() {
+                    piece.1 = m.promotion();
+                }
+            }
+        }
+        Self { pieces, stm: self.stm ^ 1 }
+    }
+
+    /// The positions the side not to move could have reached this one
+    /// from: quiet un-moves, or with a material `into`, the un-captures
+    /// and un-promotions that lead to it. Some of them may be illegal, with
+    /// the side to move in check.
+    fn unmoves(&self, into: Option<&Material>) -> Vec<Self> {
+        let mover = self.stm ^ 1;
+        let key = self.key(false);
+        let keys = into.map(|material| [material.key(), key_of(material.pieces.iter().copied(), true)]);
+        let fits = |key: u64| keys.is_some_and(|keys| keys.contains(&key));
+        let bit = |colour: usize, piece_type: PieceType| key_of([(colour, piece_type)].into_iter(), false);
+        let (forward, last_rank): (isize, usize) = if mover == WHITE { (8, 7) } else { (-8, 0) };
+        // a promotion leaves the new piece on the last rank.
+        let promoted = self.pieces.iter().any(|&(colour, piece_type, square)| {
+            colour == mover && piece_type != PieceType::Pawn && piece_type != PieceType::King && square.rank() == last_rank
+        });
+        if into.is_some_and(|material| material.pieces.len() == self.pieces.len()) && !promoted {
+            return Vec::new();
+        }
+        let mut parents = Vec::new();
+        let mut add = |i: usize, from: Square, piece_type: PieceType, restored: Option<PieceType>| {
+            let mut pieces = self.pieces.clone();
+            pieces[i].1 = piece_type;
+            pieces[i].2 = from;
+            if let Some(restored) = restored {
+                pieces.push((self.stm, restored, self.pieces[i].2));
+            }
+            parents.push(Self { pieces, stm: mover });
+        };
+
+        let state = self.bitboard();
+        let empty = !state.occupied();
+        for (i, &(colour, piece_type, to)) in self.pieces.iter().enumerate() {
+            if colour != mover {
+                continue;
+            }
+            let behind = to.wrapping_add_signed(-forward);
+            let froms = if piece_type != PieceType::Pawn {
+                attacks_mask(&state, to) & empty
+            } else if into.is_some() {
+                // pawns capture diagonally, and never from the back ranks.
+                BB_PAWN_ATTACKS[mover ^ 1][to] & empty & !BB_BACKRANKS
+            } else {
+                let mut froms = 0;
+                if empty.test(behind) && !BB_BACKRANKS.test(behind) {
+                    froms.set(behind);
+                    let double = behind.wrapping_add_signed(-forward);
+                    if to.rank() == last_rank.abs_diff(4) && empty.test(double) {
+                        froms.set(double);
+                    }
+                }
+                froms
+            };
+
+            for from in froms.iter_bits() {
+                if into.is_none() {
+                    add(i, from, piece_type, None);
+                }
+                for restored in CAPTURABLE {
+                    if fits(key + bit(self.stm, restored)) && (restored != PieceType::Pawn || !BB_BACKRANKS.test(to)) {
+                        add(i, from, piece_type, Some(restored));
+                    }
+                }
+            }
+
+            if piece_type != PieceType::Pawn && piece_type != PieceType::King && to.rank() == last_rank {
+                let key = key - bit(mover, piece_type) + bit(mover, PieceType::Pawn);
+                if fits(key) && empty.test(behind) {
+                    add(i, behind, PieceType::Pawn, None);
+                }
+                for from in (BB_PAWN_ATTACKS[mover ^ 1][to] & empty).iter_bits() {
+                    for &restored in &CAPTURABLE[1..] {
+                        if fits(key + bit(self.stm, restored)) {
+                            add(i, from, PieceType::Pawn, Some(restored));
+                        }
+                    }
+                }
+            }
+        }
+        parents
+    }
+}
+
+/// The values of every position with some material, by index.
+struct Table {
+    material: Material,
+    key: u64,
+    values: Vec<u8>,
+}
+
+impl Table {
+    /// The index of the pieces on `squares`, in the order of the material,
+    /// once the board is turned to put the white king in its part of it.
+    /// Of the ways to do that, and to order pieces of the same kind, the
+    /// lowest index is the one the position is kept under.
+    fn index(&self, squares: &[Square], stm: usize) -> usize {
+        let pawns = self.material.has_pawns();
+        let symmetries = if pawns { 2 } else { 8 };
+        (0..symmetries)
+            .filter_map(|symmetry| {
+                let king = transform(symmetry, squares[0]);
+                let king = if pawns { (king.file() < 4).then(|| king.rank() * 4 + king.file())? } else { TRIANGLE.iter().position(|&square| square == king)? };
+                let mut squares: [Square; MAX_PIECES] = std::array::from_fn(|i| squares.get(i).map_or(0, |&square| transform(symmetry, square)));
+                let squares = &mut squares[..self.material.pieces.len()];
+                let mut start = 2;
+                while start < squares.len() {
+                    let piece = self.material.pieces[start];
+                    let end = start + self.material.pieces[start..].iter().take_while(|&&p| p == piece).count();
+                    squares[start..end].sort_unstable();
+                    start = end;
+                }
+                Some(stm * self.values.len() / 2 + squares[1..].iter().fold(king, |idx, &square| idx * 64 + square))
+            })
+            .min()
+            .expect("some symmetry puts the white king in place")
+    }
+
+    /// The position kept under `idx`, if there is one.
+    fn position(&self, idx: usize) -> Option<Position> {
+        let count = self.material.pieces.len();
+        let mut squares = [0; MAX_PIECES];
+        let squares = &mut squares[..count];
+        let half = self.values.len() / 2;
+        let mut rest = idx % half;
+        for square in squares[1..].iter_mut().rev() {
+            *square = rest % 64;
+            rest /= 64;
+        }
+        squares[0] = if self.material.has_pawns() { rest / 4 * 8 + rest % 4 } else { TRIANGLE[rest] };
+
+        if self.index(squares, idx / half) != idx {
+            return None;
+        }
+        let pieces: Vec<_> = self.material.pieces.iter().zip(squares.iter()).map(|(&(colour, piece_type), &square)| (colour, piece_type, square)).collect();
+        let position = Position { pieces, stm: idx / half };
+        let state = position.bitboard();
+        let legal = state.occupied().popcount() == count
+            && (state.pawns & BB_BACKRANKS).none_set()
+            && !position.in_check(position.stm ^ 1);
+        legal.then_some(position)
+    }
+
+    /// Where `position` is kept, if it has the table's material.
+    fn locate(&self, position: &Position) -> Option<usize> {
+        let swap = if position.key(false) == self.key {
+            false
+        } else if position.key(true) == self.key {
+            true
+        } else {
+            return None;
+        };
+        // with the colours swapped, as `Position::swapped` has them.
+        let flip = if swap { 0x38 } else { 0 };
+        let mut taken = [false; MAX_PIECES];
+        let mut squares = [0; MAX_PIECES];
+        for (square, &(colour, piece_type)) in squares.iter_mut().zip(&self.material.pieces) {
+            let i = (0..position.pieces.len())
+                .find(|&i| !taken[i] && position.pieces[i].0 ^ usize::from(swap) == colour && position.pieces[i].1 == piece_type)
+                .expect("the keys match");
+            taken[i] = true;
+            *square = position.pieces[i].2 ^ flip;
+        }
+        Some(self.index(&squares[..self.material.pieces.len()], position.stm ^ usize::from(swap)))
+    }
+
+    /// The wins, draws and losses for the side to move, and the longest
+    /// mate in plies.
+    fn summary(&self) -> String {
+        let (mut wins, mut draws, mut losses, mut longest) = (0, 0, 0, 0);
+        for &value in &self.values {
+            match value {
+                INVALID => {}
+                DRAW => draws += 1,
+                value => {
+                    if value.is_multiple_of(2) {
+                        wins += 1;
+                    } else {
+                        losses += 1;
+                    }
+                    longest = longest.max(u32::from(value) - 1);
+                }
+            }
+        }
+        format!("{}: {wins} wins, {draws} draws, {losses} losses, longest mate {longest} plies", self.material.name())
+    }
+
+    /// The table as a file: the magic and version, the length and name of
+    /// the material, then the values in runs, each a value and the length
+    /// of the run as a LEB128 number. Indices that hold no position may
+    /// read back with any value.
+    fn to_bytes(&self) -> Vec<u8> {
+        let name = self.material.name();
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(name.len() as u8);
+        bytes.extend(name.bytes());
+        // indices that hold no position are never looked at, so they can
+        // take whichever value makes the runs longest.
+        let mut values = self.values.clone();
+        for i in 1..values.len() {
+            if values[i] == INVALID {
+                values[i] = values[i - 1];
+            }
+        }
+        for run in values.chunk_by(|a, b| a == b) {
+            bytes.push(run[0]);
+            let mut length = run.len();
+            while length >= 0x80 {
+                bytes.push(length as u8 | 0x80);
+                length >>= 7;
+            }
+            bytes.push(length as u8);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 6 || bytes[..4] != MAGIC {
+            return Err("not a distance-to-mate table".to_string());
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("unsupported version {}", bytes[4]));
+        }
+        let end = 6 + usize::from(bytes[5]);
+        let name = bytes.get(6..end).and_then(|name| std::str::from_utf8(name).ok()).ok_or("invalid material")?;
+        let material = Material::parse(name)?;
+        if material.name() != name {
+            return Err(format!("invalid material: {name}"));
+        }
+
+        let size = material.size();
+        let mut values = Vec::with_capacity(size);
+        let mut bytes = bytes[end..].iter();
+        while let Some(&value) = bytes.next() {
+            let (mut length, mut shift) = (0, 0);
+            loop {
+                let &byte = bytes.next().ok_or("truncated table")?;
+                length |= usize::from(byte & 0x7F) << shift;
+                shift += 7;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            if values.len() + length > size {
+                return Err("too many values".to_string());
+            }
+            values.resize(values.len() + length, value);
+        }
+        if values.len() != size {
+            return Err("truncated table".to_string());
+        }
+        Ok(Self { key: material.key(), material, values })
+    }
+}
+
+/// A set of distance-to-mate tables, loaded from the directories given to
+/// `Tables::new` or generated.
+#[derive(Default)]
+pub struct Tables {
+    /// Tables by the material key of the colouring they were made for.
+    tables: HashMap<u64, Table>,
+    max_pieces: usize,
+}
+
+impl Debug for Tables {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tables").field("tables", &self.tables.len()).field("max_pieces", &self.max_pieces).finish()
+    }
+}
+
+impl Tables {
+    /// Loads every `.dtm` file in `paths`, a list of directories separated
+    /// as in `PATH`.
+    pub fn new(paths: &str) -> Result<Self, String> {
+        let mut tables = Self::default();
+        for dir in env::split_paths(paths).filter(|dir| !dir.as_os_str().is_empty()) {
+            let entries = fs::read_dir(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+            for entry in entries {
+                let path = entry.map_err(|e| format!("{}: {e}", dir.display()))?.path();
+                if path.extension().is_none_or(|extension| extension != "dtm") {
+                    continue;
+                }
+                let bytes = fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+                tables.insert(Table::from_bytes(&bytes).map_err(|e| format!("{}: {e}", path.display()))?);
+            }
+        }
+        Ok(tables)
+    }
+
+    fn insert(&mut self, table: Table) {
+        self.max_pieces = self.max_pieces.max(table.material.pieces.len());
+        self.tables.insert(table.key, table);
+    }
+
+    /// The most pieces of any table.
+    pub const fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// The value of `position`, if there is a table for it.
+    fn value(&self, position: &Position) -> Option<u8> {
+        let table = self.tables.get(&position.key(false)).or_else(|| self.tables.get(&position.key(true)))?;
+        Some(table.values[table.locate(position)?])
+    }
+
+    /// The result of `board` with best play, if there is a table for it.
+    /// Positions with castling rights are never in the tables.
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        let state = board.state();
+        if state.castling_rights.any_set() || state.occupied().popcount() > self.max_pieces {
+            return None;
+        }
+        self.value(&Position::from_board(board)).map(Dtm::from_value)
+    }
+
+    /// Generates the table named `name`, and the tables it converts into
+    /// that are missing, returning the names of the new tables.
+    pub fn generate(&mut self, name: &str) -> Result<Vec<String>, String> {
+        let mut generated = Vec::new();
+        self.generate_material(Material::parse(name)?, &mut generated)?;
+        Ok(generated)
+    }
+
+    fn generate_material(&mut self, material: Material, generated: &mut Vec<String>) -> Result<(), String> {
+        if self.tables.contains_key(&material.key()) {
+            return Ok(());
+        }
+        for conversion in material.conversions() {
+            self.generate_material(conversion, generated)?;
+        }
+        generated.push(material.name());
+        let table = self.retrograde(material)?;
+        self.insert(table);
+        Ok(())
+    }
+
+    /// Works out a table whose conversions are all in `self`. The mates are
+    /// found first, then each pass goes back a ply from the positions
+    /// settled in the pass before, here and in the smaller tables: a move
+    /// into a loss wins, and a position is lost once every entry its moves
+    /// lead to is.
+    fn retrograde(&self, material: Material) -> Result<Table, String> {
+        let mut table = Table { key: material.key(), values: vec![INVALID; material.size()], material };
+        let smaller: Vec<&Table> = table.material.conversions().iter().map(|material| &self.tables[&material.key()]).collect();
+
+        // symmetric children share an entry, so each position counts the
+        // entries its moves reach rather than the moves themselves.
+        let mut remaining = vec![0_u8; table.values.len()];
+        let mut children = Vec::new();
+        for (idx, remaining) in remaining.iter_mut().enumerate() {
+            let Some(position) = table.position(idx) else {
+                continue;
+            };
+            table.values[idx] = DRAW;
+            if table.material.drawn() {
+                continue;
+            }
+            let moves = position.legal_moves();
+            children.clear();
+            for &m in &moves {
+                let child = position.after(m);
+                children.push(match table.locate(&child) {
+                    Some(idx) => (table.key, idx),
+                    None => smaller
+                        .iter()
+                        .find_map(|other| Some((other.key, other.locate(&child)?)))
+                        .expect("every conversion has a table"),
+                });
+            }
+            children.sort_unstable();
+            children.dedup();
+            *remaining = children.len() as u8;
+            if moves.len() == 0 && position.in_check(position.stm) {
+                table.values[idx] = value(0);
+            }
+        }
+
+        let deepest = smaller
+            .iter()
+            .flat_map(|table| &table.values)
+            .filter(|&&value| value != DRAW && value != INVALID)
+            .map(|&value| u32::from(value) - 1)
+            .max()
+            .unwrap_or(0);
+        let mut parents = Vec::new();
+        let mut plies = 0;
+        loop {
+            let mut settled = false;
+            for idx in 0..table.values.len() {
+                if table.values[idx] == value(plies) {
+                    settled = true;
+                    let position = table.position(idx).expect("values are only set for positions");
+                    parents.clear();
+                    parents.extend(position.unmoves(None).iter().filter_map(|parent| table.locate(parent)));
+                    Self::settle(&mut table, &mut remaining, &mut parents, plies);
+                }
+            }
+            for other in &smaller {
+                // a table without pawns keeps each position only one way
+                // round, but a pawn can only have promoted on the last rank;
+                // and a table with the same pieces on both sides keeps both
+                // colourings already.
+                let symmetries = if other.material.has_pawns() == table.material.has_pawns() { 1 } else { 8 };
+                let colourings = if other.material.symmetric() { 1 } else { 2 };
+                for (idx, _) in other.values.iter().enumerate().filter(|&(_, &v)| v == value(plies)) {
+                    let Some(position) = other.position(idx) else {
+                        continue;
+                    };
+                    parents.clear();
+                    for symmetry in 0..symmetries {
+                        let child = position.transformed(symmetry);
+                        for child in [child.swapped(), child].iter().skip(2 - colourings) {
+                            parents.extend(
+                                child.unmoves(Some(&table.material)).iter().filter_map(|parent| table.locate(parent)),
+                            );
+                        }
+                    }
+                    Self::settle(&mut table, &mut remaining, &mut parents, plies);
+                }
+            }
+
+            if !settled && plies >= deepest {
+                return Ok(table);
+            }
+            plies += 1;
+            if plies >= MAX_PLIES {
+                return Err(format!("{} has mates too long to store", table.material.name()));
+            }
+        }
+    }
+
+    /// Settles the `parents` of one entry settled `plies` plies from mate,
+    /// counting it off those it does not win for.
+    fn settle(table: &mut Table, remaining: &mut [u8], parents: &mut Vec<usize>, plies: u32) {
+        parents.sort_unstable();
+        parents.dedup();
+        for &idx in parents.iter() {
+            if table.values[idx] != DRAW {
+                continue;
+            }
+            if !plies.is_multiple_of(2) {
+                remaining[idx] -= 1;
+                if remaining[idx] > 0 {
+                    continue;
+                }
+            }
+            table.values[idx] = value(plies + 1);
+        }
+    }
+}
+
+/// Generates the tables named on the command line into a directory, with
+/// the smaller tables they need, using any tables already there.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: tbgen <material>... [--dir DIR], with up to 5 pieces, or 4 with pawns, and pawns for one side only";
+    let mut dir = ".".to_string();
+    let mut names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => dir.clone_from(args.next().ok_or(usage)?),
+            _ => names.push(arg),
+        }
+    }
+    if names.is_empty() {
+        return Err(usage.to_string());
+    }
+
+    let mut tables = Tables::new(&dir)?;
+    for name in names {
+        for generated in tables.generate(name)? {
+            let table = &tables.tables[&Material::parse(&generated)?.key()];
+            let path = Path::new(&dir).join(format!("{generated}.dtm"));
+            fs::write(&path, table.to_bytes()).map_err(|e| format!("{}: {e}", path.display()))?;
+            eprintln!("{}", table.summary());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod dtm_tests {
+    use std::sync::LazyLock;
+
+    use crate::board::Board;
+    use crate::colour::{BLACK, WHITE};
+    use crate::dtm::{Dtm, Material, Position, Table, Tables, DRAW, INVALID};
+    use crate::kpk;
+    use crate::piece::PieceType;
+
+    static TABLES: LazyLock<Tables> = LazyLock::new(|| {
+        let mut tables = Tables::default();
+        tables.generate("KPvK").unwrap();
+        tables
+    });
+
+    fn table(name: &str) -> &'static Table {
+        &TABLES.tables[&Material::parse(name).unwrap().key()]
+    }
+
+    #[test]
+    fn materials() {
+        let material = Material::parse("KvKRP").unwrap();
+        assert_eq!(material.name(), "KRPvK");
+        assert_eq!(Material::parse("KRvKNN").unwrap().name(), "KNNvKR");
+        assert_eq!(Material::parse("KBvKN").unwrap().name(), "KBvKN");
+        let conversions: Vec<_> = material.conversions().iter().map(Material::name).collect();
+        assert_eq!(conversions, ["KPvK", "KRvK", "KRNvK", "KRBvK", "KRRvK", "KQRvK"]);
+        assert!(Material::parse("KQRvKRB").is_err());
+        assert!(Material::parse("KRPvKR").is_err());
+        assert!(Material::parse("KRPvK").is_ok());
+        assert!(Material::parse("KPvKP").is_err());
+        assert!(Material::parse("KXvK").is_err());
+    }
+
+    #[test]
+    fn indices() {
+        // a position and its reflections are kept under one index, which
+        // gives the position back.
+        let table = table("KRvK");
+        let idx = table.index(&[10, 45, 20], WHITE);
+        for (squares, stm) in [([10, 45, 20], WHITE), ([13, 42, 19], WHITE), ([50, 21, 44], WHITE), ([17, 45, 34], WHITE)] {
+            assert_eq!(table.index(&squares, stm), idx, "{squares:?}");
+        }
+        let position = table.position(idx).unwrap();
+        assert_eq!(table.locate(&position), Some(idx));
+        assert_ne!(table.index(&[10, 45, 20], BLACK), idx);
+        // the black king next to the white king, or in check with white to
+        // move.
+        assert_eq!(table.position(table.index(&[10, 19, 20], WHITE)), None);
+        assert_eq!(table.position(table.index(&[10, 45, 40], WHITE)), None);
+
+        let valid = table.values.iter().filter(|&&value| value != INVALID).count();
+        assert!(valid > 40_000 && valid < 60_000, "{valid}");
+    }
+
+    #[test]
+    fn known_results() {
+        let probe = |fen: &str| TABLES.probe(&Board::from_fen(fen).unwrap());
+        assert_eq!(probe("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        assert_eq!(probe("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+        // the same, with the colours swapped.
+        assert_eq!(probe("1q6/8/8/8/8/6k1/8/7K b - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe("8/8/8/8/8/6k1/6q1/7K w - - 0 1"), Some(Dtm::Loss(0)));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KB6 w - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KBB5 w - - 0 1"), None);
+        assert_eq!(probe("8/8/8/4k3/8/8/8/R3K3 w Q - 0 1"), None);
+
+        // the longest mates with a queen and with a rook are 10 and 16
+        // moves.
+        let longest = |name| table(name).values.iter().filter(|&&value| value != DRAW && value.is_multiple_of(2)).max().map(|&value| value - 1);
+        assert_eq!(longest("KQvK"), Some(19));
+        assert_eq!(longest("KRvK"), Some(31));
+        assert_eq!(longest("KBvK"), None);
+    }
+
+    #[test]
+    fn agrees_with_kpk() {
+        let table = table("KPvK");
+        for idx in 0..table.values.len() {
+            let Some(position) = table.position(idx) else {
+                continue;
+            };
+            let square = |piece_type, colour| position.pieces.iter().find(|&&(c, p, _)| c == colour && p == piece_type).unwrap().2;
+            let (pawn, white_king, black_king) = (square(PieceType::Pawn, WHITE), square(PieceType::King, WHITE), square(PieceType::King, BLACK));
+            // the bitbase has the pawn on the a-d files.
+            let mirror = if pawn % 8 > 3 { 7 } else { 0 };
+            let won = kpk::probe(white_king ^ mirror, pawn ^ mirror, black_king ^ mirror, position.stm);
+            let value = table.values[idx];
+            let white_wins = value != DRAW && value.is_multiple_of(2) == (position.stm == WHITE);
+            assert_eq!(white_wins, won, "{position:?}");
+        }
+    }
+
+    #[test]
+    fn unmoves_undo_moves() {
+        // every move is undone by an un-move of the position it leads to.
+        for name in ["KPvK", "KRvK"] {
+            let table = table(name);
+            for idx in (0..table.values.len()).step_by(97) {
+                let Some(position) = table.position(idx) else {
+                    continue;
+                };
+                for &m in &position.legal_moves() {
+                    let child = position.after(m);
+                    let into = (child.pieces.len() < position.pieces.len() || m.is_promotion()).then_some(&table.material);
+                    assert!(child.unmoves(into).iter().any(|parent| same(parent, &position)), "{position:?} {m}");
+                }
+            }
+        }
+
+        // and every legal un-move by a move, whether it is quiet, takes
+        // back a capture, or takes back a promotion.
+        let mut conversions = 0;
+        for (name, into) in [("KPvK", None), ("KRvK", None), ("KQvK", Some("KPvK")), ("KvK", Some("KPvK")), ("KPvK", Some("KPvKN")), ("KRvK", Some("KRvKB"))] {
+            let table = table(name);
+            let into = into.map(|into| Material::parse(into).unwrap());
+            let symmetries = if table.material.has_pawns() { 1 } else { 8 };
+            for idx in (0..table.values.len()).step_by(29) {
+                let Some(position) = table.position(idx) else {
+                    continue;
+                };
+                // one of the ways the position can be turned, in turn.
+                let child = position.transformed(idx / 29 % symmetries);
+                for child in [child.swapped(), child] {
+                    for parent in child.unmoves(into.as_ref()).into_iter().filter(|parent| !parent.in_check(parent.stm ^ 1)) {
+                        conversions += usize::from(into.is_some());
+                        assert!((&parent.legal_moves()).into_iter().any(|&m| same(&parent.after(m), &child)), "{parent:?} to {child:?}");
+                    }
+                }
+            }
+        }
+        assert!(conversions > 1000, "{conversions}");
+    }
+
+    /// Whether two positions have the same pieces, in any order.
+    fn same(a: &Position, b: &Position) -> bool {
+        let sorted = |position: &Position| {
+            let mut pieces: Vec<_> = position.pieces.iter().map(|&(colour, piece_type, square)| (colour, piece_type as usize, square)).collect();
+            pieces.sort_unstable();
+            pieces
+        };
+        a.stm == b.stm && sorted(a) == sorted(b)
+    }
+
+    #[test]
+    fn files() {
+        let table = table("KPvK");
+        let bytes = table.to_bytes();
+        assert!(bytes.len() < table.values.len() * 3 / 5, "{}", bytes.len());
+        let read = Table::from_bytes(&bytes).unwrap();
+        assert_eq!(read.material, table.material);
+        assert!(read.values.iter().zip(&table.values).all(|(&read, &value)| read == value || value == INVALID));
+
+        assert!(Table::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Table::from_bytes(b"IDTM\x02").is_err());
+        let mut renamed = bytes;
+        renamed[6..10].copy_from_slice(b"KQvK");
+        assert!(Table::from_bytes(&renamed).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+#![allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+
+//! Endgames the general evaluation gets wrong: wins it would not know how
+//! to finish, and material it would think wins but rarely does.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::bitboards::Bitboard;
+use crate::bitmethods::Bithackable;
+use crate::board::Board;
+use crate::colour::{BLACK, WHITE};
+use crate::evaluation::PIECE_VALUES;
+use crate::kpk;
+use crate::magicnumbers::BB_DARK_SQUARES;
+use crate::piece::PieceType;
+use crate::squares::{Square, SquareTrait};
+use crate::tablebase::{material_key, parse_material};
+
+/// Well above any ordinary evaluation, but below the mate scores.
+pub const KNOWN_WIN: i32 = 10_000;
+/// The scale factor of material with no trouble winning.
+pub const SCALE_NORMAL: i32 = 64;
+
+/// Scores a position for the stronger side, which is passed in.
+type Evaluator = fn(&Board, usize) -> i32;
+
+/// The evaluators by material key, with the stronger side.
+static EVALUATORS: LazyLock<HashMap<u64, (Evaluator, usize)>> = LazyLock::new(|| {
+    let evaluators: [(&str, Evaluator); 5] =
+        [("KPvK", kpk), ("KBNvK", kbnk), ("KRvK", kxk), ("KQvK", kxk), ("KNNvK", draw)];
+    let mut map = HashMap::new();
+    for (code, evaluator) in evaluators {
+        let [strong, weak] = parse_material(code).expect("valid material code");
+        map.insert(material_key(&strong, &weak), (evaluator, WHITE));
+        map.insert(material_key(&weak, &strong), (evaluator, BLACK));
+    }
+    map
+});
+
+/// The score of `board` for the side to move, if its material has an
+/// evaluation of its own.
+pub fn evaluate(board: &Board) -> Option<i32> {
+    // none of the endgames has more than four pieces.
+    if board.state().occupied().popcount() > 4 {
+        return None;
+    }
+    let &(evaluator, strong) = EVALUATORS.get(&board.material_key())?;
+    let score = evaluator(board, strong);
+    Some(if board.turn_as_idx() == strong { score } else { -score })
+}
+
+/// How much of the endgame score counts for `strong`, out of
+/// `SCALE_NORMAL`. Without pawns a small edge in material rarely wins,
+/// and bishops of opposite colours often hold a pawn or two down.
+pub fn scale_factor(state: &Bitboard, strong: usize) -> i32 {
+    let non_pawn = |colour: usize| {
+        [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen]
+            .iter()
+            .map(|&piece_type| {
+                PIECE_VALUES[piece_type as usize] * (state.pieces(piece_type) & state.occupied_co[colour]).popcount() as i32
+            })
+            .sum::<i32>()
+    };
+    let (ours, theirs) = (non_pawn(strong), non_pawn(strong ^ 1));
+    let bishop = PIECE_VALUES[PieceType::Bishop as usize];
+    if (state.pawns & state.occupied_co[strong]).none_set() && ours - theirs <= bishop {
+        return if ours < PIECE_VALUES[PieceType::Rook as usize] {
+            0
+        } else if theirs <= bishop {
+            4
+        } else {
+            14
+        };
+    }
+
+    let bishops = [WHITE, BLACK].map(|colour| state.bishops & state.occupied_co[colour]);
+    if bishops.iter().all(|&b| b.popcount() == 1) && ((bishops[WHITE] | bishops[BLACK]) & BB_DARK_SQUARES).popcount() == 1 {
+        return if ours == bishop && theirs == bishop { 22 } else { 46 };
+    }
+    SCALE_NORMAL
+}
+
+fn king(state: &Bitboard, colour: usize) -> Square {
+    (state.kings & state.occupied_co[colour]).lsb()
+}
+
+fn material(state: &Bitboard, colour: usize) -> i32 {
+    [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen]
+        .iter()
+        .map(|&piece_type| PIECE_VALUES[piece_type as usize] * (state.pieces(piece_type) & state.occupied_co[colour]).popcount() as i32)
+        .sum()
+}
+
+/// From 0 in the centre up to 120 in the corners.
+fn push_to_edge(square: Square) -> i32 {
+    let file = square.file().min(7 - square.file()) as i32;
+    let rank = square.rank().min(7 - square.rank()) as i32;
+    20 * (6 - file - rank)
+}
+
+/// From 0 for kings at opposite corners up to 120 for kings side by side.
+fn push_close(a: Square, b: Square) -> i32 {
+    20 * (7 - Square::square_distance(a, b) as i32)
+}
+
+/// King and rook or queen against king: the lone king is driven to the
+/// edge, with the other king coming up to help.
+fn kxk(board: &Board, strong: usize) -> i32 {
+    if board.turn_as_idx() != strong && board.legal_moves().len() == 0 {
+        return 0;
+    }
+    let state = board.state();
+    let (ours, theirs) = (king(state, strong), king(state, strong ^ 1));
+    KNOWN_WIN + material(state, strong) + push_to_edge(theirs) + push_close(ours, theirs)
+}
+
+/// King, bishop and knight against king: mate can only be forced in a
+/// corner of the bishop's colour, so the lone king is driven to one.
+fn kbnk(board: &Board, strong: usize) -> i32 {
+    let state = board.state();
+    let (ours, theirs) = (king(state, strong), king(state, strong ^ 1));
+    let corners = if (state.bishops & BB_DARK_SQUARES).any_set() { [0, 63] } else { [7, 56] };
+    let corner_distance = corners
+        .iter()
+        .map(|&corner: &Square| (theirs.file().abs_diff(corner.file()) + theirs.rank().abs_diff(corner.rank())) as i32)
+        .min()
+        .unwrap_or(0);
+    KNOWN_WIN + material(state, strong) + push_close(ours, theirs) + 20 * (14 - corner_distance)
+}
+
+/// King and pawn against king, from the bitbase: a win is worth more the
+/// further the pawn has got, and anything else is a draw.
+fn kpk(board: &Board, strong: usize) -> i32 {
+    let state = board.state();
+    let pawn = state.pawns.lsb();
+    // the bitbase has white as the stronger side, with the pawn on the a-d
+    // files.
+    let mirror = if pawn.file() > 3 { 7 } else { 0 };
+    let flip = if strong == WHITE { 0 } else { 56 };
+    let normalise = |square: Square| square ^ mirror ^ flip;
+    let stm = if board.turn_as_idx() == strong { WHITE } else { BLACK };
+    if !kpk::probe(normalise(king(state, strong)), normalise(pawn), normalise(king(state, strong ^ 1)), stm) {
+        return 0;
+    }
+    KNOWN_WIN + PIECE_VALUES[PieceType::Pawn as usize] + 20 * normalise(pawn).rank() as i32
+}
+
+/// Material that cannot force mate against a lone king.
+const fn draw(_: &Board, _: usize) -> i32 {
+    0
+}
+
+#[cfg(test)]
+mod endgame_tests {
+    use crate::board::Board;
+    use crate::colour::{BLACK, WHITE};
+    use crate::endgame::{evaluate, scale_factor, KNOWN_WIN, SCALE_NORMAL};
+    use crate::evaluation::{self, evaluate_with, EvalParams};
+
+    fn score(fen: &str) -> Option<i32> {
+        evaluate(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn known_wins() {
+        let krk = score("8/8/8/4k3/8/8/8/KR6 w - - 0 1").unwrap();
+        assert!(krk > KNOWN_WIN);
+        // the same, from the other side and with black to move.
+        assert_eq!(score("kr6/8/8/8/4K3/8/8/8 b - - 0 1"), Some(krk));
+        assert_eq!(score("kr6/8/8/8/4K3/8/8/8 w - - 0 1"), Some(-krk));
+        // the lone king is better off in the centre than on the edge.
+        assert!(score("7k/8/8/8/8/8/8/KR6 w - - 0 1").unwrap() > krk);
+        // stalemate.
+        assert_eq!(score("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(0));
+
+        // KBNK: the right corners for a dark-squared bishop are a1 and h8.
+        let right = score("7k/8/5K2/8/8/8/8/4BN2 w - - 0 1").unwrap();
+        let wrong = score("8/8/8/8/8/8/5K2/4BN1k w - - 0 1").unwrap();
+        assert!(right > wrong && wrong > KNOWN_WIN);
+
+        assert_eq!(score("8/8/8/4k3/8/8/8/KNN5 w - - 0 1"), Some(0));
+        assert_eq!(score("8/8/8/4k3/8/8/8/KNB5 w - - 0 1").map(i32::signum), Some(1));
+        assert_eq!(score("8/8/8/4k3/8/8/P7/KQ6 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn king_and_pawn() {
+        // the bitbase, whichever side and wing.
+        for fen in ["3k4/8/3K4/3P4/8/8/8/8 w - - 0 1", "8/8/8/8/4p3/4k3/8/4K3 b - - 0 1", "8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"] {
+            assert!(score(fen).unwrap().abs() > KNOWN_WIN, "{fen}");
+        }
+        // the opposition.
+        assert_eq!(score("8/3k4/8/3K4/3P4/8/8/8 w - - 0 1"), Some(0));
+        assert!(score("8/3k4/8/3K4/3P4/8/8/8 b - - 0 1").unwrap() < -KNOWN_WIN);
+        // rook pawns with the king in the corner.
+        assert_eq!(score("8/8/8/8/2k5/8/p7/K7 b - - 0 1"), Some(0));
+        assert_eq!(score("k7/P7/8/2K5/8/8/8/8 w - - 0 1"), Some(0));
+    }
+
+    #[test]
+    fn scale_factors() {
+        let scale = |fen: &str, strong| scale_factor(Board::from_fen(fen).unwrap().state(), strong);
+        assert_eq!(scale("8/8/8/4k3/8/8/8/KB6 w - - 0 1", WHITE), 0);
+        assert_eq!(scale("8/8/8/4kb2/8/8/8/KR6 w - - 0 1", WHITE), 4);
+        assert_eq!(scale("8/8/8/4kb2/8/8/8/KR6 w - - 0 1", BLACK), 0);
+        assert_eq!(scale("8/8/8/4kbr1/8/8/8/KRR5 w - - 0 1", WHITE), 14);
+        // bishops of opposite colours, alone and with rooks.
+        assert_eq!(scale("8/5p2/4kb2/8/8/1P6/P1B5/K7 w - - 0 1", WHITE), 22);
+        assert_eq!(scale("8/5p2/4kb2/8/8/1P6/P1B5/KR5r w - - 0 1", WHITE), 46);
+        assert_eq!(scale("8/5p2/4k1b1/8/8/1P6/P1B5/K7 w - - 0 1", WHITE), SCALE_NORMAL);
+        assert_eq!(scale("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", BLACK), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn scaled_evaluation() {
+        // the engine's evaluation scales the endgame score down where the
+        // weights alone do not: a lone knight against a pawn, a lone
+        // bishop, and bishops of opposite colours.
+        for fen in [
+            "4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1",
+            "8/8/8/4k3/8/8/8/KB6 b - - 0 1",
+            "8/5p2/4kb2/8/8/1P6/P1B5/K7 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let unscaled = evaluate_with(&board, &EvalParams::new());
+            assert!(evaluation::evaluate(&board).abs() * 2 < unscaled.abs(), "{fen}");
+        }
+        let board = Board::from_fen("8/5p2/4k1b1/8/8/1P6/P1B5/K7 w - - 0 1").unwrap();
+        assert_eq!(evaluation::evaluate(&board), evaluate_with(&board, &EvalParams::new()));
+    }
+}
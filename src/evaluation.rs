@@ -0,0 +1,835 @@
+#![allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use crate::bitboards::Bitboard;
+use crate::bitmethods::{into_bb, Bithackable};
+use crate::board::Board;
+use crate::colour::{BLACK, WHITE};
+use crate::endgame;
+use crate::magicnumbers::{BB_ALL, BB_FILES, BB_KING_ATTACKS};
+use crate::movegen::attacks_mask;
+use crate::pawntable::{PawnEntry, PawnTable};
+use crate::piece::PieceType;
+use crate::squares::{Square, SquareTrait};
+
+/// Piece values in centipawns, indexed by `PieceType`.
+pub const PIECE_VALUES: [i32; 7] = [0, 100, 320, 330, 500, 900, 0];
+
+/// The game phase with every piece on the board, falling to 0 as pieces
+/// are traded.
+pub const MAX_PHASE: i32 = 24;
+/// How much each piece counts towards the game phase, indexed by
+/// `PieceType`.
+pub const PHASE_WEIGHTS: [i32; 7] = [0, 0, 1, 1, 2, 4, 0];
+
+/// A midgame and an endgame score, blended by the game phase.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+pub const fn s(mg: i32, eg: i32) -> Score {
+    Score { mg, eg }
+}
+
+impl Score {
+    pub const ZERO: Self = s(0, 0);
+
+    /// The score at game phase `phase`, from the midgame score at
+    /// `MAX_PHASE` to the endgame score at 0.
+    pub const fn taper(self, phase: i32) -> i32 {
+        let phase = if phase > MAX_PHASE { MAX_PHASE } else { phase };
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        s(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl Sub for Score {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        s(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl Neg for Score {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        s(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        s(self.mg * rhs, self.eg * rhs)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+// Piece-square tables as seen from white, with the eighth rank first.
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     20,  20,  20,  20,  20,  20,  20,  20,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const ROOK_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// Combines midgame and endgame tables laid out as above into a table
+/// indexed by square.
+const fn table(mg: [i32; 64], eg: [i32; 64]) -> [Score; 64] {
+    let mut table = [Score::ZERO; 64];
+    let mut square = 0;
+    while square < 64 {
+        table[square] = s(mg[square ^ 0x38], eg[square ^ 0x38]);
+        square += 1;
+    }
+    table
+}
+
+/// How many squares a piece usually attacks, indexed by `PieceType`.
+/// Mobility is scored by how far a piece is above or below this.
+const MOBILITY_BASE: [i32; 7] = [0, 0, 4, 6, 7, 13, 0];
+
+/// Every weight of the evaluation, in centipawns, so that they can be
+/// tuned together. Penalties are negative.
+///
+/// Nothing but `Score`s may be added, laid out in order, so that the
+/// offset of a weight tells which one it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct EvalParams {
+    /// Indexed by `PieceType`.
+    pub material: [Score; 7],
+    /// Indexed by `PieceType` and square, as seen from white.
+    pub pst: [[Score; 64]; 7],
+    pub bishop_pair: Score,
+    /// Per square a piece attacks above `MOBILITY_BASE`, leaving out squares
+    /// held by its own pieces or attacked by enemy pawns. Indexed by
+    /// `PieceType`.
+    pub mobility: [Score; 7],
+    /// Per pawn with another of its own ahead of it on the file.
+    pub doubled_pawn: Score,
+    /// Per pawn with none of its own on the files beside it.
+    pub isolated_pawn: Score,
+    /// Per pawn that its neighbours have all passed, and that cannot
+    /// advance without being taken by a pawn.
+    pub backward_pawn: Score,
+    /// Indexed by rank, counted from the pawn's own side.
+    pub passed_pawn: [Score; 8],
+    /// Per passed pawn with a piece of either side in front of it.
+    pub blocked_passed_pawn: Score,
+    pub rook_open_file: Score,
+    /// A file with only enemy pawns on it.
+    pub rook_semi_open_file: Score,
+    /// Per square around the enemy king attacked by a piece of each type,
+    /// once at least two pieces attack there. Indexed by `PieceType`.
+    pub king_attack: [Score; 7],
+}
+
+impl EvalParams {
+    pub const fn new() -> Self {
+        Self {
+            material: [s(0, 0), s(90, 110), s(320, 300), s(330, 320), s(480, 540), s(950, 980), s(0, 0)],
+            pst: [
+                [Score::ZERO; 64],
+                table(PAWN_MG, PAWN_EG),
+                table(KNIGHT, KNIGHT),
+                table(BISHOP, BISHOP),
+                table(ROOK_MG, [0; 64]),
+                table(QUEEN, QUEEN),
+                table(KING_MG, KING_EG),
+            ],
+            bishop_pair: s(30, 50),
+            mobility: [s(0, 0), s(0, 0), s(4, 4), s(5, 5), s(2, 4), s(1, 2), s(0, 0)],
+            doubled_pawn: s(-10, -20),
+            isolated_pawn: s(-10, -15),
+            backward_pawn: s(-8, -10),
+            passed_pawn: [s(0, 0), s(5, 10), s(5, 15), s(10, 25), s(25, 50), s(45, 90), s(70, 130), s(0, 0)],
+            blocked_passed_pawn: s(-5, -20),
+            rook_open_file: s(25, 10),
+            rook_semi_open_file: s(12, 8),
+            king_attack: [s(0, 0), s(0, 0), s(-12, 0), s(-10, 0), s(-15, 0), s(-25, 0), s(0, 0)],
+        }
+    }
+
+    /// Every weight, in the order they are laid out.
+    pub fn weights_mut(&mut self) -> Vec<&mut Score> {
+        let Self {
+            material,
+            pst,
+            bishop_pair,
+            mobility,
+            doubled_pawn,
+            isolated_pawn,
+            backward_pawn,
+            passed_pawn,
+            blocked_passed_pawn,
+            rook_open_file,
+            rook_semi_open_file,
+            king_attack,
+        } = self;
+        material
+            .iter_mut()
+            .chain(pst.iter_mut().flatten())
+            .chain([bishop_pair])
+            .chain(mobility.iter_mut())
+            .chain([doubled_pawn, isolated_pawn, backward_pawn])
+            .chain(passed_pawn.iter_mut())
+            .chain([blocked_passed_pawn, rook_open_file, rook_semi_open_file])
+            .chain(king_attack.iter_mut())
+            .collect()
+    }
+
+    /// The position of `weight`, which must be one of these, in
+    /// `weights_mut`.
+    fn index_of(&self, weight: &Score) -> usize {
+        (std::ptr::from_ref(weight).addr() - std::ptr::from_ref(self).addr()) / std::mem::size_of::<Score>()
+    }
+
+    /// The weights written out as the body of `new`.
+    pub fn to_source(&self) -> String {
+        let list = |scores: &[Score]| scores.iter().map(|w| format!("s({}, {})", w.mg, w.eg)).collect::<Vec<_>>().join(", ");
+        let mut lines = vec!["Self {".to_string(), format!("    material: [{}],", list(&self.material)), "    pst: [".to_string()];
+        for table in &self.pst {
+            lines.push("        [".to_string());
+            lines.extend(table.chunks(8).map(|rank| format!("            {},", list(rank))));
+            lines.push("        ],".to_string());
+        }
+        lines.extend([
+            "    ],".to_string(),
+            format!("    bishop_pair: {},", list(&[self.bishop_pair])),
+            format!("    mobility: [{}],", list(&self.mobility)),
+            format!("    doubled_pawn: {},", list(&[self.doubled_pawn])),
+            format!("    isolated_pawn: {},", list(&[self.isolated_pawn])),
+            format!("    backward_pawn: {},", list(&[self.backward_pawn])),
+            format!("    passed_pawn: [{}],", list(&self.passed_pawn)),
+            format!("    blocked_passed_pawn: {},", list(&[self.blocked_passed_pawn])),
+            format!("    rook_open_file: {},", list(&[self.rook_open_file])),
+            format!("    rook_semi_open_file: {},", list(&[self.rook_semi_open_file])),
+            format!("    king_attack: [{}],", list(&self.king_attack)),
+            "}".to_string(),
+        ]);
+        lines.join("\n")
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static DEFAULT_PARAMS: EvalParams = EvalParams::new();
+
+const PIECE_TYPES: [PieceType; 6] =
+    [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King];
+
+/// The material and piece-square scores of a position, from white's point
+/// of view, and its game phase. These change only piece by piece, so
+/// `Board` keeps them up to date as moves are made.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Psqt {
+    pub material: Score,
+    pub pst: Score,
+    pub phase: i32,
+}
+
+impl Psqt {
+    /// Computes the scores from scratch, with the default weights.
+    pub const fn new(state: &Bitboard) -> Self {
+        Self::with_params(state, &DEFAULT_PARAMS)
+    }
+
+    pub const fn with_params(state: &Bitboard, params: &EvalParams) -> Self {
+        let piece_bbs = [state.pawns, state.knights, state.bishops, state.rooks, state.queens, state.kings];
+
+        let mut psqt = Self { material: Score::ZERO, pst: Score::ZERO, phase: 0 };
+        let mut colour = 0;
+        while colour < 2 {
+            let mut piece = 0;
+            while piece < 6 {
+                let mut bb = piece_bbs[piece] & state.occupied_co[colour];
+                while bb != 0 {
+                    psqt.update(params, colour, piece + 1, bb.trailing_zeros() as usize, 1);
+                    bb &= bb - 1;
+                }
+                piece += 1;
+            }
+            colour += 1;
+        }
+        psqt
+    }
+
+    /// Adds (`sign` 1) or takes away (`sign` -1) a piece of type index
+    /// `piece` on `square`.
+    const fn update(&mut self, params: &EvalParams, colour: usize, piece: usize, square: Square, sign: i32) {
+        // black's pieces count against white, and see the board upside down.
+        let (relative, side) = if colour == WHITE { (square, sign) } else { (square ^ 0x38, -sign) };
+        let material = params.material[piece];
+        let pst = params.pst[piece][relative];
+        self.material.mg += side * material.mg;
+        self.material.eg += side * material.eg;
+        self.pst.mg += side * pst.mg;
+        self.pst.eg += side * pst.eg;
+        self.phase += sign * PHASE_WEIGHTS[piece];
+    }
+
+    pub fn add(&mut self, colour: usize, piece_type: PieceType, square: Square) {
+        self.update(&DEFAULT_PARAMS, colour, piece_type as usize, square, 1);
+    }
+
+    pub fn remove(&mut self, colour: usize, piece_type: PieceType, square: Square) {
+        self.update(&DEFAULT_PARAMS, colour, piece_type as usize, square, -1);
+    }
+}
+
+/// The squares on the ranks ahead of `square`, as seen by `colour`.
+fn ranks_ahead(colour: usize, square: Square) -> u64 {
+    let rank = square.rank() as u32;
+    if colour == WHITE {
+        BB_ALL.checked_shl(8 * (rank + 1)).unwrap_or(0)
+    } else {
+        BB_ALL.checked_shr(8 * (8 - rank)).unwrap_or(0)
+    }
+}
+
+fn adjacent_files(square: Square) -> u64 {
+    let file = square.file();
+    let left = if file > 0 { BB_FILES[file - 1] } else { 0 };
+    let right = if file < 7 { BB_FILES[file + 1] } else { 0 };
+    left | right
+}
+
+/// The rank of `square` counted from `colour`'s side of the board.
+const fn relative_rank(colour: usize, square: Square) -> usize {
+    if colour == WHITE { square / 8 } else { 7 - square / 8 }
+}
+
+fn pawn_attacks(colour: usize, pawns: u64) -> u64 {
+    if colour == WHITE {
+        pawns.shift_up_left() | pawns.shift_up_right()
+    } else {
+        pawns.shift_down_left() | pawns.shift_down_right()
+    }
+}
+
+/// Scores built up term by term, from white's point of view.
+struct Evaluation<'a> {
+    state: &'a Bitboard,
+    params: &'a EvalParams,
+    score: Score,
+    phase: i32,
+    pawn_attacks: [u64; 2],
+    /// Indexed by colour.
+    passed: [u64; 2],
+    /// How many times each weight was added for white, less for black,
+    /// when tracing.
+    trace: Option<Vec<i32>>,
+}
+
+impl<'a> Evaluation<'a> {
+    fn new(state: &'a Bitboard, params: &'a EvalParams, psqt: Psqt) -> Self {
+        let pawn_attacks = [WHITE, BLACK].map(|colour| pawn_attacks(colour, state.pawns & state.occupied_co[colour]));
+        Self { state, params, score: psqt.material + psqt.pst, phase: psqt.phase, pawn_attacks, passed: [0; 2], trace: None }
+    }
+
+    /// How much of the endgame score counts, out of
+    /// `endgame::SCALE_NORMAL`, for the side it favours.
+    fn scale_factor(&self) -> i32 {
+        let strong = if self.score.eg > 0 { WHITE } else { BLACK };
+        endgame::scale_factor(self.state, strong)
+    }
+
+    /// The evaluation with the endgame score scaled down where the
+    /// material is hard to win with.
+    fn scaled(mut self) -> Self {
+        self.score.eg = self.score.eg * self.scale_factor() / endgame::SCALE_NORMAL;
+        self
+    }
+
+    /// The tapered score, from the point of view of `turn`.
+    const fn relative(&self, turn: usize) -> i32 {
+        let score = self.score.taper(self.phase);
+        if turn == WHITE { score } else { -score }
+    }
+
+    /// Adds `count` times `weight` for `colour`. The weight is passed by
+    /// reference so that a trace can tell which one it is.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn add(&mut self, colour: usize, weight: &Score, count: i32) {
+        let count = if colour == WHITE { count } else { -count };
+        self.score += *weight * count;
+        if let Some(trace) = &mut self.trace {
+            trace[self.params.index_of(weight)] += count;
+        }
+    }
+
+    /// The material and piece-square terms, which are otherwise kept in
+    /// `Psqt`.
+    fn psqt(&mut self, colour: usize) {
+        let params = self.params;
+        for piece_type in PIECE_TYPES {
+            for square in (self.state.pieces(piece_type) & self.state.occupied_co[colour]).iter_bits() {
+                let relative = if colour == WHITE { square } else { square.flip_180() };
+                self.add(colour, &params.material[piece_type as usize], 1);
+                self.add(colour, &params.pst[piece_type as usize][relative], 1);
+                self.phase += PHASE_WEIGHTS[piece_type as usize];
+            }
+        }
+    }
+
+    fn pawns(&mut self, colour: usize) {
+        let params = self.params;
+        let ours = self.state.pawns & self.state.occupied_co[colour];
+        let theirs = self.state.pawns & self.state.occupied_co[colour ^ 1];
+        for square in ours.iter_bits() {
+            let file = BB_FILES[square.file()];
+            let adjacent = adjacent_files(square);
+            let ahead = ranks_ahead(colour, square);
+
+            let doubled = (ours & file & ahead).any_set();
+            if doubled {
+                self.add(colour, &params.doubled_pawn, 1);
+            }
+
+            if (ours & adjacent).none_set() {
+                self.add(colour, &params.isolated_pawn, 1);
+            } else {
+                let stop = if colour == WHITE { into_bb(square) << 8 } else { into_bb(square) >> 8 };
+                let unsupported = (ours & adjacent & !ahead).none_set();
+                if unsupported && (stop & self.pawn_attacks[colour ^ 1]).any_set() {
+                    self.add(colour, &params.backward_pawn, 1);
+                }
+            }
+
+            if !doubled && (theirs & (file | adjacent) & ahead).none_set() {
+                self.add(colour, &params.passed_pawn[relative_rank(colour, square)], 1);
+                self.passed[colour].set(square);
+            }
+        }
+    }
+
+    /// Passed pawns of `colour` held up by a piece in front of them.
+    fn blocked_passers(&mut self, colour: usize) {
+        let passed = self.passed[colour];
+        let stops = if colour == WHITE { passed.shift_up() } else { passed.shift_down() };
+        let blocked = (stops & self.state.occupied()).popcount() as i32;
+        self.add(colour, &self.params.blocked_passed_pawn, blocked);
+    }
+
+    /// The bishop pair, mobility and rook files for the pieces of `colour`,
+    /// and the attacks on the enemy king for king safety.
+    fn pieces(&mut self, colour: usize) {
+        let params = self.params;
+        let state = self.state;
+        if (state.bishops & state.occupied_co[colour]).popcount() >= 2 {
+            self.add(colour, &params.bishop_pair, 1);
+        }
+
+        let area = !state.occupied_co[colour] & !self.pawn_attacks[colour ^ 1];
+        let enemy_king = state.kings & state.occupied_co[colour ^ 1];
+        let king_zone = if enemy_king.any_set() { enemy_king | BB_KING_ATTACKS[enemy_king.lsb()] } else { 0 };
+
+        let mut attackers = 0;
+        let mut zone_attacks = [0; 7];
+        for piece_type in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+            for square in (state.pieces(piece_type) & state.occupied_co[colour]).iter_bits() {
+                let attacks = attacks_mask(state, square);
+                let mobility = (attacks & area).popcount() as i32 - MOBILITY_BASE[piece_type as usize];
+                self.add(colour, &params.mobility[piece_type as usize], mobility);
+
+                if (attacks & king_zone).any_set() {
+                    attackers += 1;
+                    zone_attacks[piece_type as usize] += (attacks & king_zone).popcount() as i32;
+                }
+
+                if piece_type == PieceType::Rook {
+                    let file = BB_FILES[square.file()];
+                    if (state.pawns & file).none_set() {
+                        self.add(colour, &params.rook_open_file, 1);
+                    } else if (state.pawns & state.occupied_co[colour] & file).none_set() {
+                        self.add(colour, &params.rook_semi_open_file, 1);
+                    }
+                }
+            }
+        }
+
+        if attackers >= 2 {
+            for (piece_type, &count) in zone_attacks.iter().enumerate() {
+                self.add(colour ^ 1, &params.king_attack[piece_type], count);
+            }
+        }
+    }
+}
+
+/// Static evaluation of the position in centipawns, from the point of
+/// view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    if let Some(score) = endgame::evaluate(board) {
+        return score;
+    }
+    #[cfg(feature = "nnue")]
+    if let Some(score) = board.accumulators().evaluate(board.turn_as_idx()) {
+        return score;
+    }
+    evaluate_from(board, &DEFAULT_PARAMS, board.psqt(), None).scaled().relative(board.turn_as_idx())
+}
+
+/// As `evaluate`, looking up the pawn structure in `pawns` and storing it
+/// there if it is not found.
+pub fn evaluate_cached(board: &Board, pawns: &mut PawnTable) -> i32 {
+    if let Some(score) = endgame::evaluate(board) {
+        return score;
+    }
+    #[cfg(feature = "nnue")]
+    if let Some(score) = board.accumulators().evaluate(board.turn_as_idx()) {
+        return score;
+    }
+    evaluate_from(board, &DEFAULT_PARAMS, board.psqt(), Some(pawns)).scaled().relative(board.turn_as_idx())
+}
+
+/// Static evaluation of the position with the weights `params` alone,
+/// without the endgame knowledge `evaluate` adds.
+pub fn evaluate_with(board: &Board, params: &EvalParams) -> i32 {
+    evaluate_from(board, params, Psqt::with_params(board.state(), params), None).relative(board.turn_as_idx())
+}
+
+/// The pawn structure terms for both sides, which depend on nothing but
+/// the pawns.
+fn pawn_entry(state: &Bitboard, params: &EvalParams, key: u64) -> PawnEntry {
+    let mut evaluation = Evaluation::new(state, params, Psqt::default());
+    for colour in [WHITE, BLACK] {
+        evaluation.pawns(colour);
+    }
+    PawnEntry { key, score: evaluation.score, passed: evaluation.passed }
+}
+
+/// Evaluates the rest of the position on top of the material and
+/// piece-square scores in `psqt`. The pawn table must only ever hold
+/// entries scored with `params`.
+fn evaluate_from<'a>(board: &'a Board, params: &'a EvalParams, psqt: Psqt, pawns: Option<&mut PawnTable>) -> Evaluation<'a> {
+    let state = board.state();
+    let key = board.pawn_key();
+    let entry = pawns.map_or_else(
+        || pawn_entry(state, params, key),
+        |pawns| {
+            pawns.probe(key).unwrap_or_else(|| {
+                let entry = pawn_entry(state, params, key);
+                pawns.store(entry);
+                entry
+            })
+        },
+    );
+
+    let mut evaluation = Evaluation::new(state, params, psqt);
+    evaluation.score += entry.score;
+    evaluation.passed = entry.passed;
+    for colour in [WHITE, BLACK] {
+        evaluation.blocked_passers(colour);
+        evaluation.pieces(colour);
+    }
+    evaluation
+}
+
+/// How many weights there are in `EvalParams`.
+pub const WEIGHT_COUNT: usize = std::mem::size_of::<EvalParams>() / std::mem::size_of::<Score>();
+
+/// The evaluation of `board` as a sum of weights, for tuning: how many
+/// times each weight of `params` was added for white less for black, in
+/// the order of `EvalParams::weights_mut`, the game phase, and the scale
+/// factor `evaluate` applies to the endgame weights, out of
+/// `endgame::SCALE_NORMAL`.
+pub fn trace(board: &Board, params: &EvalParams) -> (Vec<i32>, i32, i32) {
+    let mut evaluation = Evaluation::new(board.state(), params, Psqt::default());
+    evaluation.trace = Some(vec![0; WEIGHT_COUNT]);
+    for colour in [WHITE, BLACK] {
+        evaluation.psqt(colour);
+        evaluation.pawns(colour);
+    }
+    for colour in [WHITE, BLACK] {
+        evaluation.blocked_passers(colour);
+        evaluation.pieces(colour);
+    }
+    let scale = evaluation.scale_factor();
+    (evaluation.trace.unwrap_or_default(), evaluation.phase, scale)
+}
+
+#[cfg(test)]
+mod evaluation_tests {
+    use crate::board::Board;
+    use crate::colour::WHITE;
+    use crate::endgame;
+    use crate::evaluation::{evaluate, evaluate_with, s, trace, EvalParams, Psqt, Score, WEIGHT_COUNT};
+
+    /// The position with the colours swapped and the board turned around.
+    fn mirror(fen: &str) -> String {
+        let fields = fen.split_whitespace().collect::<Vec<_>>();
+        let placement = fields[0]
+            .split('/')
+            .rev()
+            .map(|rank| rank.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("/");
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        format!("{placement} {turn} - - 0 1")
+    }
+
+    #[test]
+    fn symmetric() {
+        assert_eq!(evaluate(&Board::new()), 0);
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "8/5pk1/6p1/3P4/1p6/1P3KP1/8/8 b - - 0 40",
+            "2r2rk1/pp1b1ppp/4pn2/q2p4/3P4/P1PB1N2/2Q2PPP/R4RK1 w - - 0 16",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror(fen)).unwrap();
+            assert_eq!(evaluate(&board), evaluate(&mirrored), "{fen}");
+        }
+
+        let white = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let black = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        assert!(evaluate(&white) > 400);
+        assert_eq!(evaluate(&black), -evaluate(&white));
+    }
+
+    /// Checks the incremental scores against a full evaluation everywhere
+    /// in the tree below `board`.
+    fn check_incremental(board: &mut Board, depth: usize) {
+        assert_eq!(board.psqt(), Psqt::new(board.state()), "{}", board.fen());
+        let full = Board::from_fen(&board.fen()).unwrap();
+        assert_eq!(evaluate(board), evaluate(&full), "{}", board.fen());
+        if depth == 0 {
+            return;
+        }
+        for &m in &board.legal_moves() {
+            board.make(m);
+            check_incremental(board, depth - 1);
+            board.unmake();
+        }
+    }
+
+    #[test]
+    fn incremental() {
+        // castling both ways, en passant, and promotions with and without
+        // a capture.
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1",
+            "1n2k3/P7/8/8/8/8/7p/4K1N1 w - - 0 1",
+        ] {
+            check_incremental(&mut Board::from_fen(fen).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn traced() {
+        // number the weights in order, and find the numbers again.
+        let mut params = EvalParams::new();
+        assert_eq!(params.weights_mut().len(), WEIGHT_COUNT);
+        for (i, weight) in params.weights_mut().into_iter().enumerate() {
+            *weight = s(i32::try_from(i).unwrap(), 0);
+        }
+        for weight in [&params.material[1], &params.pst[6][63], &params.bishop_pair, &params.passed_pawn[7], &params.king_attack[6]] {
+            assert_eq!(params.index_of(weight), usize::try_from(weight.mg).unwrap());
+        }
+        assert!(params.to_source().contains("    bishop_pair: s(455, 0),\n"));
+
+        let mut params = EvalParams::new();
+
+        // the weights added up as traced give the evaluation, with the
+        // endgame weights scaled down for bishops of opposite colours.
+        for (fen, scaled) in [
+            ("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", false),
+            ("2r2rk1/pp1b1ppp/4pn2/q2p4/3P4/P1PB1N2/2Q2PPP/R4RK1 b - - 0 16", false),
+            ("8/5pk1/6p1/3P4/1p6/1P3KP1/8/8 b - - 0 40", false),
+            ("8/5p2/4kb2/8/8/1P6/P1B5/K7 w - - 0 1", true),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let (counts, phase, scale) = trace(&board, &params);
+            assert_eq!(scale < endgame::SCALE_NORMAL, scaled, "{fen}");
+            let mut total = params.weights_mut().into_iter().zip(counts).fold(Score::ZERO, |total, (w, n)| total + *w * n);
+            total.eg = total.eg * scale / endgame::SCALE_NORMAL;
+            let score = if board.turn_as_idx() == WHITE { total.taper(phase) } else { -total.taper(phase) };
+            assert_eq!(score, evaluate(&board), "{fen}");
+        }
+    }
+
+    #[test]
+    fn taper() {
+        let score = s(100, -20);
+        assert_eq!(score.taper(24), 100);
+        assert_eq!(score.taper(0), -20);
+        assert_eq!(score.taper(12), 40);
+        assert_eq!(Score::ZERO.taper(7), 0);
+    }
+
+    #[test]
+    fn terms() {
+        // only the weight being checked is left.
+        let only = |set: fn(&mut EvalParams)| {
+            let mut params = EvalParams::new();
+            params.pst = [[Score::ZERO; 64]; 7];
+            params.mobility = [Score::ZERO; 7];
+            params.king_attack = [Score::ZERO; 7];
+            params.material = [Score::ZERO; 7];
+            params.bishop_pair = Score::ZERO;
+            params.doubled_pawn = Score::ZERO;
+            params.isolated_pawn = Score::ZERO;
+            params.backward_pawn = Score::ZERO;
+            params.passed_pawn = [Score::ZERO; 8];
+            params.blocked_passed_pawn = Score::ZERO;
+            params.rook_open_file = Score::ZERO;
+            params.rook_semi_open_file = Score::ZERO;
+            set(&mut params);
+            params
+        };
+        let eval = |fen: &str, params: &EvalParams| evaluate_with(&Board::from_fen(fen).unwrap(), params);
+
+        // a pawn-only ending is scored at the endgame weights.
+        let params = only(|p| p.passed_pawn[5] = s(0, 50));
+        assert_eq!(eval("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1", &params), 50);
+        assert_eq!(eval("4k3/2p5/3P4/8/8/8/8/4K3 w - - 0 1", &params), 0);
+        let params = only(|p| p.blocked_passed_pawn = s(-20, -20));
+        assert_eq!(eval("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1", &params), 0);
+        assert_eq!(eval("4k3/3n4/3P4/8/8/8/8/4K3 w - - 0 1", &params), -20);
+
+        // the rear pawn of the doubled pair is doubled, and both isolated.
+        let params = only(|p| p.doubled_pawn = s(0, -10));
+        assert_eq!(eval("4k3/8/8/3P4/3P4/8/8/4K3 w - - 0 1", &params), -10);
+        let params = only(|p| p.isolated_pawn = s(0, -10));
+        assert_eq!(eval("4k3/8/8/3P4/3P4/8/8/4K3 w - - 0 1", &params), -20);
+        assert_eq!(eval("4k3/8/8/3P4/2P5/8/8/4K3 w - - 0 1", &params), 0);
+
+        // c3 has fallen behind d4 and cannot go to c4, which b5 attacks.
+        let params = only(|p| p.backward_pawn = s(0, -10));
+        assert_eq!(eval("4k3/8/8/1p6/3P4/2P5/8/4K3 w - - 0 1", &params), -10);
+
+        let params = only(|p| {
+            p.rook_open_file = s(20, 20);
+            p.rook_semi_open_file = s(10, 10);
+        });
+        assert_eq!(eval("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1", &params), 20);
+        assert_eq!(eval("4k3/p7/8/8/8/8/4P3/R3K3 w - - 0 1", &params), 10);
+
+        let params = only(|p| p.bishop_pair = s(30, 30));
+        assert_eq!(eval("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", &params), 30);
+
+        // the queen alone is no danger, but with the rook each reaches two
+        // squares around the king.
+        let params = only(|p| {
+            p.king_attack[4] = s(-10, -10);
+            p.king_attack[5] = s(-10, -10);
+        });
+        assert_eq!(eval("6k1/8/8/8/8/8/8/K6Q b - - 0 1", &params), 0);
+        assert_eq!(eval("6k1/8/8/8/8/8/8/K5RQ b - - 0 1", &params), -40);
+    }
+}
@@ -0,0 +1,178 @@
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::cmove::Move;
+use crate::piece::PieceType;
+use crate::search::MAX_PLY;
+use crate::squares::Square;
+
+/// History scores are kept within plus or minus this.
+pub const MAX_HISTORY: i32 = 16384;
+const MAX_BONUS: i32 = 1200;
+
+/// A moved piece, as `colour * 6 + piece type - 1`, and the square it
+/// moved to. Continuation history is indexed by these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceTo {
+    pub piece: usize,
+    pub to: Square,
+}
+
+impl PieceTo {
+    pub const fn new(colour_idx: usize, piece_type: PieceType, to: Square) -> Self {
+        Self { piece: colour_idx * 6 + piece_type as usize - PieceType::Pawn as usize, to }
+    }
+}
+
+/// Moves the history bonus towards `bonus`, damped as it nears the limit,
+/// so that old results fade out rather than saturating.
+fn gravity(entry: &mut i16, bonus: i32) {
+    let value = i32::from(*entry);
+    *entry = (value + bonus - value * bonus.abs() / MAX_HISTORY) as i16;
+}
+
+/// Move ordering statistics gathered during search: killer moves per ply,
+/// countermoves, butterfly history by side and from/to squares, and
+/// continuation history keyed by the previous moves.
+pub struct History {
+    killers: Vec<[Move; 2]>,
+    countermoves: Vec<[Move; 64]>,
+    butterfly: Vec<[[i16; 64]; 64]>,
+    /// Flattened, indexed by `continuation_index`.
+    continuation: Vec<i16>,
+}
+
+const fn continuation_index(previous: PieceTo, current: PieceTo) -> usize {
+    ((previous.piece * 64 + previous.to) * 12 + current.piece) * 64 + current.to
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            killers: vec![[Move::null(); 2]; MAX_PLY + 1],
+            countermoves: vec![[Move::null(); 64]; 12],
+            butterfly: vec![[[0; 64]; 64]; 2],
+            continuation: vec![0; 12 * 64 * 12 * 64],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Clears the killers of the next ply, as its siblings' killers are
+    /// less relevant than those of its cousins.
+    pub fn clear_killers(&mut self, ply: usize) {
+        self.killers[ply] = [Move::null(); 2];
+    }
+
+    pub fn killers(&self, ply: usize) -> [Move; 2] {
+        self.killers[ply]
+    }
+
+    pub fn countermove(&self, previous: Option<PieceTo>) -> Move {
+        previous.map_or(Move::null(), |p| self.countermoves[p.piece][p.to])
+    }
+
+    /// The history score of a quiet move by `colour_idx`, given the moves
+    /// one and two plies earlier.
+    pub fn quiet_score(&self, colour_idx: usize, m: Move, piece_to: PieceTo, continuations: [Option<PieceTo>; 2]) -> i32 {
+        let mut score = i32::from(self.butterfly[colour_idx][m.from_sq()][m.to_sq()]);
+        for previous in continuations.into_iter().flatten() {
+            score += i32::from(self.continuation[continuation_index(previous, piece_to)]);
+        }
+        score
+    }
+
+    fn update_quiet(&mut self, colour_idx: usize, m: Move, piece_to: PieceTo, continuations: [Option<PieceTo>; 2], bonus: i32) {
+        gravity(&mut self.butterfly[colour_idx][m.from_sq()][m.to_sq()], bonus);
+        for previous in continuations.into_iter().flatten() {
+            gravity(&mut self.continuation[continuation_index(previous, piece_to)], bonus);
+        }
+    }
+
+    /// Records that the quiet move `best` caused a beta cutoff at `ply`
+    /// after the quiet moves in `tried` failed to.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        colour_idx: usize,
+        ply: usize,
+        depth: usize,
+        best: (Move, PieceTo),
+        tried: &[(Move, PieceTo)],
+        continuations: [Option<PieceTo>; 2],
+    ) {
+        let (m, piece_to) = best;
+        let bonus = i32::try_from(depth * depth).map_or(MAX_BONUS, |bonus| bonus.min(MAX_BONUS));
+
+        self.update_quiet(colour_idx, m, piece_to, continuations, bonus);
+        for &(other, other_piece_to) in tried {
+            self.update_quiet(colour_idx, other, other_piece_to, continuations, -bonus);
+        }
+
+        let killers = &mut self.killers[ply];
+        if killers[0] != m {
+            killers[1] = killers[0];
+            killers[0] = m;
+        }
+
+        if let Some(previous) = continuations[0] {
+            self.countermoves[previous.piece][previous.to] = m;
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use crate::cmove::Move;
+    use crate::history::{History, PieceTo, MAX_HISTORY};
+    use crate::piece::PieceType;
+    use crate::squares::SquareEnum::{C3, E4, E5, F3, G1, G8};
+
+    #[test]
+    fn updates() {
+        let mut history = History::new();
+        let nf3 = Move::new(G1 as usize, F3 as usize);
+        let nc3 = Move::new(G1 as usize, C3 as usize);
+        let kingside_knight = PieceTo::new(0, PieceType::Knight, F3 as usize);
+        let queenside_knight = PieceTo::new(0, PieceType::Knight, C3 as usize);
+        let previous = Some(PieceTo::new(1, PieceType::Pawn, E5 as usize));
+        let continuations = [previous, None];
+
+        history.update(0, 3, 4, (nf3, kingside_knight), &[(nc3, queenside_knight)], continuations);
+        assert_eq!(history.quiet_score(0, nf3, kingside_knight, continuations), 32);
+        assert_eq!(history.quiet_score(0, nc3, queenside_knight, continuations), -32);
+        // without the continuation only the butterfly history counts.
+        assert_eq!(history.quiet_score(0, nf3, kingside_knight, [None, None]), 16);
+        // the other side's history is separate.
+        assert_eq!(history.quiet_score(1, nf3, kingside_knight, [None, None]), 0);
+
+        assert_eq!(history.killers(3), [nf3, Move::null()]);
+        assert_eq!(history.countermove(previous), nf3);
+        assert_eq!(history.countermove(Some(PieceTo::new(1, PieceType::Pawn, E4 as usize))), Move::null());
+
+        let g8 = Move::new(E4 as usize, G8 as usize);
+        history.update(0, 3, 2, (g8, queenside_knight), &[], continuations);
+        assert_eq!(history.killers(3), [g8, nf3]);
+        history.clear_killers(3);
+        assert_eq!(history.killers(3), [Move::null(); 2]);
+    }
+
+    #[test]
+    fn saturates() {
+        let mut history = History::new();
+        let m = Move::new(G1 as usize, F3 as usize);
+        let piece_to = PieceTo::new(0, PieceType::Knight, F3 as usize);
+        for _ in 0..1000 {
+            history.update(0, 0, 30, (m, piece_to), &[], [None, None]);
+        }
+        let score = history.quiet_score(0, m, piece_to, [None, None]);
+        assert!(score <= MAX_HISTORY && score > MAX_HISTORY - 100);
+    }
+}
@@ -0,0 +1,180 @@
+#![allow(clippy::cast_possible_truncation)]
+
+//! A bitbase for king and pawn against king, worked out by retrograde
+//! analysis the first time it is probed.
+
+use std::sync::LazyLock;
+
+use crate::bitboards::Bitboard;
+use crate::colour::{BLACK, WHITE};
+use crate::magicnumbers::{BB_ALL, BB_KING_ATTACKS, BB_PAWN_ATTACKS};
+use crate::movebuffer::MoveBuf;
+use crate::movegen::generate_legal_moves;
+use crate::piece::PieceType;
+use crate::squares::Square;
+
+/// Every position with the pawn on the a-d files, legal or not.
+const MAX_INDEX: usize = 2 * 24 * 64 * 64;
+/// Successors whose result is known without looking them up.
+const WIN: u32 = u32::MAX;
+const DRAW: u32 = u32::MAX - 1;
+
+/// A bit for every position that white wins.
+static BITBASE: LazyLock<Vec<u64>> = LazyLock::new(generate);
+
+/// The index of the position with `stm` to move and the white pawn, on
+/// the a-d files, on `pawn`.
+const fn index(stm: usize, black_king: Square, white_king: Square, pawn: Square) -> usize {
+    stm | black_king << 1 | white_king << 7 | (pawn & 7) << 13 | (6 - pawn / 8) << 15
+}
+
+/// The side to move, black king, white king and pawn of an index.
+const fn decode(idx: usize) -> (usize, Square, Square, Square) {
+    let pawn = (6 - (idx >> 15)) * 8 + (idx >> 13 & 3);
+    (idx & 1, idx >> 1 & 63, idx >> 7 & 63, pawn)
+}
+
+/// Whether white wins with its king on `white_king` and its pawn on
+/// `pawn`, against black's king on `black_king`, with `stm` to move. The
+/// pawn must be on the a-d files.
+pub fn probe(white_king: Square, pawn: Square, black_king: Square, stm: usize) -> bool {
+    debug_assert!(pawn & 7 <= 3 && (8..56).contains(&pawn));
+    let idx = index(stm, black_king, white_king, pawn);
+    BITBASE[idx / 64] >> (idx % 64) & 1 != 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+/// The positions the legal moves of a position lead to, or `WIN` or
+/// `DRAW` for moves that leave king and pawn against king. Promotions
+/// win unless the black king can take the new queen, and only promotions
+/// to a queen are tried.
+fn successors(idx: usize, children: &mut Vec<u32>) -> Outcome {
+    let (stm, black_king, white_king, pawn) = decode(idx);
+    let pawn_attacks = BB_PAWN_ATTACKS[WHITE][pawn];
+    if white_king == black_king
+        || pawn == white_king
+        || pawn == black_king
+        || BB_KING_ATTACKS[white_king] & 1 << black_king != 0
+        || (stm == WHITE && pawn_attacks & 1 << black_king != 0)
+    {
+        return Outcome::Invalid;
+    }
+
+    let mut state = Bitboard::clear();
+    state.kings = 1 << white_king | 1 << black_king;
+    state.pawns = 1 << pawn;
+    state.occupied_co[WHITE] = 1 << white_king | 1 << pawn;
+    state.occupied_co[BLACK] = 1 << black_king;
+    let mut moves = MoveBuf::new();
+    generate_legal_moves(&mut moves, &state, stm, BB_ALL, BB_ALL);
+
+    if moves.len() == 0 {
+        let mated = stm == BLACK && pawn_attacks & 1 << black_king != 0;
+        return if mated { Outcome::Win } else { Outcome::Draw };
+    }
+    for &m in &moves {
+        let (from, to) = (m.from_sq(), m.to_sq());
+        let child = if stm == BLACK {
+            if to == pawn { DRAW } else { index(WHITE, to, white_king, pawn) as u32 }
+        } else if from == white_king {
+            index(BLACK, black_king, to, pawn) as u32
+        } else if to < 56 {
+            index(BLACK, black_king, white_king, to) as u32
+        } else if m.promotion() != PieceType::Queen {
+            continue;
+        } else if BB_KING_ATTACKS[black_king] & 1 << to == 0 || BB_KING_ATTACKS[white_king] & 1 << to != 0 {
+            WIN
+        } else {
+            DRAW
+        };
+        children.push(child);
+    }
+    Outcome::Unknown
+}
+
+/// Settles positions until no more can be: white wins if some move wins
+/// and black loses if every move does. Whatever is left is a draw.
+fn generate() -> Vec<u64> {
+    let mut outcomes = Vec::with_capacity(MAX_INDEX);
+    let mut starts = Vec::with_capacity(MAX_INDEX + 1);
+    let mut children = Vec::new();
+    for idx in 0..MAX_INDEX {
+        starts.push(children.len());
+        outcomes.push(successors(idx, &mut children));
+    }
+    starts.push(children.len());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..MAX_INDEX {
+            if outcomes[idx] != Outcome::Unknown {
+                continue;
+            }
+            let mut results = children[starts[idx]..starts[idx + 1]].iter().map(|&child| match child {
+                WIN => Outcome::Win,
+                DRAW => Outcome::Draw,
+                child => outcomes[child as usize],
+            });
+            let (good, bad) = if idx & 1 == WHITE { (Outcome::Win, Outcome::Draw) } else { (Outcome::Draw, Outcome::Win) };
+            let outcome = if results.clone().any(|result| result == good) {
+                good
+            } else if results.all(|result| result == bad) {
+                bad
+            } else {
+                continue;
+            };
+            outcomes[idx] = outcome;
+            changed = true;
+        }
+    }
+
+    let mut bitbase = vec![0; MAX_INDEX / 64];
+    for (idx, &outcome) in outcomes.iter().enumerate() {
+        if outcome == Outcome::Win {
+            bitbase[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+    bitbase
+}
+
+#[cfg(test)]
+mod kpk_tests {
+    use crate::colour::{BLACK, WHITE};
+    use crate::kpk::{decode, index, probe, BITBASE, MAX_INDEX};
+
+    #[test]
+    fn indices() {
+        for idx in [0, 1, 12_345, MAX_INDEX - 1] {
+            let (stm, black_king, white_king, pawn) = decode(idx);
+            assert_eq!(index(stm, black_king, white_king, pawn), idx);
+        }
+    }
+
+    #[test]
+    fn known_positions() {
+        // e6/e5 against e8 wins whoever is to move; d6/d5 mirrored.
+        assert!(probe(43, 35, 59, WHITE));
+        assert!(probe(43, 35, 59, BLACK));
+        // with the king just in front of its pawn, the opposition decides.
+        assert!(!probe(35, 27, 51, WHITE));
+        assert!(probe(35, 27, 51, BLACK));
+        // a rook pawn with the defending king in the corner is drawn.
+        assert!(!probe(2, 48, 56, WHITE));
+        assert!(!probe(42, 40, 56, WHITE));
+        // the pawn outruns the king.
+        assert!(probe(7, 24, 63, WHITE));
+        assert!(!probe(7, 24, 37, BLACK));
+
+        // most positions are wins, but far from all.
+        let wins = BITBASE.iter().map(|bits| bits.count_ones()).sum::<u32>();
+        assert!(wins > 100_000 && wins < 120_000, "{wins}");
+    }
+}
@@ -18,11 +18,41 @@ mod squares;
 mod board;
 mod cmove;
 mod colour;
+mod datagen;
+mod dtm;
 mod piece;
 mod movebuffer;
 mod movegen;
+mod packed;
+mod render;
+mod svg;
+mod endgame;
+mod evaluation;
+#[cfg(feature = "nnue")]
+mod nnue;
+mod search;
+mod see;
+mod zobrist;
+mod transposition;
+mod pawntable;
+mod history;
+mod kpk;
+mod movepicker;
+mod params;
+mod smp;
+mod tablebase;
+mod timeman;
+mod tune;
+mod uci;
 
-fn main() {
-    println!("Hi! I am Istus version 1");
-    
+fn main() -> std::io::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("tune") => return tune::run(&args[2..]).map_err(std::io::Error::other),
+        Some("datagen") => return datagen::run(&args[2..]).map_err(std::io::Error::other),
+        Some("tbgen") => return dtm::run(&args[2..]).map_err(std::io::Error::other),
+        _ => {}
+    }
+    let stdin = std::io::stdin();
+    uci::Uci::new(std::io::stdout()).run(stdin.lock())
 }
@@ -33,6 +33,11 @@ impl MoveBuf {
     pub const fn len(&self) -> usize {
         self.len
     }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        assert!(a < self.len && b < self.len, "Index out of bounds");
+        self.buffer.swap(a, b);
+    }
 }
 
 impl Index<usize> for MoveBuf {
@@ -53,4 +58,26 @@ impl<'a> IntoIterator for &'a MoveBuf {
     fn into_iter(self) -> Self::IntoIter {
         self.buffer[..self.len].iter()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MoveBuf {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MoveBuf {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let moves = Vec::<Move>::deserialize(deserializer)?;
+        if moves.len() > 256 {
+            return Err(serde::de::Error::invalid_length(moves.len(), &"at most 256 moves"));
+        }
+        let mut buffer = Self::new();
+        for m in moves {
+            buffer.push(m);
+        }
+        Ok(buffer)
+    }
+}
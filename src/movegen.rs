@@ -9,7 +9,7 @@ use crate::{
         BB_RANK_MASKS,
     },
     piece::PieceType,
-    squares::{Square, SquareTrait}, board::Board,
+    squares::{Square, SquareTrait},
 };
 
 use crate::magicnumbers::{
@@ -26,15 +26,87 @@ fn ray(a: usize, b: usize) -> u64 {
 
 fn between(a: usize, b: usize) -> u64 {
     let bb = BB_RAYS[a][b] & ((BB_ALL << a) ^ (BB_ALL << b));
-    bb & (bb - 1)
+    bb & bb.wrapping_sub(1)
 }
 
-fn legal(state: &Board, pl_move: Move) -> bool {
-    // https://github.com/official-stockfish/Stockfish/blob/master/src/position.cpp
-    todo!();
+/// Pieces of the side to move that are the only piece between their king
+/// and an enemy slider, and so may be pinned.
+fn slider_blockers(state: &Bitboard, turn_idx: usize, king: Square) -> u64 {
+    let rooks_and_queens = state.rooks | state.queens;
+    let bishops_and_queens = state.bishops | state.queens;
+
+    let snipers = (BB_RANK_ATTACKS[king][&0] & rooks_and_queens)
+        | (BB_FILE_ATTACKS[king][&0] & rooks_and_queens)
+        | (BB_DIAG_ATTACKS[king][&0] & bishops_and_queens);
+
+    let mut blockers = 0;
+    for sniper in (snipers & state.occupied_co[1 ^ turn_idx]).iter_bits() {
+        let b = between(king, sniper) & state.occupied();
+        if b.popcount() == 1 {
+            blockers |= b;
+        }
+    }
+
+    blockers & state.occupied_co[turn_idx]
 }
 
-fn attackers_mask(state: &Bitboard, turn_idx: usize, square: usize, occupied: u64) -> u64 {
+/// The squares a piece on `square` can move to without leaving its king
+/// in check through a pin, or every square if it is not pinned.
+fn pin_mask(state: &Bitboard, turn_idx: usize, king: Square, square: Square) -> u64 {
+    let square_mask = into_bb(square);
+
+    let lines = [
+        (BB_FILE_ATTACKS[king][&0], state.rooks | state.queens),
+        (BB_RANK_ATTACKS[king][&0], state.rooks | state.queens),
+        (BB_DIAG_ATTACKS[king][&0], state.bishops | state.queens),
+    ];
+    for (rays, sliders) in lines {
+        if (rays & square_mask).any_set() {
+            let snipers = rays & sliders & state.occupied_co[1 ^ turn_idx];
+            for sniper in snipers.iter_bits() {
+                if between(sniper, king) & (state.occupied() | square_mask) == square_mask {
+                    return ray(king, sniper);
+                }
+            }
+            break;
+        }
+    }
+
+    BB_ALL
+}
+
+/// Whether capturing en passant with the pawn on `capturer` would expose
+/// the king along the rank or a diagonal, by removing both pawns at once.
+fn ep_skewered(state: &Bitboard, turn_idx: usize, king: Square, capturer: Square) -> bool {
+    let ep_square = state.ep_square.lsb();
+    let last_double = if turn_idx == WHITE { ep_square - 8 } else { ep_square + 8 };
+
+    let occupancy = (state.occupied() & !into_bb(last_double) & !into_bb(capturer)) | state.ep_square;
+
+    let horizontal_attackers = state.occupied_co[1 ^ turn_idx] & (state.rooks | state.queens);
+    if (BB_RANK_ATTACKS[king][&(BB_RANK_MASKS[king] & occupancy)] & horizontal_attackers).any_set() {
+        return true;
+    }
+
+    let diagonal_attackers = state.occupied_co[1 ^ turn_idx] & (state.bishops | state.queens);
+    (BB_DIAG_ATTACKS[king][&(BB_DIAG_MASKS[king] & occupancy)] & diagonal_attackers).any_set()
+}
+
+fn is_safe(state: &Bitboard, turn_idx: usize, king: Square, blockers: u64, m: Move) -> bool {
+    let from = m.from_sq();
+    let to = m.to_sq();
+    if from == king {
+        m.move_type() == MoveType::Castling
+            || attackers_mask(state, 1 ^ turn_idx, to, state.occupied()).none_set()
+    } else if m.move_type() == MoveType::EnPassant {
+        (pin_mask(state, turn_idx, king, from) & into_bb(to)).any_set()
+            && !ep_skewered(state, turn_idx, king, from)
+    } else {
+        !blockers.test(from) || ray(from, to).test(king)
+    }
+}
+
+pub fn attackers_mask(state: &Bitboard, turn_idx: usize, square: usize, occupied: u64) -> u64 {
     let rank_pieces = BB_RANK_MASKS[square] & occupied;
     let file_pieces = BB_FILE_MASKS[square] & occupied;
     let diag_pieces = BB_DIAG_MASKS[square] & occupied;
@@ -74,7 +146,7 @@ fn generate_castling_moves(
         return;
     }
     let king_sq = king_bb.lsb();
-    let our_rights = state.castling_rights & backrank & to_mask;
+    let our_rights = state.castling_rights & state.rooks & state.occupied_co[turn_idx] & backrank & to_mask;
 
     let bb_c = BB_FILE_C & backrank;
     let bb_d = BB_FILE_D & backrank;
@@ -130,10 +202,11 @@ fn generate_evasions(
         attacked |= ray(king, checker) & !into_bb(checker);
     }
 
-    let bb = BB_KING_ATTACKS[king] & !state.occupied_co[turn_idx] & !attacked;
-    for to_square in bb.iter_bits() {
-        let capture = state.piece_type_at(to_square);
-        buffer.push(Move::new(king, to_square));
+    if from_mask.test(king) {
+        let bb = BB_KING_ATTACKS[king] & !state.occupied_co[turn_idx] & !attacked & to_mask;
+        for to_square in bb.iter_bits() {
+            buffer.push(Move::new(king, to_square));
+        }
     }
 
     let checker = checkers.lsb();
@@ -183,7 +256,6 @@ pub fn generate_pseudo_legal_moves(
     for from_square in non_pawns.iter_bits() {
         let moves = attacks_mask(state, from_square) & !our_pieces & to_mask;
         for to_square in moves.iter_bits() {
-            let capture = state.piece_type_at(to_square);
             buffer.push(Move::new(from_square, to_square));
         }
     }
@@ -207,7 +279,6 @@ pub fn generate_pseudo_legal_moves(
 
         for to_square in targets.iter_bits() {
             let to_square: Square = to_square;
-            let capture = state.piece_type_at(to_square);
             if to_square.rank() == 0 || to_square.rank() == 7 {
                 buffer.push(Move::new_promotion(
                     from_square,
@@ -287,7 +358,38 @@ pub fn generate_pseudo_legal_moves(
     }
 }
 
-fn generate_pseudo_legal_ep(
+pub fn generate_legal_moves(
+    buffer: &mut MoveBuf,
+    state: &Bitboard,
+    turn_idx: usize,
+    from_mask: u64,
+    to_mask: u64,
+) {
+    let king_bb = state.kings & state.occupied_co[turn_idx];
+    if king_bb.none_set() {
+        generate_pseudo_legal_moves(buffer, state, turn_idx, from_mask, to_mask);
+        return;
+    }
+    let king = king_bb.lsb();
+
+    let blockers = slider_blockers(state, turn_idx, king);
+    let checkers = attackers_mask(state, 1 ^ turn_idx, king, state.occupied());
+
+    let mut pseudo_legal = MoveBuf::new();
+    if checkers.any_set() {
+        generate_evasions(&mut pseudo_legal, state, turn_idx, king, checkers, from_mask, to_mask);
+    } else {
+        generate_pseudo_legal_moves(&mut pseudo_legal, state, turn_idx, from_mask, to_mask);
+    }
+
+    for &m in &pseudo_legal {
+        if is_safe(state, turn_idx, king, blockers, m) {
+            buffer.push(m);
+        }
+    }
+}
+
+pub fn generate_pseudo_legal_ep(
     buffer: &mut MoveBuf,
     state: &Bitboard,
     turn_idx: usize,
@@ -316,11 +418,12 @@ fn generate_pseudo_legal_ep(
     }
 }
 
-fn attacks_mask(state: &Bitboard, square: Square) -> u64 {
+/// The squares attacked by the piece on `square`.
+pub fn attacks_mask(state: &Bitboard, square: Square) -> u64 {
     let bb_square = into_bb(square);
 
     if (bb_square & state.pawns).any_set() {
-        let color = (bb_square & state.occupied_co[WHITE]).any_set() as usize;
+        let color = usize::from((bb_square & state.occupied_co[BLACK]).any_set());
         BB_PAWN_ATTACKS[color][square]
     } else if (bb_square & state.knights).any_set() {
         BB_KNIGHT_ATTACKS[square]
@@ -394,6 +497,22 @@ mod tests {
         assert_eq!(count, 400);
     }
 
+    #[test]
+    fn perft_positions() {
+        // https://www.chessprogramming.org/Perft_Results
+        let positions = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97_862),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43_238),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9_467),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3, 62_379),
+            ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 2, 2_079),
+        ];
+        for (fen, depth, expected) in positions {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_eq!(perft(&mut board, depth), expected, "perft {depth} of {fen}");
+        }
+    }
+
     #[test]
     fn perft_4() {
         let mut board = Board::new();
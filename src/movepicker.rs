@@ -0,0 +1,310 @@
+use crate::board::Board;
+use crate::cmove::Move;
+use crate::evaluation::PIECE_VALUES;
+use crate::history::{History, PieceTo};
+use crate::movebuffer::MoveBuf;
+use crate::piece::PieceType;
+
+const GOOD_CAPTURE: i32 = 2_000_000;
+const QUEEN_PROMOTION: i32 = 1_900_000;
+
+/// What is needed to order quiet moves at a node, besides the history.
+#[derive(Clone, Copy)]
+pub struct QuietOrdering {
+    pub killers: [Move; 2],
+    pub countermove: Move,
+    /// The moves one and two plies earlier, if they were not null moves.
+    pub continuations: [Option<PieceTo>; 2],
+}
+
+impl QuietOrdering {
+    const fn refutations(&self) -> [Move; 3] {
+        [self.killers[0], self.killers[1], self.countermove]
+    }
+}
+
+/// Most valuable victim, least valuable attacker.
+fn mvv_lva(board: &Board, m: Move) -> i32 {
+    PIECE_VALUES[board.captured_piece(m) as usize] * 8 - board.state().piece_type_at(m.from_sq()) as i32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    GenerateNoisy,
+    Noisy,
+    Refutations,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Underpromotions,
+    Done,
+}
+
+/// Yields moves best first, in stages: the TT move, captures that win
+/// material by static exchange (by MVV-LVA) and queen promotions, killers
+/// and the countermove, quiet moves by history, losing captures, and
+/// underpromotions last. As most nodes cut off after the first few moves,
+/// each stage's moves are only generated and scored once it is reached,
+/// and only sorted as far as they are asked for.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Move,
+    /// Without it, killers are not tried early, and quiet moves keep their
+    /// generation order.
+    ordering: Option<QuietOrdering>,
+    /// Only captures and queen promotions, for the quiescence search.
+    tactical: bool,
+    moves: MoveBuf,
+    scores: [i32; 256],
+    next: usize,
+    bad_captures: MoveBuf,
+    underpromotions: MoveBuf,
+}
+
+impl MovePicker {
+    /// Picks from every legal move.
+    pub const fn new(tt_move: Move, ordering: Option<QuietOrdering>) -> Self {
+        Self {
+            stage: Stage::TtMove,
+            tt_move,
+            ordering,
+            tactical: false,
+            moves: MoveBuf::new(),
+            scores: [0; 256],
+            next: 0,
+            bad_captures: MoveBuf::new(),
+            underpromotions: MoveBuf::new(),
+        }
+    }
+
+    /// Picks from the legal captures and queen promotions.
+    pub const fn tactical(tt_move: Move) -> Self {
+        Self { tactical: true, ..Self::new(tt_move, None) }
+    }
+
+    /// The next move to try in `board`, which must be the position the
+    /// picker was made for.
+    pub fn next_move(&mut self, board: &Board, history: &History) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateNoisy;
+                    let m = self.tt_move;
+                    let wanted = !self.tactical
+                        || if m.is_promotion() { m.promotion() == PieceType::Queen } else { board.is_capture(m) };
+                    if m != Move::null() && wanted && board.is_legal(m) {
+                        return Some(m);
+                    }
+                    // it is not among the moves generated, so nothing needs
+                    // to skip it.
+                    self.tt_move = Move::null();
+                }
+                Stage::GenerateNoisy => {
+                    self.generate_noisy(board);
+                    self.stage = Stage::Noisy;
+                }
+                Stage::Noisy => match self.pick() {
+                    // a losing capture waits until the quiet moves have been
+                    // tried. Only the captures picked pay for the exchange.
+                    Some(m) if board.is_capture(m) && !board.see(m, 0) => self.bad_captures.push(m),
+                    Some(m) => return Some(m),
+                    None => {
+                        self.stage = Stage::Refutations;
+                        self.next = 0;
+                    }
+                },
+                Stage::Refutations => {
+                    let Some(refutations) = self.ordering.filter(|_| !self.tactical).map(|o| o.refutations()) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    let Some(&m) = refutations.get(self.next) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    self.next += 1;
+                    if m != Move::null()
+                        && m != self.tt_move
+                        && !refutations[..self.next - 1].contains(&m)
+                        && !board.is_capture(m)
+                        && !m.is_promotion()
+                        && board.is_legal(m)
+                    {
+                        return Some(m);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    if self.tactical {
+                        self.stage = Stage::BadCaptures;
+                        self.next = 0;
+                        continue;
+                    }
+                    self.generate_quiets(board, history);
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    if let Some(m) = self.pick() {
+                        return Some(m);
+                    }
+                    self.stage = Stage::BadCaptures;
+                    self.next = 0;
+                }
+                Stage::BadCaptures => {
+                    if self.next < self.bad_captures.len() {
+                        self.next += 1;
+                        return Some(self.bad_captures[self.next - 1]);
+                    }
+                    self.stage = Stage::Underpromotions;
+                    self.next = 0;
+                }
+                Stage::Underpromotions => {
+                    if self.next < self.underpromotions.len() {
+                        self.next += 1;
+                        return Some(self.underpromotions[self.next - 1]);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Captures and queen promotions, scored, with underpromotions set
+    /// aside for the last stage.
+    fn generate_noisy(&mut self, board: &Board) {
+        let moves = if self.tactical { board.tactical_moves() } else { board.noisy_moves() };
+        self.moves = MoveBuf::new();
+        self.next = 0;
+        for &m in &moves {
+            if m == self.tt_move {
+                continue;
+            }
+            if m.is_promotion() && m.promotion() != PieceType::Queen {
+                self.underpromotions.push(m);
+                continue;
+            }
+            self.scores[self.moves.len()] =
+                if board.is_capture(m) { GOOD_CAPTURE + mvv_lva(board, m) } else { QUEEN_PROMOTION };
+            self.moves.push(m);
+        }
+    }
+
+    /// Quiet moves, other than those already tried, scored by history.
+    fn generate_quiets(&mut self, board: &Board, history: &History) {
+        let us = board.turn_as_idx();
+        let tried = self.ordering.map_or([Move::null(); 3], |ordering| ordering.refutations());
+        self.moves = MoveBuf::new();
+        self.next = 0;
+        for &m in &board.quiet_moves() {
+            if m == self.tt_move || tried.contains(&m) {
+                continue;
+            }
+            self.scores[self.moves.len()] = self.ordering.map_or(0, |ordering| {
+                let piece_to = PieceTo::new(us, board.state().piece_type_at(m.from_sq()), m.to_sq());
+                history.quiet_score(us, m, piece_to, ordering.continuations)
+            });
+            self.moves.push(m);
+        }
+    }
+
+    /// The best of the moves not yet picked in this stage.
+    fn pick(&mut self) -> Option<Move> {
+        if self.next >= self.moves.len() {
+            return None;
+        }
+
+        // selection sort, one step at a time.
+        let mut best = self.next;
+        for i in self.next + 1..self.moves.len() {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.next, best);
+        self.scores.swap(self.next, best);
+
+        let m = self.moves[self.next];
+        self.next += 1;
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+mod movepicker_tests {
+    use crate::board::Board;
+    use crate::cmove::Move;
+    use crate::history::{History, PieceTo};
+    use crate::movepicker::{MovePicker, QuietOrdering};
+    use crate::piece::PieceType;
+    use crate::squares::SquareEnum;
+
+    fn collect(board: &Board, mut picker: MovePicker, history: &History) -> Vec<String> {
+        let mut moves = Vec::new();
+        while let Some(m) = picker.next_move(board, history) {
+            moves.push(m.to_string());
+        }
+        moves
+    }
+
+    fn order(board: &Board, tt_move: &str, ordering: Option<QuietOrdering>, history: &History) -> Vec<String> {
+        let tt_move = if tt_move.is_empty() { Move::null() } else { board.parse_uci(tt_move).unwrap() };
+        collect(board, MovePicker::new(tt_move, ordering), history)
+    }
+
+    #[test]
+    fn captures_and_promotions() {
+        // a pawn can take a queen or a defended rook, or promote; the
+        // rook can take a defended pawn.
+        let history = History::new();
+        let board = Board::from_fen("1q2k3/P2r4/2P1p3/3p4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let moves = order(&board, "", None, &history);
+        assert_eq!(moves.len(), board.legal_moves().len());
+        assert_eq!(&moves[..3], ["a7b8q", "c6d7", "a7a8q"]);
+        assert_eq!(&moves[moves.len() - 7..moves.len() - 6], ["d1d5"]);
+        assert!(moves[moves.len() - 6..].iter().all(|m| m.starts_with("a7") && !m.ends_with('q')));
+
+        // the TT move beats everything, and comes up only once.
+        let moves = order(&board, "e1f2", None, &history);
+        assert_eq!(moves[0], "e1f2");
+        assert_eq!(moves.len(), board.legal_moves().len());
+
+        // the quiescence search only gets captures and queen promotions,
+        // and a quiet TT move is not one of them.
+        let mut tactical = collect(&board, MovePicker::tactical(Move::null()), &history);
+        assert_eq!(collect(&board, MovePicker::tactical(board.parse_uci("e1f2").unwrap()), &history), tactical);
+        tactical.sort();
+        assert_eq!(tactical, ["a7a8q", "a7b8q", "c6d7", "d1d5"]);
+
+        // an illegal TT move, as from another position, is left out.
+        let moves = collect(&board, MovePicker::new(Move::from_uci("d1d8").unwrap(), None), &history);
+        assert!(!moves.contains(&"d1d8".to_string()));
+        assert_eq!(moves.len(), board.legal_moves().len());
+    }
+
+    #[test]
+    fn quiet_moves() {
+        let board = Board::new();
+        let mut history = History::new();
+        let e4 = board.parse_uci("e2e4").unwrap();
+        let d4 = board.parse_uci("d2d4").unwrap();
+        let piece_to = PieceTo::new(0, PieceType::Pawn, SquareEnum::E4 as usize);
+        history.update(0, 0, 5, (e4, piece_to), &[], [None, None]);
+
+        let ordering = QuietOrdering {
+            killers: [board.parse_uci("g1f3").unwrap(), board.parse_uci("b1c3").unwrap()],
+            countermove: d4,
+            continuations: [None, None],
+        };
+        let moves = order(&board, "", Some(ordering), &history);
+        assert_eq!(&moves[..4], ["g1f3", "b1c3", "d2d4", "e2e4"]);
+        assert_eq!(moves.len(), board.legal_moves().len());
+
+        // killers that are not legal here are skipped, and one that is the
+        // TT move is not tried twice.
+        let ordering = QuietOrdering { killers: [Move::from_uci("e7e5").unwrap(), d4], ..ordering };
+        let moves = order(&board, "d2d4", Some(ordering), &history);
+        assert_eq!(&moves[..2], ["d2d4", "e2e4"]);
+        assert_eq!(moves.len(), board.legal_moves().len());
+    }
+}
@@ -0,0 +1,376 @@
+use std::fs;
+use std::sync::Arc;
+
+use crate::bitboards::Bitboard;
+use crate::colour::WHITE;
+use crate::piece::PieceType;
+use crate::squares::Square;
+
+/// One input for every colour, piece type and square.
+pub const INPUTS: usize = 768;
+/// The quantisation of the first layer, and of the output layer.
+const QA: i16 = 255;
+const QB: i32 = 64;
+/// Converts the output of the network to centipawns.
+const SCALE: i32 = 400;
+const MAGIC: &[u8; 4] = b"ISNN";
+/// Network evaluations are kept well clear of mate scores.
+const MAX_EVAL: i32 = 20_000;
+
+/// A (768 → N)x2 → 1 network. Each side's accumulator sees the board from
+/// that side, and the output layer weighs the side to move's half first.
+///
+/// Weight files are little-endian: the magic `ISNN`, N as a `u32`, then as
+/// `i16`s the 768 × N feature weights (by feature), the N feature biases,
+/// the 2 × N output weights and the output bias.
+#[derive(PartialEq, Eq)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let header = bytes.get(..8).ok_or("network file too short")?;
+        if &header[..4] != MAGIC {
+            return Err("not a network file".to_string());
+        }
+        let hidden = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let expected = 8 + 2 * (INPUTS * hidden + 3 * hidden + 1);
+        if hidden == 0 || bytes.len() != expected {
+            return Err(format!("network file should be {expected} bytes for {hidden} hidden neurons, not {}", bytes.len()));
+        }
+
+        let mut values = bytes[8..].chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut take = |n| values.by_ref().take(n).collect::<Vec<_>>();
+        let feature_weights = take(INPUTS * hidden);
+        let feature_biases = take(hidden);
+        let output_weights = take(2 * hidden);
+        let output_bias = take(1)[0];
+        Ok(Self { hidden, feature_weights, feature_biases, output_weights, output_bias })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub const fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    /// The input for a piece, as seen by `perspective`: its own pieces come
+    /// first, and black sees the board upside down.
+    const fn feature(perspective: usize, colour: usize, piece_type: PieceType, square: Square) -> usize {
+        let (side, square) = if perspective == WHITE { (colour, square) } else { (colour ^ 1, square ^ 0x38) };
+        side * 384 + (piece_type as usize - 1) * 64 + square
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    /// Both sides' accumulators for `state` computed from scratch, white's
+    /// first.
+    fn accumulate(&self, state: &Bitboard, out: &mut [i16]) {
+        let (white, black) = out.split_at_mut(self.hidden);
+        white.copy_from_slice(&self.feature_biases);
+        black.copy_from_slice(&self.feature_biases);
+        for colour in 0..2 {
+            for piece_type in [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King] {
+                let mut bb = state.pieces(piece_type) & state.occupied_co[colour];
+                while bb != 0 {
+                    let square = bb.trailing_zeros() as usize;
+                    add(white, self.weights(Self::feature(WHITE, colour, piece_type, square)));
+                    add(black, self.weights(Self::feature(WHITE ^ 1, colour, piece_type, square)));
+                    bb &= bb - 1;
+                }
+            }
+        }
+    }
+
+    /// The evaluation in centipawns, given the accumulators of the side to
+    /// move and of the other side.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn output(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let sum = dot(us, our_weights).wrapping_add(dot(them, their_weights)).wrapping_add(i32::from(self.output_bias));
+        let eval = i64::from(sum) * i64::from(SCALE) / i64::from(i32::from(QA) * QB);
+        eval.clamp(-i64::from(MAX_EVAL), i64::from(MAX_EVAL)) as i32
+    }
+}
+
+// networks come from files, so nothing stops their weights overflowing
+// an accumulator. The sums wrap, as they do in SIMD, which keeps updates
+// and refreshes in agreement.
+fn add(accumulator: &mut [i16], weights: &[i16]) {
+    for (a, &w) in accumulator.iter_mut().zip(weights) {
+        *a = a.wrapping_add(w);
+    }
+}
+
+fn sub(accumulator: &mut [i16], weights: &[i16]) {
+    for (a, &w) in accumulator.iter_mut().zip(weights) {
+        *a = a.wrapping_sub(w);
+    }
+}
+
+/// The sum of the clipped accumulator values times the weights.
+fn dot(accumulator: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return unsafe { dot_avx2(accumulator, weights) };
+    }
+    dot_scalar(accumulator, weights)
+}
+
+fn dot_scalar(accumulator: &[i16], weights: &[i16]) -> i32 {
+    accumulator.iter().zip(weights).map(|(&a, &w)| i32::from(a.clamp(0, QA)) * i32::from(w)).fold(0, i32::wrapping_add)
+}
+
+/// As `dot_scalar`, sixteen values at a time. Wrapping integer sums come
+/// out the same in any order, so the two agree exactly.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn dot_avx2(accumulator: &[i16], weights: &[i16]) -> i32 {
+    use std::arch::x86_64::{
+        __m256i, _mm256_add_epi32, _mm256_extracti128_si256, _mm256_loadu_si256, _mm256_madd_epi16, _mm256_max_epi16,
+        _mm256_min_epi16, _mm256_set1_epi16, _mm256_setzero_si256, _mm_add_epi32, _mm_cvtsi128_si32, _mm_hadd_epi32,
+    };
+
+    let chunks = accumulator.len().min(weights.len()) / 16;
+    let (zero, max) = (_mm256_setzero_si256(), _mm256_set1_epi16(QA));
+    let mut sum = _mm256_setzero_si256();
+    for i in 0..chunks {
+        let a = _mm256_loadu_si256(accumulator.as_ptr().add(i * 16).cast::<__m256i>());
+        let w = _mm256_loadu_si256(weights.as_ptr().add(i * 16).cast::<__m256i>());
+        let clipped = _mm256_min_epi16(_mm256_max_epi16(a, zero), max);
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+    }
+    let half = _mm_add_epi32(_mm256_extracti128_si256(sum, 0), _mm256_extracti128_si256(sum, 1));
+    let quarter = _mm_hadd_epi32(half, half);
+    let total = _mm_cvtsi128_si32(_mm_hadd_epi32(quarter, quarter));
+    total.wrapping_add(dot_scalar(&accumulator[chunks * 16..], &weights[chunks * 16..]))
+}
+
+/// The first layer of the network for every position since the network
+/// was set, so that a move only adds and takes away the weights of the
+/// pieces it moves and unmaking one is a pop. Without a network this does
+/// nothing.
+#[derive(Clone, Default)]
+pub struct Accumulators {
+    network: Option<Arc<Network>>,
+    /// Both sides' accumulators for each position, white's first.
+    values: Vec<i16>,
+}
+
+/// Accumulators are equal when their values are. Their networks are not
+/// compared, as that would mean comparing every weight.
+impl PartialEq for Accumulators {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+impl Eq for Accumulators {}
+
+impl Accumulators {
+    pub const fn new() -> Self {
+        Self { network: None, values: Vec::new() }
+    }
+
+    pub const fn network(&self) -> Option<&Arc<Network>> {
+        self.network.as_ref()
+    }
+
+    /// Switches to `network`, starting again from `state`.
+    pub fn set_network(&mut self, network: Option<Arc<Network>>, state: &Bitboard) {
+        self.network = network;
+        self.restart(state);
+    }
+
+    /// Forgets the accumulators of earlier positions, starting again from
+    /// `state`.
+    pub fn restart(&mut self, state: &Bitboard) {
+        self.values.clear();
+        self.refresh(state);
+    }
+
+    /// Recomputes the current accumulators from scratch.
+    pub fn refresh(&mut self, state: &Bitboard) {
+        if let Some(network) = &self.network {
+            let size = 2 * network.hidden;
+            let start = self.values.len().saturating_sub(size);
+            self.values.resize(start + size, 0);
+            network.accumulate(state, &mut self.values[start..]);
+        }
+    }
+
+    /// Whether the current accumulators are what `refresh` would make them.
+    pub fn is_fresh(&self, state: &Bitboard) -> bool {
+        self.network.as_ref().is_none_or(|network| {
+            let mut fresh = vec![0; 2 * network.hidden];
+            network.accumulate(state, &mut fresh);
+            self.current() == fresh.as_slice()
+        })
+    }
+
+    fn current(&self) -> &[i16] {
+        let size = self.network.as_ref().map_or(0, |network| 2 * network.hidden);
+        &self.values[self.values.len() - size..]
+    }
+
+    /// Starts the accumulators for the next position as a copy of these.
+    pub fn push(&mut self) {
+        if let Some(network) = &self.network {
+            let size = 2 * network.hidden;
+            self.values.extend_from_within(self.values.len() - size..);
+        }
+    }
+
+    /// Goes back to the accumulators of the previous position.
+    pub fn pop(&mut self) {
+        if let Some(network) = &self.network {
+            self.values.truncate(self.values.len() - 2 * network.hidden);
+        }
+    }
+
+    pub fn add(&mut self, colour: usize, piece_type: PieceType, square: Square) {
+        self.update(colour, piece_type, square, add);
+    }
+
+    pub fn remove(&mut self, colour: usize, piece_type: PieceType, square: Square) {
+        self.update(colour, piece_type, square, sub);
+    }
+
+    fn update(&mut self, colour: usize, piece_type: PieceType, square: Square, apply: fn(&mut [i16], &[i16])) {
+        if let Some(network) = &self.network {
+            let start = self.values.len() - 2 * network.hidden;
+            let (white, black) = self.values[start..].split_at_mut(network.hidden);
+            apply(white, network.weights(Network::feature(WHITE, colour, piece_type, square)));
+            apply(black, network.weights(Network::feature(WHITE ^ 1, colour, piece_type, square)));
+        }
+    }
+
+    /// The network's evaluation from the point of view of `turn`, if there
+    /// is a network.
+    pub fn evaluate(&self, turn: usize) -> Option<i32> {
+        let network = self.network.as_ref()?;
+        let (white, black) = self.current().split_at(network.hidden);
+        Some(if turn == WHITE { network.output(white, black) } else { network.output(black, white) })
+    }
+}
+
+#[cfg(test)]
+mod nnue_tests {
+    use std::sync::Arc;
+
+    use crate::board::Board;
+    use crate::nnue::{dot, dot_scalar, Network, INPUTS, MAX_EVAL, QA};
+
+    /// A tiny network with 16 hidden neurons, made up of pseudo-random
+    /// weights, to check inference against.
+    const TEST_NET: &[u8] = include_bytes!("../nets/test.nnue");
+
+    fn network() -> Arc<Network> {
+        Arc::new(Network::from_bytes(TEST_NET).unwrap())
+    }
+
+    #[test]
+    fn loading() {
+        let network = network();
+        assert_eq!(network.hidden(), 16);
+        assert_eq!(network.feature_weights.len(), INPUTS * 16);
+
+        assert!(Network::from_bytes(&TEST_NET[..TEST_NET.len() - 2]).is_err());
+        assert!(Network::from_bytes(b"ISNN").is_err());
+        let mut bytes = TEST_NET.to_vec();
+        bytes[0] = b'X';
+        assert!(Network::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn inference() {
+        // worked out independently of the engine.
+        for (fen, expected) in [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", -131),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1", -131),
+            ("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", -186),
+            ("8/5pk1/6p1/3P4/1p6/1P3KP1/8/8 b - - 0 40", 461),
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            board.set_network(Some(network()));
+            assert_eq!(board.accumulators().evaluate(board.turn_as_idx()), Some(expected), "{fen}");
+        }
+    }
+
+    #[test]
+    fn simd_matches_scalar() {
+        let accumulator = (0..40).map(|i: i16| i * 37 % 611 - 200).collect::<Vec<_>>();
+        let weights = (0..40).map(|i: i16| 50 - i * 13 % 101).collect::<Vec<_>>();
+        assert_eq!(dot(&accumulator, &weights), dot_scalar(&accumulator, &weights));
+
+        // the sixteen-wide part comes just short of overflowing, and the
+        // rest takes it over.
+        let (accumulator, weights) = (vec![QA; 16 * 16 + 2], vec![i16::MAX; 16 * 16 + 2]);
+        assert_eq!(dot(&accumulator, &weights), dot_scalar(&accumulator, &weights));
+    }
+
+    #[test]
+    fn large_weights() {
+        // weights this large overflow the accumulators, which must wrap
+        // rather than panic.
+        let mut bytes = TEST_NET.to_vec();
+        for (i, weight) in bytes[8..].chunks_exact_mut(2).enumerate() {
+            weight.copy_from_slice(&(if i % 3 == 0 { i16::MIN } else { i16::MAX }).to_le_bytes());
+        }
+        let mut board = Board::new();
+        board.set_network(Some(Arc::new(Network::from_bytes(&bytes).unwrap())));
+        for uci in ["e2e4", "d7d5", "e4d5", "d8d5"] {
+            let m = board.parse_uci(uci).unwrap();
+            board.make(m);
+            assert!(board.accumulators().is_fresh(board.state()), "{uci}");
+            assert!(board.accumulators().evaluate(board.turn_as_idx()).is_some_and(|eval| eval.abs() <= MAX_EVAL));
+        }
+
+        // boards are compared without comparing the networks' weights.
+        let mut other = board.clone();
+        other.set_network(Some(network()));
+        assert!(other != board);
+        other.set_network(board.accumulators().network().cloned());
+        assert!(other == board);
+    }
+
+    #[test]
+    fn incremental() {
+        // make checks the accumulators against fresh ones as it goes.
+        let mut board = Board::from_fen("r3k2r/1P3ppp/8/3pP3/8/8/5PPP/R3K2R w KQkq d6 0 1").unwrap();
+        board.set_network(Some(network()));
+        let start = board.clone();
+        for uci in ["e5d6", "e8g8", "b7a8q", "f8a8", "e1g1", "a8a1", "f1a1"] {
+            let m = board.parse_uci(uci).unwrap();
+            board.make(m);
+            assert!(board.accumulators().is_fresh(board.state()), "{uci}");
+        }
+        board.make_null();
+        while board.move_history().next().is_some() {
+            board.unmake();
+        }
+        assert!(board == start);
+
+        // setting the network part way through a game still allows going
+        // back to the start.
+        let mut board = Board::new();
+        for uci in ["e2e4", "d7d5", "e4d5"] {
+            let m = board.parse_uci(uci).unwrap();
+            board.make(m);
+        }
+        board.set_network(Some(network()));
+        board.unmake();
+        assert!(board.accumulators().is_fresh(board.state()));
+    }
+}
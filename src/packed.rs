@@ -0,0 +1,380 @@
+#![allow(clippy::cast_possible_truncation)]
+
+use std::io::{self, Read, Write};
+
+use crate::bitboards::Bitboard;
+use crate::bitmethods::{into_bb, Bithackable};
+use crate::colour::{Colour, BLACK, WHITE};
+use crate::magicnumbers::{BB_A1, BB_A8, BB_H1, BB_H8, BB_RANK_3, BB_RANK_6};
+use crate::piece::PieceType;
+
+/// A position packed into 28 bytes, following the layout of Marlinformat:
+/// bytes  0- 7: occupancy bitboard (little endian)
+/// bytes  8-23: one nibble per occupied square, in ascending square order
+///              bit 0-2: piece type (pawn = 0 ... king = 5, 6 = rook with castling rights)
+///              bit   3: colour (set for black)
+/// byte     24: bit 7 set if black is to move, bit 0-6 en passant square (64 for none)
+/// byte     25: halfmove clock
+/// bytes 26-27: fullmove number (little endian)
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedBoard {
+    pub occupancy: u64,
+    pub pieces: [u8; 16],
+    pub stm_ep_square: u8,
+    pub halfmove_clock: u8,
+    pub fullmove_number: u16,
+}
+
+const UNMOVED_ROOK: u8 = 6;
+const NO_EP_SQUARE: u8 = 64;
+
+impl PackedBoard {
+    pub const SIZE: usize = 28;
+
+    /// Packs the given state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 32 pieces on the board.
+    pub fn pack(state: &Bitboard, turn: Colour, halfmove_clock: u8, fullmove_number: u16) -> Self {
+        let occupancy = state.occupied();
+        assert!(occupancy.popcount() <= 32, "cannot pack a position with more than 32 pieces");
+
+        let mut pieces = [0; 16];
+        for (i, square) in occupancy.iter_bits().enumerate() {
+            let piece_type = state.piece_type_at(square);
+            let mut nibble = if piece_type == PieceType::Rook && state.castling_rights.test(square) {
+                UNMOVED_ROOK
+            } else {
+                piece_type as u8 - PieceType::Pawn as u8
+            };
+            if state.occupied_co[Colour::Black as usize].test(square) {
+                nibble |= 1 << 3;
+            }
+            pieces[i / 2] |= nibble << ((i % 2) * 4);
+        }
+
+        let ep_square = if state.ep_square.any_set() {
+            state.ep_square.lsb() as u8
+        } else {
+            NO_EP_SQUARE
+        };
+        let stm = if turn == Colour::Black { 1 << 7 } else { 0 };
+
+        Self {
+            occupancy,
+            pieces,
+            stm_ep_square: stm | ep_square,
+            halfmove_clock,
+            fullmove_number,
+        }
+    }
+
+    /// Unpacks into a state, side to move, halfmove clock and fullmove number.
+    pub fn unpack(&self) -> Result<(Bitboard, Colour, u8, u16), &'static str> {
+        let mut state = Bitboard::clear();
+        for (i, square) in self.occupancy.iter_bits().enumerate() {
+            let nibble = (self.pieces[i / 2] >> ((i % 2) * 4)) & 0xF;
+            let colour = (nibble >> 3) as usize;
+            let bb = into_bb(square);
+            match nibble & 7 {
+                0 => state.pawns |= bb,
+                1 => state.knights |= bb,
+                2 => state.bishops |= bb,
+                3 => state.rooks |= bb,
+                4 => state.queens |= bb,
+                5 => state.kings |= bb,
+                UNMOVED_ROOK => {
+                    state.rooks |= bb;
+                    state.castling_rights |= bb;
+                }
+                _ => return Err("invalid piece nibble in packed board"),
+            }
+            state.occupied_co[colour] |= bb;
+        }
+
+        // move generation expects one king each, and castling rights
+        // only on rooks in their own corners.
+        for colour in [WHITE, BLACK] {
+            if (state.kings & state.occupied_co[colour]).popcount() != 1 {
+                return Err("packed board does not have one king each");
+            }
+        }
+        let corners = ((BB_A1 | BB_H1) & state.occupied_co[WHITE]) | ((BB_A8 | BB_H8) & state.occupied_co[BLACK]);
+        if (state.castling_rights & !corners).any_set() {
+            return Err("castling rights off the corners in packed board");
+        }
+
+        let turn = Colour::from(self.stm_ep_square >> 7);
+        let ep_square = self.stm_ep_square & 0x7F;
+        if ep_square < NO_EP_SQUARE {
+            // the square a pawn of the other side has just passed over.
+            let ep = into_bb(ep_square as usize);
+            let (rank, pawn) = if turn == Colour::White { (BB_RANK_6, ep >> 8) } else { (BB_RANK_3, ep << 8) };
+            let theirs = state.pawns & state.occupied_co[turn.flip() as usize];
+            if (ep & rank).none_set() || (ep & state.occupied()).any_set() || (pawn & theirs).none_set() {
+                return Err("invalid en passant square in packed board");
+            }
+            state.ep_square = ep;
+        } else if ep_square > NO_EP_SQUARE {
+            return Err("invalid en passant square in packed board");
+        }
+        // the board counts the plies played in a u16.
+        if self.fullmove_number > 1 << 15 {
+            return Err("fullmove number out of range in packed board");
+        }
+
+        Ok((state, turn, self.halfmove_clock, self.fullmove_number))
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pieces);
+        bytes[24] = self.stm_ep_square;
+        bytes[25] = self.halfmove_clock;
+        bytes[26..28].copy_from_slice(&self.fullmove_number.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut occupancy = [0; 8];
+        occupancy.copy_from_slice(&bytes[0..8]);
+        let mut pieces = [0; 16];
+        pieces.copy_from_slice(&bytes[8..24]);
+        Self {
+            occupancy: u64::from_le_bytes(occupancy),
+            pieces,
+            stm_ep_square: bytes[24],
+            halfmove_clock: bytes[25],
+            fullmove_number: u16::from_le_bytes([bytes[26], bytes[27]]),
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GameResult {
+    BlackWin = 0,
+    Draw = 1,
+    WhiteWin = 2,
+}
+
+impl TryFrom<u8> for GameResult {
+    type Error = &'static str;
+
+    fn try_from(wdl: u8) -> Result<Self, Self::Error> {
+        match wdl {
+            0 => Ok(Self::BlackWin),
+            1 => Ok(Self::Draw),
+            2 => Ok(Self::WhiteWin),
+            _ => Err("invalid game result in packed record"),
+        }
+    }
+}
+
+/// A training record: a packed position, its evaluation in centipawns
+/// from white's point of view, and the result of the game it came from.
+/// Serialised as 32 bytes, the packed board followed by the eval (i16,
+/// little endian), the result, and a byte of padding.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedRecord {
+    pub board: PackedBoard,
+    pub eval: i16,
+    pub result: GameResult,
+}
+
+impl PackedRecord {
+    pub const SIZE: usize = 32;
+
+    pub const fn new(board: PackedBoard, eval: i16, result: GameResult) -> Self {
+        Self { board, eval, result }
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..PackedBoard::SIZE].copy_from_slice(&self.board.to_bytes());
+        bytes[28..30].copy_from_slice(&self.eval.to_le_bytes());
+        bytes[30] = self.result as u8;
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Result<Self, &'static str> {
+        let mut board = [0; PackedBoard::SIZE];
+        board.copy_from_slice(&bytes[..PackedBoard::SIZE]);
+        Ok(Self {
+            board: PackedBoard::from_bytes(&board),
+            eval: i16::from_le_bytes([bytes[28], bytes[29]]),
+            result: GameResult::try_from(bytes[30])?,
+        })
+    }
+}
+
+/// Writes packed records to a byte stream, one after another.
+pub struct RecordWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub fn write(&mut self, record: &PackedRecord) -> io::Result<()> {
+        self.inner.write_all(&record.to_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads packed records from a byte stream until it is exhausted.
+pub struct RecordReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> RecordReader<R> {
+    pub const fn new(inner: R) -> Self {
+        Self { inner }
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<PackedRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; PackedRecord::SIZE];
+        let mut filled = 0;
+        while filled < bytes.len() {
+            match self.inner.read(&mut bytes[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => return Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated packed record"))),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(PackedRecord::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
+
+#[cfg(test)]
+mod packed_tests {
+    use std::io::Cursor;
+
+    use crate::board::{Board, EnPassantMode};
+    use crate::packed::{GameResult, PackedBoard, PackedRecord, RecordReader, RecordWriter};
+
+    const FENS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 13 40",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1",
+    ];
+
+    #[test]
+    fn board_round_trip() {
+        let puzzles = include_str!("../puzzles.txt");
+        for fen in FENS.into_iter().chain(puzzles.lines()) {
+            let board = Board::from_fen(fen).unwrap();
+            let packed = board.to_packed();
+            let bytes = packed.to_bytes();
+            assert_eq!(PackedBoard::from_bytes(&bytes), packed);
+            let unpacked = Board::from_packed(&packed).unwrap();
+            assert_eq!(unpacked.fen_with(EnPassantMode::Fen, false), board.fen_with(EnPassantMode::Fen, false));
+            assert_eq!(unpacked, board);
+        }
+    }
+
+    #[test]
+    fn fullmove_number_range() {
+        let mut packed = Board::from_fen("8/8/8/4k3/8/8/8/4K3 b - - 0 32768").unwrap().to_packed();
+        assert_eq!(Board::from_packed(&packed).unwrap().fen(), "8/8/8/4k3/8/8/8/4K3 b - - 0 32768");
+        packed.fullmove_number = 32769;
+        assert!(Board::from_packed(&packed).is_err());
+        packed.fullmove_number = u16::MAX;
+        assert!(PackedBoard::from_bytes(&packed.to_bytes()).unpack().is_err());
+    }
+
+    #[test]
+    fn invalid_boards() {
+        let packed = |fen| Board::from_fen(fen).unwrap().to_packed();
+        assert!(Board::from_packed(&packed("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2")).is_ok());
+
+        // castling rights on a rook off its corners, or in the other
+        // side's corner. The low nibble is a1 or b1, the high one e1.
+        let mut board = packed("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+        assert_eq!(board.pieces[0], 0x56);
+        assert!(board.unpack().is_ok());
+        board.pieces[0] = 0x5E;
+        assert!(board.unpack().is_err());
+        let mut board = packed("4k3/8/8/8/8/8/8/1R2K3 w - - 0 1");
+        board.pieces[0] = 0x56;
+        assert!(board.unpack().is_err());
+
+        // en passant squares on the wrong rank, occupied, or with no pawn
+        // that could have just moved past them.
+        for (fen, ep_square) in [
+            ("4k3/8/8/3pP3/8/8/8/4K3 b - - 0 2", 43),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 2", 35),
+            ("4k3/8/3p4/3pP3/8/8/8/4K3 w - - 0 2", 43),
+            ("4k3/8/8/4P3/8/8/8/4K3 w - - 0 2", 43),
+            ("4k3/8/8/3PP3/8/8/8/4K3 w - - 0 2", 43),
+        ] {
+            let mut board = packed(fen);
+            board.stm_ep_square = (board.stm_ep_square & 0x80) | ep_square;
+            assert!(board.unpack().is_err(), "{fen}");
+        }
+
+        // a king short, or one too many.
+        assert!(packed("8/8/8/8/8/8/8/4K3 w - - 0 1").unpack().is_err());
+        assert!(packed("4k3/8/8/8/8/8/8/4KK2 w - - 0 1").unpack().is_err());
+    }
+
+    #[test]
+    fn start_position_bytes() {
+        let bytes = Board::new().to_packed().to_bytes();
+        assert_eq!(&bytes[0..8], &0xFFFF_0000_0000_FFFF_u64.to_le_bytes());
+        // a1 is a white rook with castling rights, b1 a white knight.
+        assert_eq!(bytes[8], 0x16);
+        // white to move, no en passant square.
+        assert_eq!(bytes[24], 64);
+    }
+
+    #[test]
+    fn records_stream() {
+        let records = FENS
+            .iter()
+            .enumerate()
+            .map(|(i, fen)| {
+                let board = Board::from_fen(fen).unwrap();
+                let result = [GameResult::WhiteWin, GameResult::Draw, GameResult::BlackWin][i % 3];
+                PackedRecord::new(board.to_packed(), i16::try_from(i).unwrap() * -150, result)
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = RecordWriter::new(Vec::new());
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let bytes = writer.into_inner();
+        assert_eq!(bytes.len(), records.len() * PackedRecord::SIZE);
+
+        let reader = RecordReader::new(Cursor::new(&bytes));
+        let read = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(read, records);
+
+        let mut reader = RecordReader::new(Cursor::new(&bytes[..bytes.len() - 1]));
+        assert_eq!(reader.by_ref().take(FENS.len() - 1).count(), FENS.len() - 1);
+        assert!(reader.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+/// Switches and tunable parameters for the selective parts of the search.
+/// Every technique can be turned off on its own, so that its worth can be
+/// measured against the rest.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchParams {
+    pub null_move: bool,
+    pub lmr: bool,
+    pub reverse_futility: bool,
+    pub futility: bool,
+    pub late_move_pruning: bool,
+    pub razoring: bool,
+    pub check_extensions: bool,
+    pub singular_extensions: bool,
+
+    /// Null move pruning is tried from this depth, reducing by
+    /// `nmp_reduction + depth / nmp_depth_divisor`, plus one for every
+    /// `nmp_eval_divisor` the static evaluation is above beta (up to 3).
+    pub nmp_min_depth: i32,
+    pub nmp_reduction: i32,
+    pub nmp_depth_divisor: i32,
+    pub nmp_eval_divisor: i32,
+    /// From this depth a null move cutoff is verified by a reduced search
+    /// without null moves, to guard against zugzwang.
+    pub nmp_verification_depth: i32,
+    /// Late move reductions are `lmr_base + ln(depth) * ln(move number) /
+    /// lmr_divisor`, both in hundredths.
+    pub lmr_base: i32,
+    pub lmr_divisor: i32,
+    pub rfp_max_depth: i32,
+    pub rfp_margin: i32,
+    pub futility_max_depth: i32,
+    pub futility_base: i32,
+    pub futility_margin: i32,
+    /// At most `lmp_base + depth * depth` quiet moves are searched.
+    pub lmp_max_depth: i32,
+    pub lmp_base: i32,
+    pub razor_max_depth: i32,
+    pub razor_margin: i32,
+    pub singular_min_depth: i32,
+    /// The TT move is singular if every other move fails low against the
+    /// TT score less this much per ply of depth.
+    pub singular_margin: i32,
+}
+
+impl SearchParams {
+    pub const fn new() -> Self {
+        Self {
+            null_move: true,
+            lmr: true,
+            reverse_futility: true,
+            futility: true,
+            late_move_pruning: true,
+            razoring: true,
+            check_extensions: true,
+            singular_extensions: true,
+
+            nmp_min_depth: 3,
+            nmp_reduction: 3,
+            nmp_depth_divisor: 3,
+            nmp_eval_divisor: 200,
+            nmp_verification_depth: 12,
+            lmr_base: 75,
+            lmr_divisor: 225,
+            rfp_max_depth: 8,
+            rfp_margin: 75,
+            futility_max_depth: 6,
+            futility_base: 80,
+            futility_margin: 90,
+            lmp_max_depth: 8,
+            lmp_base: 3,
+            razor_max_depth: 3,
+            razor_margin: 300,
+            singular_min_depth: 8,
+            singular_margin: 2,
+        }
+    }
+
+    /// Every technique turned off, leaving a plain alpha-beta search.
+    pub const fn none() -> Self {
+        Self {
+            null_move: false,
+            lmr: false,
+            reverse_futility: false,
+            futility: false,
+            late_move_pruning: false,
+            razoring: false,
+            check_extensions: false,
+            singular_extensions: false,
+            ..Self::new()
+        }
+    }
+
+    const SWITCHES: [&'static str; 8] = [
+        "NullMove",
+        "LMR",
+        "ReverseFutility",
+        "Futility",
+        "LateMovePruning",
+        "Razoring",
+        "CheckExtensions",
+        "SingularExtensions",
+    ];
+
+    const TUNABLES: [(&'static str, i32, i32); 18] = [
+        ("NmpMinDepth", 1, 20),
+        ("NmpReduction", 0, 8),
+        ("NmpDepthDivisor", 1, 20),
+        ("NmpEvalDivisor", 1, 1000),
+        ("NmpVerificationDepth", 1, 64),
+        ("LmrBase", 0, 300),
+        ("LmrDivisor", 50, 600),
+        ("RfpMaxDepth", 0, 20),
+        ("RfpMargin", 0, 500),
+        ("FutilityMaxDepth", 0, 20),
+        ("FutilityBase", 0, 500),
+        ("FutilityMargin", 0, 500),
+        ("LmpMaxDepth", 0, 20),
+        ("LmpBase", 0, 50),
+        ("RazorMaxDepth", 0, 20),
+        ("RazorMargin", 0, 1000),
+        // below 3 the verification search would have no depth left, and
+        // the quiescence search cannot exclude the move.
+        ("SingularMinDepth", 3, 64),
+        ("SingularMargin", 0, 20),
+    ];
+
+    fn switch_mut(&mut self, name: &str) -> &mut bool {
+        match name {
+            "NullMove" => &mut self.null_move,
+            "LMR" => &mut self.lmr,
+            "ReverseFutility" => &mut self.reverse_futility,
+            "Futility" => &mut self.futility,
+            "LateMovePruning" => &mut self.late_move_pruning,
+            "Razoring" => &mut self.razoring,
+            "CheckExtensions" => &mut self.check_extensions,
+            "SingularExtensions" => &mut self.singular_extensions,
+            _ => unreachable!(),
+        }
+    }
+
+    fn tunable_mut(&mut self, name: &str) -> &mut i32 {
+        match name {
+            "NmpMinDepth" => &mut self.nmp_min_depth,
+            "NmpReduction" => &mut self.nmp_reduction,
+            "NmpDepthDivisor" => &mut self.nmp_depth_divisor,
+            "NmpEvalDivisor" => &mut self.nmp_eval_divisor,
+            "NmpVerificationDepth" => &mut self.nmp_verification_depth,
+            "LmrBase" => &mut self.lmr_base,
+            "LmrDivisor" => &mut self.lmr_divisor,
+            "RfpMaxDepth" => &mut self.rfp_max_depth,
+            "RfpMargin" => &mut self.rfp_margin,
+            "FutilityMaxDepth" => &mut self.futility_max_depth,
+            "FutilityBase" => &mut self.futility_base,
+            "FutilityMargin" => &mut self.futility_margin,
+            "LmpMaxDepth" => &mut self.lmp_max_depth,
+            "LmpBase" => &mut self.lmp_base,
+            "RazorMaxDepth" => &mut self.razor_max_depth,
+            "RazorMargin" => &mut self.razor_margin,
+            "SingularMinDepth" => &mut self.singular_min_depth,
+            "SingularMargin" => &mut self.singular_margin,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The UCI `option` lines for every switch and parameter, with the
+    /// current values as defaults.
+    pub fn uci_options(&self) -> Vec<String> {
+        let mut params = *self;
+        let mut options = Vec::new();
+        for name in Self::SWITCHES {
+            options.push(format!("option name {name} type check default {}", params.switch_mut(name)));
+        }
+        for (name, min, max) in Self::TUNABLES {
+            options.push(format!("option name {name} type spin default {} min {min} max {max}", params.tunable_mut(name)));
+        }
+        options
+    }
+
+    /// Sets the option `name`, ignoring case. Returns `None` if there is no
+    /// such option.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Option<Result<(), String>> {
+        let invalid = || Err(format!("invalid value for {name}: {value}"));
+
+        if let Some(switch) = Self::SWITCHES.into_iter().find(|switch| switch.eq_ignore_ascii_case(name)) {
+            let Ok(on) = value.parse() else {
+                return Some(invalid());
+            };
+            *self.switch_mut(switch) = on;
+            return Some(Ok(()));
+        }
+
+        let (tunable, min, max) = Self::TUNABLES.into_iter().find(|(tunable, ..)| tunable.eq_ignore_ascii_case(name))?;
+        match value.parse() {
+            Ok(n) if (min..=max).contains(&n) => {
+                *self.tunable_mut(tunable) = n;
+                Some(Ok(()))
+            }
+            _ => Some(invalid()),
+        }
+    }
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod params_tests {
+    use crate::params::SearchParams;
+
+    #[test]
+    fn options() {
+        let mut params = SearchParams::new();
+        let options = params.uci_options();
+        assert_eq!(options.len(), SearchParams::SWITCHES.len() + SearchParams::TUNABLES.len());
+        assert!(options.contains(&"option name NullMove type check default true".to_string()));
+        assert!(options.contains(&"option name RfpMargin type spin default 75 min 0 max 500".to_string()));
+
+        assert_eq!(params.set_option("nullmove", "false"), Some(Ok(())));
+        assert!(!params.null_move);
+        assert_eq!(params.set_option("RFPMARGIN", "120"), Some(Ok(())));
+        assert_eq!(params.rfp_margin, 120);
+        assert!(params.uci_options().contains(&"option name NullMove type check default false".to_string()));
+
+        assert!(params.set_option("LMR", "maybe").unwrap().is_err());
+        assert!(params.set_option("LmrDivisor", "10").unwrap().is_err());
+        assert!(params.set_option("SingularMinDepth", "2").unwrap().is_err());
+        assert_eq!(params.set_option("Hash", "16"), None);
+        assert_eq!(params, SearchParams { null_move: false, rfp_margin: 120, ..SearchParams::new() });
+    }
+}
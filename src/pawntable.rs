@@ -0,0 +1,78 @@
+#![allow(clippy::cast_possible_truncation)]
+
+use crate::evaluation::Score;
+
+/// Entries in the pawn hash table of each search thread.
+const PAWN_TABLE_ENTRIES: usize = 1 << 14;
+
+/// The pawn structure of a position, scored once and then found again by
+/// its pawn key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PawnEntry {
+    pub key: u64,
+    /// The pawn structure terms, from white's point of view.
+    pub score: Score,
+    /// The passed pawns, indexed by colour.
+    pub passed: [u64; 2],
+}
+
+/// A small always-replace hash table of pawn structures. Each search
+/// thread has its own, so it needs no locking.
+///
+/// Empty entries have key 0, which is also the key with no pawns on the
+/// board; they hold the right answer for it, a score of nothing.
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        Self { entries: vec![PawnEntry::default(); PAWN_TABLE_ENTRIES] }
+    }
+
+    const fn index(&self, key: u64) -> usize {
+        (key & (self.entries.len() - 1) as u64) as usize
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        let entry = self.entries[self.index(key)];
+        (entry.key == key).then_some(entry)
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = self.index(entry.key);
+        self.entries[index] = entry;
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod pawntable_tests {
+    use crate::board::Board;
+    use crate::evaluation::{evaluate, evaluate_cached};
+    use crate::pawntable::PawnTable;
+
+    #[test]
+    fn cached_evaluation() {
+        let mut pawns = PawnTable::new();
+        let mut board = Board::from_fen("4k3/pp3p2/8/3P4/8/5P2/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(pawns.probe(board.pawn_key()), None);
+        assert_eq!(evaluate_cached(&board, &mut pawns), evaluate(&board));
+        let entry = pawns.probe(board.pawn_key()).unwrap();
+        assert_eq!(entry.passed, [1 << 35, 0]);
+
+        // a king move keeps the pawns, and so the entry.
+        board.make_uci("e1d2").unwrap();
+        assert_eq!(pawns.probe(board.pawn_key()), Some(entry));
+        assert_eq!(evaluate_cached(&board, &mut pawns), evaluate(&board));
+
+        board.make_uci("b7b6").unwrap();
+        assert_eq!(pawns.probe(board.pawn_key()), None);
+        assert_eq!(evaluate_cached(&board, &mut pawns), evaluate(&board));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::colour::Colour;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PieceType {
     None = 0,
@@ -39,6 +40,24 @@ impl Piece {
         }) as char
     }
 
+    pub const fn unicode_symbol(self) -> char {
+        match (self.colour, self.piece_type) {
+            (Colour::White, PieceType::Pawn) => '♙',
+            (Colour::White, PieceType::Knight) => '♘',
+            (Colour::White, PieceType::Bishop) => '♗',
+            (Colour::White, PieceType::Rook) => '♖',
+            (Colour::White, PieceType::Queen) => '♕',
+            (Colour::White, PieceType::King) => '♔',
+            (Colour::Black, PieceType::Pawn) => '♟',
+            (Colour::Black, PieceType::Knight) => '♞',
+            (Colour::Black, PieceType::Bishop) => '♝',
+            (Colour::Black, PieceType::Rook) => '♜',
+            (Colour::Black, PieceType::Queen) => '♛',
+            (Colour::Black, PieceType::King) => '♚',
+            (_, PieceType::None) => '?',
+        }
+    }
+
     pub fn from_symbol(symbol: char) -> Result<Self, &'static str> {
         const VALID_CHARS: &str = "PNBRQKpnbrqk";
         if !VALID_CHARS.contains(symbol) {
@@ -64,3 +83,18 @@ impl Piece {
         })
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Piece {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.symbol())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Piece {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let symbol = char::deserialize(deserializer)?;
+        Self::from_symbol(symbol).map_err(serde::de::Error::custom)
+    }
+}
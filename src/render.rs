@@ -0,0 +1,192 @@
+use std::fmt::Write;
+
+use crate::bitmethods::{into_bb, Bithackable};
+use crate::board::Board;
+use crate::colour::Colour;
+use crate::piece::Piece;
+use crate::squares::{Square, SquareTrait};
+
+const RESET: &str = "\x1b[0m";
+const LIGHT_SQUARE: &str = "\x1b[48;5;223m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const LIGHT_HIGHLIGHT: &str = "\x1b[48;5;150m";
+const DARK_HIGHLIGHT: &str = "\x1b[48;5;106m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+
+/// Options for rendering a board as text, for use in a terminal.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Draw pieces as Unicode chess glyphs rather than FEN letters.
+    pub unicode: bool,
+    /// Colour light and dark squares (and pieces) with ANSI escape codes.
+    pub ansi_colours: bool,
+    /// Label the ranks and files.
+    pub coordinates: bool,
+    /// Draw the board from black's side.
+    pub flipped: bool,
+    /// Highlight the from- and to-squares of the last move played.
+    pub last_move: bool,
+    /// Additional squares to highlight, e.g. from `Board::attackers`.
+    pub highlight: u64,
+}
+
+impl RenderOptions {
+    pub const fn new() -> Self {
+        Self {
+            unicode: false,
+            ansi_colours: false,
+            coordinates: false,
+            flipped: false,
+            last_move: false,
+            highlight: 0,
+        }
+    }
+
+    /// Unicode glyphs, colours, coordinates and last move highlighting.
+    pub const fn pretty() -> Self {
+        Self {
+            unicode: true,
+            ansi_colours: true,
+            coordinates: true,
+            flipped: false,
+            last_move: true,
+            highlight: 0,
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn piece_char(piece: Option<Piece>, options: &RenderOptions) -> char {
+    match piece {
+        None if options.ansi_colours => ' ',
+        None => '.',
+        // on coloured squares the filled glyphs read better for both sides,
+        // the colour of the piece is carried by the foreground colour.
+        Some(piece) if options.unicode && options.ansi_colours => {
+            Piece::new(piece.piece_type, Colour::Black).unicode_symbol()
+        }
+        Some(piece) if options.unicode => piece.unicode_symbol(),
+        Some(piece) => piece.symbol(),
+    }
+}
+
+impl Board {
+    /// Renders the board as text according to `options`.
+    /// Each square takes three columns; without colours, highlighted
+    /// squares are bracketed.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let mut highlight = options.highlight;
+        if options.last_move {
+            if let Some(m) = self.move_history().last() {
+                highlight |= into_bb(m.from_sq()) | into_bb(m.to_sq());
+            }
+        }
+
+        let ranks: Vec<usize> = if options.flipped { (0..8).collect() } else { (0..8).rev().collect() };
+        let files: Vec<usize> = if options.flipped { (0..8).rev().collect() } else { (0..8).collect() };
+
+        let mut out = String::new();
+        for &rank in &ranks {
+            if options.coordinates {
+                write!(out, "{} ", rank + 1).unwrap();
+            }
+            for &file in &files {
+                let square = Square::from_rank_file(rank, file);
+                let piece = self.get_piece_at(square);
+                let symbol = piece_char(piece, options);
+                let highlighted = highlight.test(square);
+                if options.ansi_colours {
+                    let light = (rank + file) % 2 == 1;
+                    let background = match (light, highlighted) {
+                        (true, false) => LIGHT_SQUARE,
+                        (false, false) => DARK_SQUARE,
+                        (true, true) => LIGHT_HIGHLIGHT,
+                        (false, true) => DARK_HIGHLIGHT,
+                    };
+                    let foreground = match piece.map(|p| p.colour) {
+                        Some(Colour::Black) => BLACK_PIECE,
+                        _ => WHITE_PIECE,
+                    };
+                    write!(out, "{background}{foreground} {symbol} {RESET}").unwrap();
+                } else if highlighted {
+                    write!(out, "[{symbol}]").unwrap();
+                } else {
+                    write!(out, " {symbol} ").unwrap();
+                }
+            }
+            out.push('\n');
+        }
+        if options.coordinates {
+            out.push_str("  ");
+            for &file in &files {
+                write!(out, " {} ", &Square::from_rank_file(0, file).name()[..1]).unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod render_tests {
+    use crate::board::Board;
+    use crate::colour::Colour;
+    use crate::render::RenderOptions;
+    use crate::squares::SquareEnum;
+
+    #[test]
+    fn unicode_start_position() {
+        let options = RenderOptions { unicode: true, coordinates: true, ..RenderOptions::new() };
+        let expected = "\
+8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜
+7  ♟  ♟  ♟  ♟  ♟  ♟  ♟  ♟
+6  .  .  .  .  .  .  .  .
+5  .  .  .  .  .  .  .  .
+4  .  .  .  .  .  .  .  .
+3  .  .  .  .  .  .  .  .
+2  ♙  ♙  ♙  ♙  ♙  ♙  ♙  ♙
+1  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖
+   a  b  c  d  e  f  g  h
+";
+        let rendered = Board::new().render(&options);
+        assert_eq!(rendered.lines().map(str::trim_end).collect::<Vec<_>>(), expected.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn flipped_with_last_move() {
+        let mut board = Board::new();
+        board.make_uci("g1f3").unwrap();
+        let options = RenderOptions { coordinates: true, flipped: true, last_move: true, ..RenderOptions::new() };
+        let rendered = board.render(&options);
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "1  R [.] B  K  Q  B  N  R ");
+        assert_eq!(lines[5], "6  .  .  .  .  .  .  .  . ");
+        assert_eq!(lines[2], "3  .  . [N] .  .  .  .  . ");
+        assert_eq!(lines[8], "   h  g  f  e  d  c  b  a ");
+    }
+
+    #[test]
+    fn highlight_attackers() {
+        let board = Board::new();
+        let attackers = board.attackers(Colour::White, SquareEnum::F3 as usize);
+        let options = RenderOptions { highlight: attackers, ..RenderOptions::new() };
+        let rendered = board.render(&options);
+        assert_eq!(rendered.lines().nth(6), Some(" P  P  P  P [P] P [P] P "));
+        assert_eq!(rendered.lines().nth(7), Some(" R  N  B  Q  K  B [N] R "));
+    }
+
+    #[test]
+    fn ansi_colours() {
+        let rendered = Board::new().render(&RenderOptions::pretty());
+        assert!(rendered.contains("\x1b[48;5;137m\x1b[1;97m ♜ \x1b[0m"));
+        assert!(rendered.contains("\x1b[48;5;223m\x1b[1;30m ♞ \x1b[0m"));
+        assert_eq!(rendered.lines().count(), 9);
+    }
+}
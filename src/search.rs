@@ -0,0 +1,1092 @@
+#![allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::cmove::Move;
+use crate::evaluation::{evaluate_cached, PIECE_VALUES};
+use crate::history::{History, PieceTo};
+use crate::movepicker::{MovePicker, QuietOrdering};
+use crate::params::SearchParams;
+use crate::pawntable::PawnTable;
+use crate::dtm::{Dtm, Tables};
+use crate::tablebase::{Tablebase, Wdl};
+use crate::timeman::TimeManager;
+use crate::piece::PieceType;
+use crate::transposition::{Bound, TranspositionTable, DEFAULT_HASH_MB};
+
+pub const MAX_PLY: usize = 128;
+pub const INFINITY: i32 = 32_000;
+pub const MATE: i32 = 31_000;
+/// Scores beyond this are mates, with the distance to mate in plies
+/// being the difference from `MATE`.
+pub const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+pub const MAX_MULTIPV: usize = 256;
+/// Tablebase wins score just below the mates, less the distance from the
+/// root.
+const TB_WIN: i32 = MATE_BOUND - 1;
+
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_MIN_DEPTH: usize = 4;
+/// Captures that cannot raise the static evaluation to within this margin
+/// of alpha are not searched in quiescence.
+const DELTA_MARGIN: i32 = 200;
+const QUEEN_PROMOTION_GAIN: i32 = PIECE_VALUES[PieceType::Queen as usize] - PIECE_VALUES[PieceType::Pawn as usize];
+/// How many nodes are searched between checks of the clock.
+const TIME_CHECK_INTERVAL: u64 = 1024;
+/// Extensions stop once a line is this many times the root depth long.
+const MAX_EXTENSION_FACTOR: usize = 2;
+
+pub const fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/// The number of moves (not plies) to the mate `score` represents,
+/// negative if the side to move is being mated.
+pub const fn mate_in(score: i32) -> i32 {
+    if score > 0 {
+        (MATE - score + 1) / 2
+    } else {
+        -(MATE + score) / 2
+    }
+}
+
+/// Limits on a search. A search with no limits runs to `MAX_PLY`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    /// A hard limit, at which the search stops even mid-iteration.
+    pub time: Option<Duration>,
+    /// A target for playing with a clock: no new iteration is started
+    /// after it, though it is stretched while the best move is unsettled.
+    pub soft_time: Option<Duration>,
+    /// Stop once a mate in this many moves has been found.
+    pub mate: Option<usize>,
+}
+
+impl SearchLimits {
+    pub const fn new() -> Self {
+        Self { depth: None, nodes: None, time: None, soft_time: None, mate: None }
+    }
+
+    pub const fn depth(depth: usize) -> Self {
+        Self { depth: Some(depth), ..Self::new() }
+    }
+
+    pub const fn nodes(nodes: u64) -> Self {
+        Self { nodes: Some(nodes), ..Self::new() }
+    }
+
+    pub const fn time(time: Duration) -> Self {
+        Self { time: Some(time), ..Self::new() }
+    }
+
+    pub const fn mate(moves: usize) -> Self {
+        Self { mate: Some(moves), ..Self::new() }
+    }
+}
+
+/// One of the lines found from the root in a `MultiPV` search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PvLine {
+    /// The score in centipawns from the point of view of the side to move.
+    pub score: i32,
+    pub depth: usize,
+    /// The most plies any line was searched to.
+    pub seldepth: usize,
+    /// The nodes searched by the time the line was found.
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+impl PvLine {
+    /// The principal variation in standard algebraic notation, played out
+    /// from `board`.
+    pub fn san(&self, board: &Board) -> Vec<String> {
+        let mut board = board.clone();
+        self.pv
+            .iter()
+            .map(|&m| {
+                let san = board.san(m);
+                board.make(m);
+                san
+            })
+            .collect()
+    }
+}
+
+/// The outcome of a completed iteration of a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move found, or the null move if there are no legal moves.
+    pub best_move: Move,
+    /// The score in centipawns from the point of view of the side to move.
+    pub score: i32,
+    pub depth: usize,
+    pub seldepth: usize,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub pv: Vec<Move>,
+    /// Every line searched, best first: the principal variation, and with
+    /// `MultiPV` the best lines starting with other moves.
+    pub lines: Vec<PvLine>,
+}
+
+/// A triangular principal variation table: row `ply` holds the best line
+/// found from that ply.
+struct PvTable {
+    moves: Box<[[Move; MAX_PLY]]>,
+    len: [usize; MAX_PLY],
+}
+
+impl PvTable {
+    fn new() -> Self {
+        Self { moves: vec![[Move::null(); MAX_PLY]; MAX_PLY].into_boxed_slice(), len: [0; MAX_PLY] }
+    }
+
+    const fn clear(&mut self, ply: usize) {
+        self.len[ply] = 0;
+    }
+
+    /// Sets the line at `ply` to `m` followed by the line at `ply + 1`.
+    fn update(&mut self, ply: usize, m: Move) {
+        let child_len = if ply + 1 < MAX_PLY { self.len[ply + 1] } else { 0 };
+        self.moves[ply][0] = m;
+        for i in 0..child_len.min(MAX_PLY - 1) {
+            self.moves[ply][i + 1] = self.moves[ply + 1][i];
+        }
+        self.len[ply] = child_len + 1;
+    }
+
+    fn line(&self, ply: usize) -> &[Move] {
+        &self.moves[ply][..self.len[ply]]
+    }
+}
+
+/// Late move reductions by depth and move number, from a log-log formula.
+#[allow(clippy::cast_sign_loss)]
+fn reduction_table(params: &SearchParams) -> Vec<[usize; 64]> {
+    let base = f64::from(params.lmr_base) / 100.0;
+    let divisor = f64::from(params.lmr_divisor) / 100.0;
+    let mut table = vec![[0; 64]; 64];
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (moves, reduction) in row.iter_mut().enumerate().skip(1) {
+            let r = base + f64::from(depth as u32).ln() * f64::from(moves as u32).ln() / divisor;
+            *reduction = r.max(0.0) as usize;
+        }
+    }
+    table
+}
+
+/// What the threads of a search share, besides the transposition table,
+/// and how the search is controlled from outside.
+#[derive(Debug, Default)]
+pub struct SharedState {
+    /// Set to stop every thread.
+    pub stop: AtomicBool,
+    /// While set, the search ignores its time limits. Clearing it (on a
+    /// ponder hit) starts the clock.
+    pub pondering: AtomicBool,
+    /// Set by the main thread once it is done, to stop the helpers.
+    main_done: AtomicBool,
+    /// Nodes searched by helper threads, added in batches.
+    helper_nodes: AtomicU64,
+    pub tablebase: Option<Arc<Tablebase>>,
+    pub dtm: Option<Arc<Tables>>,
+}
+
+impl SharedState {
+    pub const fn new() -> Self {
+        Self {
+            stop: AtomicBool::new(false),
+            pondering: AtomicBool::new(false),
+            main_done: AtomicBool::new(false),
+            helper_nodes: AtomicU64::new(0),
+            tablebase: None,
+            dtm: None,
+        }
+    }
+
+    /// Probes `tablebase` in the search, and at the root to leave out the
+    /// moves that throw away a win or a draw.
+    pub fn with_tablebase(mut self, tablebase: Option<Arc<Tablebase>>) -> Self {
+        self.tablebase = tablebase;
+        self
+    }
+
+    /// Probes the distance-to-mate tables `dtm` in the search.
+    pub fn with_dtm(mut self, dtm: Option<Arc<Tables>>) -> Self {
+        self.dtm = dtm;
+        self
+    }
+
+    pub fn helper_nodes(&self) -> u64 {
+        self.helper_nodes.load(Ordering::Relaxed)
+    }
+}
+
+/// Helper threads skip some depths of iterative deepening, in a pattern
+/// depending on the thread, so that the threads spread out over depths
+/// rather than all searching the same tree.
+const SKIP_SIZE: [usize; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [usize; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+const fn skips_depth(thread_id: usize, depth: usize) -> bool {
+    let i = (thread_id - 1) % SKIP_SIZE.len();
+    !((depth + SKIP_PHASE[i]) / SKIP_SIZE[i]).is_multiple_of(2)
+}
+
+pub struct Searcher<'a> {
+    limits: SearchLimits,
+    params: SearchParams,
+    reductions: Vec<[usize; 64]>,
+    tt: &'a TranspositionTable,
+    shared: &'a SharedState,
+    /// 0 for the main thread, which alone keeps to the limits and stops
+    /// the others.
+    thread_id: usize,
+    start: Instant,
+    nodes: u64,
+    /// Nodes already added to the shared count, for helper threads.
+    flushed_nodes: u64,
+    /// Whether the search is still pondering, as last seen.
+    pondering: bool,
+    stopped: bool,
+    pv: PvTable,
+    history: History,
+    pawns: PawnTable,
+    /// The piece moved and its destination at each ply of the current line,
+    /// `None` for a null move.
+    piece_to: [Option<PieceTo>; MAX_PLY],
+    root_depth: usize,
+    seldepth: usize,
+    /// How many lines to search from the root.
+    multipv: usize,
+    /// Root moves already given a line in this iteration.
+    root_excluded: Vec<Move>,
+    /// The root moves to search, if the tablebases rule some out.
+    root_moves: Vec<Move>,
+    /// Set while verifying a null move cutoff, when null moves are off.
+    verifying: bool,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(limits: SearchLimits, params: SearchParams, tt: &'a TranspositionTable, shared: &'a SharedState) -> Self {
+        Self {
+            limits,
+            params,
+            reductions: reduction_table(&params),
+            tt,
+            shared,
+            thread_id: 0,
+            start: Instant::now(),
+            nodes: 0,
+            flushed_nodes: 0,
+            pondering: false,
+            stopped: false,
+            pv: PvTable::new(),
+            history: History::new(),
+            pawns: PawnTable::new(),
+            piece_to: [None; MAX_PLY],
+            root_depth: 0,
+            seldepth: 0,
+            multipv: 1,
+            root_excluded: Vec::new(),
+            root_moves: Vec::new(),
+            verifying: false,
+        }
+    }
+
+    /// Makes this a helper thread, which searches until the main thread
+    /// stops it.
+    pub const fn helper(mut self, thread_id: usize) -> Self {
+        self.thread_id = thread_id;
+        self
+    }
+
+    /// Searches the best `multipv` lines from the root, rather than just the
+    /// best one.
+    pub fn multipv(mut self, multipv: usize) -> Self {
+        self.multipv = multipv.max(1);
+        self
+    }
+
+    fn is_root_move(&self, m: Move) -> bool {
+        self.root_moves.is_empty() || self.root_moves.contains(&m)
+    }
+
+    const fn is_main(&self) -> bool {
+        self.thread_id == 0
+    }
+
+    /// The nodes searched by every thread so far, as far as is known.
+    fn total_nodes(&self) -> u64 {
+        if self.is_main() {
+            self.nodes + self.shared.helper_nodes()
+        } else {
+            self.nodes
+        }
+    }
+
+    fn flush_nodes(&mut self) {
+        if !self.is_main() {
+            self.shared.helper_nodes.fetch_add(self.nodes - self.flushed_nodes, Ordering::Relaxed);
+            self.flushed_nodes = self.nodes;
+        }
+    }
+
+    /// Searches `board` by iterative deepening until a limit is hit,
+    /// calling `report` after every completed iteration.
+    pub fn search(&mut self, board: &mut Board, mut report: impl FnMut(&SearchResult)) -> SearchResult {
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.flushed_nodes = 0;
+        self.stopped = false;
+        self.pondering = self.shared.pondering.load(Ordering::Relaxed);
+
+        self.root_moves = self.shared.tablebase.as_deref().and_then(|tablebase| tablebase.root_moves(board)).unwrap_or_default();
+        let fallback = match self.root_moves.first() {
+            Some(&m) => m,
+            None => board.legal_moves().into_iter().next().copied().unwrap_or_else(Move::null),
+        };
+        let root_move_count = if self.root_moves.is_empty() { board.legal_moves().len() } else { self.root_moves.len() };
+        let mut result = SearchResult {
+            best_move: fallback,
+            score: 0,
+            depth: 0,
+            seldepth: 0,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+            pv: Vec::new(),
+            lines: Vec::new(),
+        };
+        if fallback == Move::null() {
+            result.score = if board.is_check() { -MATE } else { 0 };
+            return result;
+        }
+
+        let mut max_depth = self.limits.depth.unwrap_or(MAX_PLY - 1).clamp(1, MAX_PLY - 1);
+        if let Some(mate) = self.limits.mate {
+            max_depth = max_depth.min(2 * mate);
+        }
+
+        let mut time_manager = self.limits.soft_time.map(|soft| TimeManager::new(soft, self.limits.time.unwrap_or(soft)));
+        for depth in 1..=max_depth {
+            if !self.is_main() && depth > 1 && skips_depth(self.thread_id, depth) {
+                continue;
+            }
+            self.root_depth = depth;
+            self.seldepth = 0;
+
+            // each line after the first is the best without the moves
+            // already starting one.
+            let mut lines = Vec::new();
+            self.root_excluded.clear();
+            for pv_index in 0..self.multipv.min(root_move_count) {
+                let previous = result.lines.get(pv_index).map_or(result.score, |line| line.score);
+                let score = self.aspiration(board, depth, previous);
+                if self.stopped {
+                    break;
+                }
+                let pv = self.pv.line(0).to_vec();
+                self.root_excluded.push(pv.first().copied().unwrap_or(fallback));
+                lines.push(PvLine { score, depth, seldepth: self.seldepth, nodes: self.total_nodes(), pv });
+            }
+            self.root_excluded.clear();
+            if self.stopped {
+                break;
+            }
+
+            lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+            let score = lines[0].score;
+            result = SearchResult {
+                best_move: lines[0].pv.first().copied().unwrap_or(fallback),
+                score,
+                depth,
+                seldepth: self.seldepth,
+                nodes: self.total_nodes(),
+                elapsed: self.start.elapsed(),
+                pv: lines[0].pv.clone(),
+                lines,
+            };
+            report(&result);
+
+            if let Some(time_manager) = time_manager.as_mut().filter(|_| self.is_main()) {
+                time_manager.update(result.best_move, score);
+                if !self.pondering && time_manager.should_stop(self.start.elapsed()) {
+                    break;
+                }
+            }
+
+            if let Some(mate) = self.limits.mate {
+                if score >= MATE_BOUND && mate_in(score) <= mate as i32 {
+                    break;
+                }
+            }
+        }
+
+        if self.is_main() {
+            self.shared.main_done.store(true, Ordering::Relaxed);
+        }
+        self.flush_nodes();
+        result.nodes = self.nodes;
+        result.elapsed = self.start.elapsed();
+        result
+    }
+
+    /// Searches the root with a narrow window around the previous score,
+    /// widening it whenever the result falls outside.
+    fn aspiration(&mut self, board: &mut Board, depth: usize, previous: i32) -> i32 {
+        if depth < ASPIRATION_MIN_DEPTH || is_mate_score(previous) {
+            return self.negamax(board, depth, 0, -INFINITY, INFINITY, Move::null());
+        }
+
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = (previous - delta).max(-INFINITY);
+        let mut beta = (previous + delta).min(INFINITY);
+        loop {
+            let score = self.negamax(board, depth, 0, alpha, beta, Move::null());
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+            delta *= 2;
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.stopped = true;
+        } else if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            self.flush_nodes();
+            // the clock starts on a ponder hit.
+            if self.pondering && !self.shared.pondering.load(Ordering::Relaxed) {
+                self.pondering = false;
+                self.start = Instant::now();
+            }
+            if let Some(time) = self.limits.time.filter(|_| !self.pondering) {
+                self.stopped = self.start.elapsed() >= time;
+            }
+        }
+        self.stopped = self.stopped
+            || self.shared.stop.load(Ordering::Relaxed)
+            || (!self.is_main() && self.shared.main_done.load(Ordering::Relaxed));
+        self.stopped
+    }
+
+    /// Null move pruning: if passing still leaves the score above beta after
+    /// a reduced search, a real move surely would too. Not tried without
+    /// pieces, where passing may be the only good move, and verified by a
+    /// reduced search without null moves at high depth.
+    fn null_move(&mut self, board: &mut Board, depth: usize, ply: usize, beta: i32, eval: i32) -> Option<i32> {
+        let params = self.params;
+        let d = depth as i32;
+        let state = board.state();
+        let pieces = state.occupied_co[board.turn_as_idx()] & !(state.pawns | state.kings);
+        let after_null = ply > 0 && self.piece_to[ply - 1].is_none();
+        if !params.null_move
+            || self.verifying
+            || after_null
+            || d < params.nmp_min_depth
+            || eval < beta
+            || pieces == 0
+            || is_mate_score(beta)
+        {
+            return None;
+        }
+
+        let r = params.nmp_reduction + d / params.nmp_depth_divisor + ((eval - beta) / params.nmp_eval_divisor).min(3);
+        let reduced_depth = depth.saturating_sub(1 + usize::try_from(r).unwrap_or(0));
+        self.piece_to[ply] = None;
+        board.make_null();
+        let score = -self.negamax(board, reduced_depth, ply + 1, -beta, -beta + 1, Move::null());
+        board.unmake();
+        if self.stopped || score < beta {
+            return None;
+        }
+
+        // a mate found by passing is not to be trusted.
+        let score = if is_mate_score(score) { beta } else { score };
+        if d < params.nmp_verification_depth {
+            return Some(score);
+        }
+        self.verifying = true;
+        let verified = self.negamax(board, reduced_depth, ply, beta - 1, beta, Move::null());
+        self.verifying = false;
+        (verified >= beta).then_some(score)
+    }
+
+    /// Principal variation search. `excluded` is left out of the moves
+    /// searched, when testing whether the TT move is singular.
+    #[allow(clippy::too_many_lines)]
+    fn negamax(&mut self, board: &mut Board, depth: usize, ply: usize, mut alpha: i32, mut beta: i32, excluded: Move) -> i32 {
+        if depth == 0 {
+            return self.qsearch(board, ply, alpha, beta);
+        }
+
+        self.pv.clear(ply);
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        let root = ply == 0;
+        let pv_node = beta - alpha > 1;
+        let singular_search = excluded != Move::null();
+        if !root {
+            if board.halfmove_clock() >= 100 || board.is_repetition() {
+                return 0;
+            }
+
+            // mate distance pruning: no line from here can do better than
+            // mating next move, or worse than being mated now.
+            alpha = alpha.max(-MATE + ply as i32);
+            beta = beta.min(MATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let tt_hit = self.tt.probe(board.key(), ply);
+        if let Some(hit) = tt_hit {
+            if !pv_node && !singular_search && hit.depth >= depth && cutoff(hit.bound, hit.score, alpha, beta) {
+                return hit.score;
+            }
+        }
+
+        // the distance-to-mate tables know how far off the mate is, so
+        // their scores are exact, as long as the mate comes before the
+        // fifty-move rule can end the game.
+        if let Some(dtm) = self.shared.dtm.as_deref() {
+            if !root && !singular_search {
+                let in_time = |plies: u32| u32::from(board.halfmove_clock()) + plies <= 100;
+                if let Some(result) = dtm.probe(board).filter(|&result| match result {
+                    Dtm::Win(plies) | Dtm::Loss(plies) => in_time(plies),
+                    Dtm::Draw => true,
+                }) {
+                    let mate = |plies: u32| {
+                        let score = MATE - ply as i32 - plies as i32;
+                        if score > MATE_BOUND {
+                            score
+                        } else {
+                            TB_WIN - ply as i32
+                        }
+                    };
+                    let score = match result {
+                        Dtm::Win(plies) => mate(plies),
+                        Dtm::Loss(plies) => -mate(plies),
+                        Dtm::Draw => 0,
+                    };
+                    let eval = evaluate_cached(board, &mut self.pawns);
+                    self.tt.store(board.key(), Move::null(), score, eval, depth.min(MAX_PLY - 1), Bound::Exact, ply);
+                    return score;
+                }
+            }
+        }
+
+        // tablebases know the result once few pieces are left, though only
+        // as if the fifty-move counter had just been reset. In PV nodes a
+        // result that does not cut off still bounds the score.
+        let (mut tb_min, mut tb_max) = (-INFINITY, INFINITY);
+        if let Some(tablebase) = self.shared.tablebase.as_deref() {
+            if !root && !singular_search && board.halfmove_clock() == 0 && tablebase.covers(board) {
+                if let Some(wdl) = tablebase.probe_wdl_in_place(board) {
+                    let (score, bound) = match wdl {
+                        Wdl::Win => (TB_WIN - ply as i32, Bound::Lower),
+                        Wdl::Loss => (-TB_WIN + ply as i32, Bound::Upper),
+                        _ => (2 * wdl as i32, Bound::Exact),
+                    };
+                    if cutoff(bound, score, alpha, beta) {
+                        let eval = evaluate_cached(board, &mut self.pawns);
+                        self.tt.store(board.key(), Move::null(), score, eval, depth.min(MAX_PLY - 1), bound, ply);
+                        return score;
+                    }
+                    if bound == Bound::Lower {
+                        tb_min = score;
+                        alpha = alpha.max(score);
+                    } else {
+                        tb_max = score;
+                    }
+                }
+            }
+        }
+
+        let in_check = board.is_check();
+        let eval = match tt_hit {
+            _ if in_check => -INFINITY,
+            Some(hit) => hit.eval,
+            None => evaluate_cached(board, &mut self.pawns),
+        };
+        if ply >= MAX_PLY - 1 {
+            return evaluate_cached(board, &mut self.pawns);
+        }
+
+        let params = self.params;
+        let d = depth as i32;
+        if !pv_node && !in_check && !singular_search {
+            // reverse futility pruning: so far above beta that the opponent
+            // is unlikely to bring the score back down in time.
+            if params.reverse_futility && d <= params.rfp_max_depth && eval - params.rfp_margin * d >= beta && eval < MATE_BOUND {
+                return eval;
+            }
+
+            // razoring: so far below alpha that only a tactic could help,
+            // which quiescence search will find if there is one.
+            if params.razoring && d <= params.razor_max_depth && eval + params.razor_margin * d <= alpha {
+                let score = self.qsearch(board, ply, alpha, alpha + 1);
+                if score <= alpha {
+                    return score;
+                }
+            }
+
+            if let Some(score) = self.null_move(board, depth, ply, beta, eval) {
+                return score;
+            }
+        }
+
+        // singular extension: if every other move fails low against the TT
+        // score less a margin, the TT move is searched a ply deeper. If one
+        // beats beta anyway, there are several good moves and we cut off.
+        let tt_move = tt_hit.map_or(Move::null(), |hit| hit.m);
+        let mut singular = false;
+        if let Some(hit) = tt_hit {
+            if params.singular_extensions
+                && !root
+                && !singular_search
+                && d >= params.singular_min_depth
+                && hit.m != Move::null()
+                && matches!(hit.bound, Bound::Lower | Bound::Exact)
+                && hit.depth + 3 >= depth
+                && !is_mate_score(hit.score)
+            {
+                let singular_beta = hit.score - params.singular_margin * d;
+                let score = self.negamax(board, (depth - 1) / 2, ply, singular_beta - 1, singular_beta, hit.m);
+                if self.stopped {
+                    return 0;
+                }
+                if score < singular_beta {
+                    singular = true;
+                } else if singular_beta >= beta {
+                    return singular_beta;
+                }
+            }
+        }
+
+        let us = board.turn_as_idx();
+        let continuations = [
+            ply.checked_sub(1).and_then(|p| self.piece_to[p]),
+            ply.checked_sub(2).and_then(|p| self.piece_to[p]),
+        ];
+        let ordering = QuietOrdering {
+            killers: self.history.killers(ply),
+            countermove: self.history.countermove(continuations[0]),
+            continuations,
+        };
+        // the best move found here before goes first, which at the root is
+        // the best move of the last iteration.
+        let mut picker = MovePicker::new(tt_move, Some(ordering));
+        self.history.clear_killers(ply + 1);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = Move::null();
+        let mut quiets_tried = [(Move::null(), PieceTo::new(0, PieceType::Pawn, 0)); 64];
+        let mut quiets_count = 0;
+        let mut quiets_seen = 0;
+        let mut searched = 0;
+        let mut legal = 0;
+        while let Some(m) = picker.next_move(board, &self.history) {
+            legal += 1;
+            if m == excluded || (root && (self.root_excluded.contains(&m) || !self.is_root_move(m))) {
+                continue;
+            }
+
+            let quiet = !board.is_capture(m) && !m.is_promotion();
+            // late move pruning and futility pruning: quiet moves late in
+            // the ordering, or that cannot get near alpha, are skipped
+            // unless they give check, once a move has escaped being mated.
+            let prunable = !pv_node && !in_check && quiet && best_score > -MATE_BOUND;
+            let late = params.late_move_pruning && d <= params.lmp_max_depth && quiets_seen >= params.lmp_base + d * d;
+            let futile = params.futility
+                && d <= params.futility_max_depth
+                && eval + params.futility_base + params.futility_margin * d <= alpha;
+            if quiet {
+                quiets_seen += 1;
+            }
+
+            let piece_to = PieceTo::new(us, board.state().piece_type_at(m.from_sq()), m.to_sq());
+            self.piece_to[ply] = Some(piece_to);
+
+            board.make(m);
+            let gives_check = board.is_check();
+            if prunable && (late || futile) && !gives_check {
+                board.unmake();
+                continue;
+            }
+
+            let extend = (singular && m == tt_move) || (params.check_extensions && gives_check);
+            let new_depth = if extend && ply < MAX_EXTENSION_FACTOR * self.root_depth { depth } else { depth - 1 };
+            let score = if searched == 0 {
+                -self.negamax(board, new_depth, ply + 1, -beta, -alpha, Move::null())
+            } else {
+                // late move reductions: later quiet moves are searched less
+                // deeply first, and again at full depth if they beat alpha.
+                let reduction = if params.lmr && quiet && !in_check && !gives_check && depth >= 3 && searched >= 2 {
+                    let r = self.reductions[depth.min(63)][searched.min(63)];
+                    r.saturating_sub(usize::from(pv_node)).min(new_depth - 1)
+                } else {
+                    0
+                };
+                let mut score = -self.negamax(board, new_depth - reduction, ply + 1, -alpha - 1, -alpha, Move::null());
+                if reduction > 0 && score > alpha {
+                    score = -self.negamax(board, new_depth, ply + 1, -alpha - 1, -alpha, Move::null());
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(board, new_depth, ply + 1, -beta, -alpha, Move::null());
+                }
+                score
+            };
+            board.unmake();
+            searched += 1;
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = m;
+                if score > alpha {
+                    alpha = score;
+                    self.pv.update(ply, m);
+                    if alpha >= beta {
+                        if quiet {
+                            let tried = &quiets_tried[..quiets_count];
+                            self.history.update(us, ply, depth, (m, piece_to), tried, continuations);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            if quiet && quiets_count < quiets_tried.len() {
+                quiets_tried[quiets_count] = (m, piece_to);
+                quiets_count += 1;
+            }
+        }
+
+        if legal == 0 {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        // only the excluded move was legal.
+        if searched == 0 {
+            return alpha;
+        }
+
+        let best_score = best_score.clamp(tb_min, tb_max);
+        // a root search without its best moves says nothing about the root.
+        if !singular_search && (!root || self.root_excluded.is_empty()) {
+            let bound = bound(best_score, original_alpha, beta);
+            self.tt.store(board.key(), best_move, best_score, eval, depth, bound, ply);
+        }
+
+        best_score
+    }
+
+    /// Searches captures and queen promotions until the position is quiet,
+    /// so that the static evaluation is never taken in the middle of an
+    /// exchange. When in check, all evasions are searched instead.
+    fn qsearch(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv.clear(ply);
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        if ply >= MAX_PLY - 1 {
+            return evaluate_cached(board, &mut self.pawns);
+        }
+
+        let tt_hit = self.tt.probe(board.key(), ply);
+        if let Some(hit) = tt_hit {
+            if cutoff(hit.bound, hit.score, alpha, beta) {
+                return hit.score;
+            }
+        }
+
+        let original_alpha = alpha;
+        let in_check = board.is_check();
+        let tt_move = tt_hit.map_or(Move::null(), |hit| hit.m);
+        let (stand_pat, mut picker) = if in_check {
+            (-INFINITY, MovePicker::new(tt_move, None))
+        } else {
+            let stand_pat = tt_hit.map_or_else(|| evaluate_cached(board, &mut self.pawns), |hit| hit.eval);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            (stand_pat, MovePicker::tactical(tt_move))
+        };
+
+        let mut best_score = stand_pat;
+        let mut best_move = Move::null();
+        let mut legal = 0;
+        while let Some(m) = picker.next_move(board, &self.history) {
+            legal += 1;
+            if !in_check {
+                // delta pruning: skip captures that cannot bring the score
+                // anywhere near alpha, then those that lose material.
+                let promotion = if m.is_promotion() { QUEEN_PROMOTION_GAIN } else { 0 };
+                if stand_pat + PIECE_VALUES[board.captured_piece(m) as usize] + promotion + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+                if !board.see(m, 0) {
+                    continue;
+                }
+            }
+
+            board.make(m);
+            let score = -self.qsearch(board, ply + 1, -beta, -alpha);
+            board.unmake();
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = m;
+                if score > alpha {
+                    alpha = score;
+                    self.pv.update(ply, m);
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if in_check && legal == 0 {
+            return -MATE + ply as i32;
+        }
+        let bound = bound(best_score, original_alpha, beta);
+        self.tt.store(board.key(), best_move, best_score, stand_pat, 0, bound, ply);
+
+        best_score
+    }
+}
+
+/// Whether a stored score with bound `bound` settles the search of a
+/// position with the window (`alpha`, `beta`).
+const fn cutoff(bound: Bound, score: i32, alpha: i32, beta: i32) -> bool {
+    match bound {
+        Bound::Exact => true,
+        Bound::Lower => score >= beta,
+        Bound::Upper => score <= alpha,
+        Bound::None => false,
+    }
+}
+
+/// The bound a search of the window (`alpha`, `beta`) puts on `score`.
+const fn bound(score: i32, alpha: i32, beta: i32) -> Bound {
+    if score >= beta {
+        Bound::Lower
+    } else if score > alpha {
+        Bound::Exact
+    } else {
+        Bound::Upper
+    }
+}
+
+/// Searches `board` within `limits` on a single thread, calling `report`
+/// after every completed iteration of iterative deepening.
+pub fn search(
+    board: &Board,
+    limits: SearchLimits,
+    tt: &TranspositionTable,
+    report: impl FnMut(&SearchResult),
+) -> SearchResult {
+    crate::smp::lazy_smp(board, limits, SearchParams::new(), 1, 1, tt, &SharedState::new(), report)
+}
+
+/// The best `multipv` lines from `board` within `limits`, best first, with
+/// a transposition table of its own.
+pub fn analyse(board: &Board, limits: SearchLimits, multipv: usize) -> Vec<PvLine> {
+    let tt = TranspositionTable::new(DEFAULT_HASH_MB);
+    crate::smp::lazy_smp(board, limits, SearchParams::new(), 1, multipv, &tt, &SharedState::new(), |_| {}).lines
+}
+
+#[cfg(test)]
+mod search_tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::board::Board;
+    use crate::dtm::{Dtm, Tables};
+    use crate::params::SearchParams;
+    use crate::search::{analyse, mate_in, search, SearchLimits, Searcher, SharedState, MATE, MATE_BOUND};
+    use crate::transposition::TranspositionTable;
+
+    fn best_move(fen: &str, limits: SearchLimits) -> (String, i32) {
+        let board = Board::from_fen(fen).unwrap();
+        let tt = TranspositionTable::new(1);
+        let result = search(&board, limits, &tt, |_| {});
+        assert_eq!(board.fen(), Board::from_fen(fen).unwrap().fen());
+        (result.best_move.to_string(), result.score)
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let (m, score) = best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", SearchLimits::depth(2));
+        assert_eq!(m, "a1a8");
+        assert_eq!(score, MATE - 1);
+        assert_eq!(mate_in(score), 1);
+    }
+
+    #[test]
+    fn mate_in_two() {
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let (m, score) = best_move(fen, SearchLimits::mate(2));
+        assert_eq!(m, "d5f6");
+        assert_eq!(mate_in(score), 2);
+    }
+
+    #[test]
+    fn mated_and_stalemated() {
+        let (m, score) = best_move("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", SearchLimits::depth(3));
+        assert_eq!(m, "0000");
+        assert_eq!(score, -MATE);
+        assert_eq!(mate_in(-MATE + 2), -1);
+
+        let (m, score) = best_move("7k/8/6QK/8/8/8/8/8 b - - 0 1", SearchLimits::depth(3));
+        assert_eq!(m, "0000");
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    fn avoids_defended_pawn() {
+        // taking on d5 loses the queen to the pawn on e6, only seen with
+        // quiescence search at the horizon.
+        let (m, score) = best_move("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", SearchLimits::depth(1));
+        assert_ne!(m, "d1d5");
+        assert!(score > 500 && score < 900, "{score}");
+    }
+
+    #[test]
+    fn wins_hanging_queen() {
+        let (m, score) = best_move("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", SearchLimits::depth(3));
+        assert_eq!(m, "d2d5");
+        assert!(score > 300 && score < MATE_BOUND);
+    }
+
+    #[test]
+    fn pv_is_legal() {
+        let mut board = Board::new();
+        let mut reports = 0;
+        let result = search(&board, SearchLimits::depth(4), &TranspositionTable::new(1), |_| reports += 1);
+        assert_eq!(reports, 4);
+        assert_eq!(result.depth, 4);
+        assert_eq!(result.pv.len(), 4);
+        assert_eq!(result.pv[0], result.best_move);
+        for m in result.pv {
+            board.make_uci(&m.to_string()).unwrap();
+        }
+    }
+
+    #[test]
+    fn respects_limits() {
+        let board = Board::new();
+        let tt = TranspositionTable::new(1);
+        let result = search(&board, SearchLimits::nodes(5_000), &tt, |_| {});
+        assert!(result.nodes <= 5_000);
+        assert!(board.parse_uci(&result.best_move.to_string()).is_ok());
+
+        let result = search(&board, SearchLimits::time(Duration::from_millis(50)), &tt, |_| {});
+        assert!(result.elapsed < Duration::from_millis(500));
+        assert!(result.depth >= 1);
+    }
+
+    #[test]
+    fn selectivity() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let nodes = |params| {
+            let mut board = Board::from_fen(fen).unwrap();
+            let tt = TranspositionTable::new(1);
+            let shared = SharedState::new();
+            Searcher::new(SearchLimits::depth(5), params, &tt, &shared).search(&mut board, |_| {}).nodes
+        };
+        assert!(nodes(SearchParams::new()) < nodes(SearchParams::none()) / 2);
+
+        // each technique on its own, and all of them off, still find the
+        // mate and the free queen.
+        let mut params = SearchParams::none();
+        let mut alone = vec![params];
+        for switch in [
+            |p: &mut SearchParams| p.null_move = true,
+            |p: &mut SearchParams| p.lmr = true,
+            |p: &mut SearchParams| p.reverse_futility = true,
+            |p: &mut SearchParams| p.futility = true,
+            |p: &mut SearchParams| p.late_move_pruning = true,
+            |p: &mut SearchParams| p.razoring = true,
+            |p: &mut SearchParams| p.check_extensions = true,
+            |p: &mut SearchParams| p.singular_extensions = true,
+        ] {
+            params = SearchParams::none();
+            switch(&mut params);
+            alone.push(params);
+        }
+        for params in alone {
+            let tt = TranspositionTable::new(1);
+            let mut board = Board::from_fen("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1").unwrap();
+            let result = Searcher::new(SearchLimits::mate(2), params, &tt, &SharedState::new()).search(&mut board, |_| {});
+            assert_eq!(result.best_move.to_string(), "d5f6", "{params:?}");
+
+            let mut board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+            let result = Searcher::new(SearchLimits::depth(4), params, &tt, &SharedState::new()).search(&mut board, |_| {});
+            assert_eq!(result.best_move.to_string(), "d2d5", "{params:?}");
+        }
+    }
+    #[test]
+    fn multipv() {
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let lines = analyse(&board, SearchLimits::depth(4), 3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].san(&board)[0], "Rxd5");
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score && pair[0].pv[0] != pair[1].pv[0]));
+        assert!(lines.iter().all(|line| line.depth == 4 && line.seldepth >= 4));
+        // the rest lose the rook.
+        assert!(lines[1].score < -300);
+
+        // no more lines than there are legal moves.
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(analyse(&board, SearchLimits::depth(3), 10).len(), 3);
+    }
+
+    #[test]
+    fn dtm_mates() {
+        // the tables give the whole mate from a shallow search.
+        let mut tables = Tables::default();
+        tables.generate("KQvK").unwrap();
+        let mut board = Board::from_fen("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").unwrap();
+        let Some(Dtm::Win(plies)) = tables.probe(&board) else {
+            panic!("KQvK is a win");
+        };
+        let tt = TranspositionTable::new(1);
+        let shared = SharedState::new().with_dtm(Some(Arc::new(tables)));
+        let result = Searcher::new(SearchLimits::depth(2), SearchParams::new(), &tt, &shared).search(&mut board.clone(), |_| {});
+        assert_eq!(result.score, MATE - plies as i32);
+        board.make(result.best_move);
+        assert_eq!(shared.dtm.as_ref().unwrap().probe(&board), Some(Dtm::Loss(plies - 1)));
+    }
+}
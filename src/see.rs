@@ -0,0 +1,106 @@
+use crate::bitmethods::{into_bb, Bithackable};
+use crate::board::Board;
+use crate::cmove::{Move, MoveType};
+use crate::colour::{BLACK, WHITE};
+use crate::evaluation::PIECE_VALUES;
+use crate::movegen::attackers_mask;
+use crate::piece::PieceType;
+
+impl Board {
+    /// Static exchange evaluation: whether the sequence of captures on the
+    /// destination square of `m`, each side always recapturing with its
+    /// least valuable piece, gains the mover at least `threshold`.
+    /// Pins are not taken into account.
+    pub fn see(&self, m: Move, threshold: i32) -> bool {
+        if m.move_type() == MoveType::Castling {
+            return threshold <= 0;
+        }
+
+        let state = self.state();
+        let from = m.from_sq();
+        let to = m.to_sq();
+
+        let captured = if m.move_type() == MoveType::EnPassant { PieceType::Pawn } else { state.piece_type_at(to) };
+        let mut swap = PIECE_VALUES[captured as usize] - threshold;
+        if swap < 0 {
+            return false;
+        }
+
+        swap = PIECE_VALUES[state.piece_type_at(from) as usize] - swap;
+        if swap <= 0 {
+            return true;
+        }
+
+        let mut occupied = state.occupied() ^ into_bb(from) ^ into_bb(to);
+        if m.move_type() == MoveType::EnPassant {
+            occupied ^= into_bb(if self.turn_as_idx() == WHITE { to - 8 } else { to + 8 });
+        }
+
+        let all_attackers = |occupied: u64| {
+            (attackers_mask(state, WHITE, to, occupied) | attackers_mask(state, BLACK, to, occupied)) & occupied
+        };
+        let mut attackers = all_attackers(occupied);
+
+        let mut stm = self.turn_as_idx();
+        let mut result = true;
+        loop {
+            stm ^= 1;
+            attackers &= occupied;
+            let stm_attackers = attackers & state.occupied_co[stm];
+            if stm_attackers.none_set() {
+                break;
+            }
+            result = !result;
+
+            let piece_bbs = [state.pawns, state.knights, state.bishops, state.rooks, state.queens];
+            let Some((i, bb)) = piece_bbs.iter().enumerate().find(|(_, &bb)| (bb & stm_attackers).any_set()) else {
+                // only the king is left to recapture, which it may only do
+                // if the other side has nothing more to recapture with.
+                return if (attackers & state.occupied_co[stm ^ 1]).any_set() { !result } else { result };
+            };
+
+            swap = PIECE_VALUES[i + 1] - swap;
+            if swap < i32::from(result) {
+                break;
+            }
+            occupied ^= into_bb((bb & stm_attackers).lsb());
+            attackers = all_attackers(occupied);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod see_tests {
+    use crate::board::Board;
+
+    fn see(fen: &str, uci: &str, threshold: i32) -> bool {
+        let board = Board::from_fen(fen).unwrap();
+        board.see(board.parse_uci(uci).unwrap(), threshold)
+    }
+
+    #[test]
+    fn exchanges() {
+        // undefended pawn.
+        assert!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5", 100));
+        assert!(!see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5", 101));
+        // pawn defended by a pawn, taken by a knight.
+        assert!(!see("4k3/8/3p4/4p3/8/5N2/8/4K3 w - - 0 1", "f3e5", 0));
+        assert!(see("4k3/8/3p4/4p3/8/5N2/8/4K3 w - - 0 1", "f3e5", -220));
+        // x-ray: the queen behind the rook wins the exchange on d5.
+        assert!(see("3r2k1/8/8/3p4/8/8/3R4/3Q2K1 w - - 0 1", "d2d5", 0));
+        assert!(!see("3r2k1/3r4/8/3p4/8/8/3R4/3Q2K1 w - - 0 1", "d2d5", 0));
+        // a quiet move to an attacked square.
+        assert!(!see("4k3/8/8/8/3p4/8/8/2B1K3 w - - 0 1", "c1e3", 0));
+        assert!(see("4k3/8/8/8/3p4/8/8/2B1K3 w - - 0 1", "c1d2", 0));
+    }
+
+    #[test]
+    fn king_recaptures() {
+        // the king may take back only when the square is no longer defended.
+        assert!(!see("8/8/8/4k3/3p4/8/8/3RK3 w - - 0 1", "d1d4", 0));
+        assert!(see("8/8/8/4k3/3p4/8/8/3RK3 w - - 0 1", "d1d4", -400));
+        assert!(see("8/8/8/8/3pk3/8/2N5/3RK3 w - - 0 1", "d1d4", 100));
+    }
+}
@@ -0,0 +1,145 @@
+use std::thread;
+
+use crate::board::Board;
+use crate::cmove::Move;
+use crate::params::SearchParams;
+use crate::search::{SearchLimits, SearchResult, Searcher, SharedState, MATE_BOUND};
+use crate::transposition::TranspositionTable;
+
+pub const DEFAULT_THREADS: usize = 1;
+pub const MAX_THREADS: usize = 1024;
+
+/// Added to every vote, so that the thread with the lowest score still
+/// counts for something.
+const VOTE_BASE: i64 = 14;
+
+/// Picks the result to play from those of every thread. Threads vote for
+/// their best move, weighted by depth and by how far their score is above
+/// the lowest, and the thread whose move has the most votes wins, unless
+/// another has found a faster mate.
+fn pick_result(results: &[SearchResult]) -> usize {
+    let completed = || results.iter().filter(|result| result.depth > 0);
+    let min_score = completed().map(|result| result.score).min().unwrap_or(0);
+
+    let mut votes: Vec<(Move, i64)> = Vec::new();
+    for result in completed() {
+        let depth = i64::try_from(result.depth).unwrap_or(0);
+        let vote = (i64::from(result.score - min_score) + VOTE_BASE) * depth;
+        match votes.iter_mut().find(|(m, _)| *m == result.best_move) {
+            Some((_, total)) => *total += vote,
+            None => votes.push((result.best_move, vote)),
+        }
+    }
+    let votes_for = |m: Move| votes.iter().find(|&&(voted, _)| voted == m).map_or(0, |&(_, total)| total);
+
+    let mut best = 0;
+    for (i, result) in results.iter().enumerate().skip(1) {
+        let current = &results[best];
+        if result.depth == 0 {
+            continue;
+        }
+        let better = if current.depth == 0 {
+            true
+        } else if current.score >= MATE_BOUND || result.score >= MATE_BOUND {
+            result.score > current.score
+        } else {
+            votes_for(result.best_move) > votes_for(current.best_move)
+        };
+        if better {
+            best = i;
+        }
+    }
+    best
+}
+
+/// Searches `board` with `threads` threads sharing the transposition table
+/// (Lazy SMP). The calling thread is the main thread: it keeps to `limits`,
+/// calls `report` after every iteration it completes and stops the helper
+/// threads when it is done. Each helper searches its own copy of the board,
+/// with its own history, skipping depths so that the threads diverge.
+/// Every thread stops early once `shared.stop` is set. With `multipv` above
+/// one, the main thread's lines are returned as they are, without a vote.
+#[allow(clippy::too_many_arguments)]
+pub fn lazy_smp(
+    board: &Board,
+    limits: SearchLimits,
+    params: SearchParams,
+    threads: usize,
+    multipv: usize,
+    tt: &TranspositionTable,
+    shared: &SharedState,
+    report: impl FnMut(&SearchResult),
+) -> SearchResult {
+    tt.new_search();
+
+    let helper_limits = SearchLimits { depth: limits.depth, mate: limits.mate, ..SearchLimits::new() };
+    thread::scope(|scope| {
+        let mut helpers = Vec::new();
+        for thread_id in 1..threads.clamp(1, MAX_THREADS) {
+            let mut board = board.clone();
+            helpers.push(scope.spawn(move || {
+                Searcher::new(helper_limits, params, tt, shared).helper(thread_id).search(&mut board, |_| {})
+            }));
+        }
+
+        let mut main_board = board.clone();
+        let main = Searcher::new(limits, params, tt, shared).multipv(multipv).search(&mut main_board, report);
+        let nodes = main.nodes;
+
+        let mut results = vec![main];
+        results.extend(helpers.into_iter().map(|helper| helper.join().expect("search thread panicked")));
+        let picked = if multipv > 1 { 0 } else { pick_result(&results) };
+        let mut result = results.swap_remove(picked);
+        result.nodes = nodes + shared.helper_nodes();
+        result
+    })
+}
+
+#[cfg(test)]
+mod smp_tests {
+    use std::time::Duration;
+
+    use crate::board::Board;
+    use crate::cmove::Move;
+    use crate::params::SearchParams;
+    use crate::search::{PvLine, SearchLimits, SearchResult, SharedState, MATE};
+    use crate::smp::{lazy_smp, pick_result};
+    use crate::transposition::TranspositionTable;
+
+    fn result(m: &str, score: i32, depth: usize) -> SearchResult {
+        let best_move = Move::from_uci(m).unwrap();
+        let pv = vec![best_move];
+        let lines = vec![PvLine { score, depth, seldepth: depth, nodes: 0, pv: pv.clone() }];
+        SearchResult { best_move, score, depth, seldepth: depth, nodes: 0, elapsed: Duration::ZERO, pv, lines }
+    }
+
+    #[test]
+    fn voting() {
+        // two threads agreeing outvote a deeper one.
+        let results = [result("e2e4", 20, 10), result("d2d4", 30, 12), result("d2d4", 25, 11)];
+        assert_eq!(pick_result(&results), 1);
+        let results = [result("e2e4", 20, 10), result("d2d4", 15, 10), result("e2e4", 18, 10)];
+        assert_eq!(pick_result(&results), 0);
+
+        // the fastest mate wins, and unfinished threads have no say.
+        let results = [result("e2e4", 20, 10), result("d2d4", MATE - 3, 8), result("g1f3", MATE - 1, 0)];
+        assert_eq!(pick_result(&results), 1);
+    }
+
+    #[test]
+    fn threads() {
+        let tt = TranspositionTable::new(4);
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let board = Board::from_fen(fen).unwrap();
+        let mut reports = 0;
+        let result = lazy_smp(&board, SearchLimits::mate(2), SearchParams::new(), 4, 1, &tt, &SharedState::new(), |_| reports += 1);
+        assert_eq!(result.best_move.to_string(), "d5f6");
+        assert!(reports > 0);
+
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let mut main_nodes = 0;
+        let result = lazy_smp(&board, SearchLimits::depth(6), SearchParams::new(), 3, 1, &tt, &SharedState::new(), |r| main_nodes = r.nodes);
+        assert_eq!(result.best_move.to_string(), "d2d5");
+        assert!(result.nodes >= main_nodes);
+    }
+}
@@ -2,6 +2,7 @@
 
 pub type Square = usize;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(usize)]
 pub enum SquareEnum {
@@ -17,6 +18,7 @@ pub enum SquareEnum {
 
 pub type Rank = usize;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(usize)]
 pub enum RankEnum {
@@ -25,18 +27,31 @@ pub enum RankEnum {
 
 pub type File = usize;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(usize)]
 pub enum FileEnum {
     A, B, C, D, E, F, G, H
 }
 
+pub const SQUARE_NAMES: [&str; 64] = [
+  "a1", "b1", "c1", "d1", "e1", "f1", "g1", "h1",
+  "a2", "b2", "c2", "d2", "e2", "f2", "g2", "h2",
+  "a3", "b3", "c3", "d3", "e3", "f3", "g3", "h3",
+  "a4", "b4", "c4", "d4", "e4", "f4", "g4", "h4",
+  "a5", "b5", "c5", "d5", "e5", "f5", "g5", "h5",
+  "a6", "b6", "c6", "d6", "e6", "f6", "g6", "h6",
+  "a7", "b7", "c7", "d7", "e7", "f7", "g7", "h7",
+  "a8", "b8", "c8", "d8", "e8", "f8", "g8", "h8",
+];
+
 pub trait SquareTrait {
     fn rank(self) -> Rank;
     fn file(self) -> File;
     fn from_rank_file(rank: Rank, file: File) -> Square;
     fn flip_180(self) -> Square;
     fn square_distance(a: Square, b: Square) -> usize;
+    fn name(self) -> &'static str;
 }
 
 impl SquareTrait for Square {
@@ -62,4 +77,8 @@ impl SquareTrait for Square {
     fn file(self) -> File {
         self & 7
     }
+
+    fn name(self) -> &'static str {
+        SQUARE_NAMES[self]
+    }
 }
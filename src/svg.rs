@@ -0,0 +1,420 @@
+#![allow(clippy::cast_precision_loss)]
+
+use std::fmt::Write;
+
+use crate::bitmethods::{into_bb, Bithackable};
+use crate::board::Board;
+use crate::cmove::Move;
+use crate::colour::Colour;
+use crate::piece::{Piece, PieceType};
+use crate::squares::{Square, SquareTrait};
+
+const SQUARE_SIZE: usize = 45;
+const MARGIN: usize = 20;
+
+const LIGHT_SQUARE: &str = "#f0d9b5";
+const DARK_SQUARE: &str = "#b58863";
+const LAST_MOVE: &str = "#cdd16a";
+const COORDINATES: &str = "#e5e5e5";
+const BACKGROUND: &str = "#212121";
+
+/// Piece drawings on a 45x45 square, without fill or stroke, so that one
+/// outline serves both colours.
+const fn piece_outline(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::Pawn => concat!(
+            r#"<path d="M22.5 9a4 4 0 0 0-3.2 6.4 6 6 0 0 0-2.8 5.1 6 6 0 0 0 2.6 4.9"#,
+            r#"c-3.6 1.6-6.6 5.6-6.6 11.1h20c0-5.5-3-9.5-6.6-11.1a6 6 0 0 0 2.6-4.9 6 6 0 0 0-2.8-5.1A4 4 0 0 0 22.5 9z"/>"#,
+        ),
+        PieceType::Knight => concat!(
+            r#"<path d="M22 10c10.5 1 16.5 8 16 29H15c0-9 10-6.5 8-21"/>"#,
+            r#"<path d="M24 18c.4 2.9-5.5 7.4-8 9-3 2-2.8 4.3-5 4-1-.9 1.4-3 0-3-1 0 .2 1.2-1 2-1 0-4 1-4-4 0-2 6-12 6-12s1.9-1.9 2-3.5"#,
+            r#"c-.7-1-.5-2-.5-3 1-1 3 2.5 3 2.5h2s.8-2 2.5-3c1 0 1 3 1 3"/>"#,
+            r#"<circle cx="14" cy="25.5" r="0.8"/>"#,
+        ),
+        PieceType::Bishop => concat!(
+            r#"<path d="M9 36c3.4-1 10.1.4 13.5-2 3.4 2.4 10.1 1 13.5 2 0 0 1.6.5 3 2-.7 1-1.6 1-3 .5"#,
+            r#"-3.4-1-10.1.5-13.5-1-3.4 1.5-10.1 0-13.5 1-1.4.5-2.3.5-3-.5 1.4-1.9 3-2 3-2z"/>"#,
+            r#"<path d="M15 32c2.5 2.5 12.5 2.5 15 0 .5-1.5 0-2 0-2 0-2.5-2.5-4-2.5-4 5.5-1.5 6-11.5-5-15.5"#,
+            r#"-11 4-10.5 14-5 15.5 0 0-2.5 1.5-2.5 4 0 0-.5.5 0 2z"/>"#,
+            r#"<circle cx="22.5" cy="8" r="2.5"/>"#,
+            r#"<path d="M17.5 26h10M15 30h15M22.5 15.5v5M20 18h5" fill="none"/>"#,
+        ),
+        PieceType::Rook => concat!(
+            r#"<path d="M9 39h27v-3H9zM12 36v-4h21v4zM11 14V9h4v2h5V9h5v2h5V9h4v5"#,
+            r#"M34 14l-3 3H14l-3-3M31 17v12.5H14V17M31 29.5l1.5 2.5h-20l1.5-2.5M11 14h23"/>"#,
+        ),
+        PieceType::Queen => concat!(
+            r#"<path d="M9 26c8.5-1.5 21-1.5 27 0l2.5-12.5L31 25l-.3-14.1-5.2 13.6-3-14.5-3 14.5"#,
+            r#"-5.2-13.6L14 25 6.5 13.5 9 26z"/>"#,
+            r#"<path d="M9 26c0 2 1.5 2 2.5 4 1 1.5 1 1 .5 3.5-1.5 1-1 2.5-1 2.5-1.5 1.5 0 2.5 0 2.5"#,
+            r#" 6.5 1 16.5 1 23 0 0 0 1.5-1 0-2.5 0 0 .5-1.5-1-2.5-.5-2.5-.5-2 .5-3.5 1-2 2.5-2 2.5-4"#,
+            r#"-8.5-1.5-18.5-1.5-27 0z"/>"#,
+            r#"<circle cx="6" cy="12" r="2"/><circle cx="14" cy="9" r="2"/><circle cx="22.5" cy="8" r="2"/>"#,
+            r#"<circle cx="31" cy="9" r="2"/><circle cx="39" cy="12" r="2"/>"#,
+        ),
+        PieceType::King => concat!(
+            r#"<path d="M22.5 11.6V6M20 8h5" fill="none"/>"#,
+            r#"<path d="M22.5 25s4.5-7.5 3-10.5c0 0-1-2.5-3-2.5s-3 2.5-3 2.5c-1.5 3 3 10.5 3 10.5"/>"#,
+            r#"<path d="M11.5 37c5.5 3.5 15.5 3.5 21 0v-7s9-4.5 6-10.5c-4-6.5-13.5-3.5-16 4V27v-3.5"#,
+            r#"c-3.5-7.5-13-10.5-16-4-3 6 5 10 5 10V37z"/>"#,
+            r#"<path d="M11.5 30c5.5-3 15.5-3 21 0M11.5 33.5c5.5-3 15.5-3 21 0M11.5 37c5.5-3 15.5-3 21 0" fill="none"/>"#,
+        ),
+        PieceType::None => "",
+    }
+}
+
+fn piece_id(piece: Piece) -> String {
+    let colour = match piece.colour {
+        Colour::White => "white",
+        Colour::Black => "black",
+    };
+    let name = match piece.piece_type {
+        PieceType::Pawn => "pawn",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+        PieceType::None => "none",
+    };
+    format!("{colour}-{name}")
+}
+
+/// An arrow drawn from the centre of one square to another. An arrow from
+/// a square to itself is drawn as a circle around the square.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arrow {
+    pub tail: Square,
+    pub head: Square,
+    /// Any SVG colour. It is escaped when written, so it cannot break out
+    /// of its attribute.
+    pub colour: String,
+}
+
+impl Arrow {
+    pub fn new(tail: Square, head: Square) -> Self {
+        Self { tail, head, colour: "#15781b".to_string() }
+    }
+}
+
+/// Options for rendering a board as an SVG image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgOptions {
+    /// Width and height of the image, in pixels.
+    pub size: usize,
+    /// Label the ranks and files around the board.
+    pub coordinates: bool,
+    /// Draw the board from black's side.
+    pub flipped: bool,
+    /// A move to highlight, typically the last move played.
+    pub last_move: Option<Move>,
+    /// Mark the king of the side to move if it is in check.
+    pub check: bool,
+    pub arrows: Vec<Arrow>,
+    /// Sets of squares to fill with a colour, drawn in order. Colours are
+    /// escaped as for `Arrow`.
+    pub fills: Vec<(u64, String)>,
+}
+
+impl SvgOptions {
+    pub const fn new() -> Self {
+        Self {
+            size: 400,
+            coordinates: true,
+            flipped: false,
+            last_move: None,
+            check: true,
+            arrows: Vec::new(),
+            fills: Vec::new(),
+        }
+    }
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Board {
+    /// Renders the board as a standalone SVG document.
+    #[allow(clippy::too_many_lines)]
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let margin = if options.coordinates { MARGIN } else { 0 };
+        let full_size = 8 * SQUARE_SIZE + 2 * margin;
+
+        // top-left corner of a square in the image.
+        let corner = |square: Square| {
+            let (column, row) = if options.flipped {
+                (7 - square.file(), square.rank())
+            } else {
+                (square.file(), 7 - square.rank())
+            };
+            (margin + column * SQUARE_SIZE, margin + row * SQUARE_SIZE)
+        };
+        let centre = |square: Square| {
+            let (x, y) = corner(square);
+            (x + SQUARE_SIZE / 2, y + SQUARE_SIZE / 2)
+        };
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" version="1.1" width="{}" height="{}" viewBox="0 0 {full_size} {full_size}">"#,
+            options.size, options.size
+        ).unwrap();
+
+        let pieces = (0..64)
+            .filter_map(|square| self.get_piece_at(square).map(|piece| (square, piece)))
+            .collect::<Vec<_>>();
+        let check_square = if options.check && self.is_check() { self.king(self.turn()) } else { None };
+
+        // definitions for the pieces on the board, and the check marker.
+        svg.push_str("<defs>\n");
+        let mut defined = Vec::new();
+        for &(_, piece) in &pieces {
+            if defined.contains(&piece) {
+                continue;
+            }
+            defined.push(piece);
+            let fill = if piece.colour == Colour::White { "#fff" } else { "#000" };
+            writeln!(
+                svg,
+                r##"<g id="{}" fill="{fill}" stroke="#000" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round">{}</g>"##,
+                piece_id(piece),
+                piece_outline(piece.piece_type)
+            ).unwrap();
+        }
+        if check_square.is_some() {
+            svg.push_str(concat!(
+                r##"<radialGradient id="check_gradient" r="0.5">"##,
+                r##"<stop offset="0%" stop-color="#ff0000" stop-opacity="1.0"/>"##,
+                r##"<stop offset="50%" stop-color="#e70000" stop-opacity="1.0"/>"##,
+                r##"<stop offset="100%" stop-color="#9e0000" stop-opacity="0.0"/>"##,
+                "</radialGradient>\n",
+            ));
+        }
+        svg.push_str("</defs>\n");
+
+        if options.coordinates {
+            writeln!(svg, r#"<rect x="0" y="0" width="{full_size}" height="{full_size}" fill="{BACKGROUND}"/>"#).unwrap();
+        }
+
+        // squares, with any highlights.
+        let last_move = options.last_move.map_or(0, |m| into_bb(m.from_sq()) | into_bb(m.to_sq()));
+        for square in 0..64 {
+            let (x, y) = corner(square);
+            let light = (square.rank() + square.file()) % 2 == 1;
+            let fill = if light { LIGHT_SQUARE } else { DARK_SQUARE };
+            writeln!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="{SQUARE_SIZE}" height="{SQUARE_SIZE}" class="square {} {}" fill="{fill}"/>"#,
+                if light { "light" } else { "dark" },
+                square.name()
+            ).unwrap();
+            if last_move.test(square) {
+                writeln!(
+                    svg,
+                    r#"<rect x="{x}" y="{y}" width="{SQUARE_SIZE}" height="{SQUARE_SIZE}" class="lastmove" fill="{LAST_MOVE}" fill-opacity="0.8"/>"#
+                ).unwrap();
+            }
+            for (squares, colour) in &options.fills {
+                if squares.test(square) {
+                    let colour = escape(colour);
+                    writeln!(
+                        svg,
+                        r#"<rect x="{x}" y="{y}" width="{SQUARE_SIZE}" height="{SQUARE_SIZE}" class="fill" fill="{colour}"/>"#
+                    ).unwrap();
+                }
+            }
+            if check_square == Some(square) {
+                writeln!(
+                    svg,
+                    r#"<rect x="{x}" y="{y}" width="{SQUARE_SIZE}" height="{SQUARE_SIZE}" class="check" fill="url(#check_gradient)"/>"#
+                ).unwrap();
+            }
+        }
+
+        if options.coordinates {
+            for i in 0..8 {
+                let file = if options.flipped { 7 - i } else { i };
+                let rank = if options.flipped { i } else { 7 - i };
+                let label_x = margin + i * SQUARE_SIZE + SQUARE_SIZE / 2;
+                let label_y = margin + i * SQUARE_SIZE + SQUARE_SIZE / 2;
+                let file_name = &Square::from_rank_file(0, file).name()[..1];
+                for y in [margin / 2, full_size - margin / 2] {
+                    writeln!(
+                        svg,
+                        r#"<text x="{label_x}" y="{y}" text-anchor="middle" dominant-baseline="central" font-family="sans-serif" font-size="14" fill="{COORDINATES}">{file_name}</text>"#
+                    ).unwrap();
+                }
+                for x in [margin / 2, full_size - margin / 2] {
+                    writeln!(
+                        svg,
+                        r#"<text x="{x}" y="{label_y}" text-anchor="middle" dominant-baseline="central" font-family="sans-serif" font-size="14" fill="{COORDINATES}">{}</text>"#,
+                        rank + 1
+                    ).unwrap();
+                }
+            }
+        }
+
+        for &(square, piece) in &pieces {
+            let (x, y) = corner(square);
+            let id = piece_id(piece);
+            writeln!(svg, r##"<use href="#{id}" xlink:href="#{id}" transform="translate({x}, {y})"/>"##).unwrap();
+        }
+
+        for arrow in &options.arrows {
+            let (tail_x, tail_y) = centre(arrow.tail);
+            let (head_x, head_y) = centre(arrow.head);
+            let colour = &escape(&arrow.colour);
+            if arrow.tail == arrow.head {
+                writeln!(
+                    svg,
+                    r#"<circle cx="{tail_x}" cy="{tail_y}" r="{}" stroke="{colour}" stroke-width="{}" fill="none" opacity="0.8" class="arrow"/>"#,
+                    SQUARE_SIZE as f64 * 0.45,
+                    SQUARE_SIZE as f64 * 0.1
+                ).unwrap();
+                continue;
+            }
+            write_arrow(&mut svg, (tail_x as f64, tail_y as f64), (head_x as f64, head_y as f64), colour);
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// `text` with the characters that are special in XML attributes escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_arrow(svg: &mut String, tail: (f64, f64), head: (f64, f64), colour: &str) {
+    let size = SQUARE_SIZE as f64;
+    let marker_size = 0.75 * size;
+    let marker_margin = 0.1 * size;
+
+    let (dx, dy) = (head.0 - tail.0, head.1 - tail.1);
+    let length = dx.hypot(dy);
+    let (ux, uy) = (dx / length, dy / length);
+
+    // the shaft stops where the arrowhead starts.
+    let shaft_x = (-ux).mul_add(marker_size + marker_margin, head.0);
+    let shaft_y = (-uy).mul_add(marker_size + marker_margin, head.1);
+    let tip_x = (-ux).mul_add(marker_margin, head.0);
+    let tip_y = (-uy).mul_add(marker_margin, head.1);
+    let half_width = marker_size / 2.0;
+    let (left_x, left_y) = (uy.mul_add(half_width, shaft_x), (-ux).mul_add(half_width, shaft_y));
+    let (right_x, right_y) = ((-uy).mul_add(half_width, shaft_x), ux.mul_add(half_width, shaft_y));
+
+    writeln!(
+        svg,
+        r#"<line x1="{:.2}" y1="{:.2}" x2="{shaft_x:.2}" y2="{shaft_y:.2}" stroke="{colour}" stroke-width="{:.2}" stroke-linecap="butt" opacity="0.8" class="arrow"/>"#,
+        tail.0,
+        tail.1,
+        size * 0.2
+    ).unwrap();
+    writeln!(
+        svg,
+        r#"<polygon points="{tip_x:.2},{tip_y:.2} {left_x:.2},{left_y:.2} {right_x:.2},{right_y:.2}" fill="{colour}" opacity="0.8" class="arrow"/>"#
+    ).unwrap();
+}
+
+#[cfg(test)]
+mod svg_tests {
+    use crate::board::Board;
+    use crate::cmove::Move;
+    use crate::squares::SquareEnum::{E2, E4, G1, F3};
+    use crate::svg::{Arrow, SvgOptions};
+
+    #[test]
+    fn start_position() {
+        let svg = Board::new().to_svg(&SvgOptions::new());
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<use ").count(), 32);
+        assert_eq!(svg.matches(r#"class="square "#).count(), 64);
+        // 12 distinct pieces are defined once each.
+        assert_eq!(svg.matches("<g id=").count(), 12);
+        assert!(!svg.contains("check_gradient"));
+        // a8 is drawn in the top-left corner.
+        assert!(svg.contains(r#"<rect x="20" y="20" width="45" height="45" class="square light a8""#));
+        assert!(svg.contains(r##"<use href="#black-rook" xlink:href="#black-rook" transform="translate(20, 20)"/>"##));
+    }
+
+    #[test]
+    fn flipped_without_coordinates() {
+        let options = SvgOptions { coordinates: false, flipped: true, size: 360, ..SvgOptions::new() };
+        let svg = Board::new().to_svg(&options);
+        assert!(svg.contains(r#"viewBox="0 0 360 360""#));
+        assert!(svg.contains(r#"<rect x="0" y="0" width="45" height="45" class="square light h1""#));
+        assert!(!svg.contains("<text"));
+    }
+
+    #[test]
+    fn highlights_and_arrows() {
+        let mut board = Board::new();
+        board.make_uci("e2e4").unwrap();
+        let options = SvgOptions {
+            last_move: board.move_history().last(),
+            arrows: vec![Arrow::new(G1 as usize, F3 as usize), Arrow::new(E4 as usize, E4 as usize)],
+            fills: vec![(board.attackers(crate::colour::Colour::White, F3 as usize), "#ff000080".to_string())],
+            ..SvgOptions::new()
+        };
+        let svg = board.to_svg(&options);
+        assert_eq!(svg.matches(r#"class="lastmove""#).count(), 2);
+        // the pawn on g2, the knight on g1 and the queen on d1, through e2.
+        assert_eq!(svg.matches(r#"class="fill""#).count(), 3);
+        assert_eq!(svg.matches("<line ").count(), 1);
+        assert_eq!(svg.matches("<polygon ").count(), 1);
+        assert_eq!(svg.matches(r#"<circle cx="222" cy="222""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="arrow"/>"#).count(), 3);
+        assert_eq!(Move::new(E2 as usize, E4 as usize), options.last_move.unwrap());
+    }
+
+    #[test]
+    fn colours_are_escaped() {
+        let colour = r#"red" onload="alert(1)"><script>alert(2)</script><x y=""#.to_string();
+        let options = SvgOptions {
+            arrows: vec![Arrow { colour: colour.clone(), ..Arrow::new(G1 as usize, F3 as usize) }],
+            fills: vec![(1 << (E4 as usize), colour)],
+            ..SvgOptions::new()
+        };
+        let svg = Board::new().to_svg(&options);
+        assert!(!svg.contains(r#"onload=""#) && !svg.contains("<script"));
+        assert!(svg.contains(r#"fill="red&quot; onload=&quot;alert(1)&quot;&gt;&lt;script&gt;"#));
+        assert_eq!(svg.matches("<line ").count(), 1);
+    }
+
+    #[test]
+    fn check_highlight() {
+        let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let svg = board.to_svg(&SvgOptions::new());
+        assert!(svg.contains(r#"<rect x="200" y="335" width="45" height="45" class="check" fill="url(#check_gradient)"/>"#));
+        let svg = board.to_svg(&SvgOptions { check: false, ..SvgOptions::new() });
+        assert!(!svg.contains("check_gradient"));
+    }
+
+    #[test]
+    fn puzzles() {
+        for fen in include_str!("../puzzles.txt").lines() {
+            let board = Board::from_fen(fen).unwrap();
+            let svg = board.to_svg(&SvgOptions::new());
+            assert_eq!(svg.matches("<svg").count(), svg.matches("</svg>").count());
+            assert_eq!(svg.matches("<defs>").count(), 1);
+        }
+    }
+}
@@ -0,0 +1,1177 @@
+#![allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+
+//! Probing Syzygy endgame tablebases: win/draw/loss (`.rtbw`) tables for
+//! the search, and distance-to-zeroing (`.rtbz`) tables to pick moves at
+//! the root that keep a win within the fifty-move rule.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::ops::Neg;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, OnceLock};
+
+use crate::bitmethods::Bithackable;
+use crate::board::Board;
+use crate::cmove::Move;
+use crate::colour::BLACK;
+use crate::magicnumbers::BB_KING_ATTACKS;
+use crate::piece::PieceType;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+/// The most pieces any table has.
+const TB_PIECES: usize = 7;
+/// Larger than any distance to zeroing, for ranking root moves.
+const MAX_DTZ: i32 = 1 << 18;
+
+/// The flags of a table for a side to move and leading file.
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// The first byte of a table, after the magic.
+const HEADER_SPLIT: u8 = 1;
+const HEADER_HAS_PAWNS: u8 = 2;
+
+/// The result of a position with best play, counting wins that the
+/// fifty-move rule turns into draws as cursed, and such losses as blessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    const fn from_value(value: i32) -> Option<Self> {
+        match value {
+            -2 => Some(Self::Loss),
+            -1 => Some(Self::BlessedLoss),
+            0 => Some(Self::Draw),
+            1 => Some(Self::CursedWin),
+            2 => Some(Self::Win),
+            _ => None,
+        }
+    }
+
+    const fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self {
+            Self::Loss => Self::Win,
+            Self::BlessedLoss => Self::CursedWin,
+            Self::Draw => Self::Draw,
+            Self::CursedWin => Self::BlessedLoss,
+            Self::Win => Self::Loss,
+        }
+    }
+}
+
+/// The distance to zeroing of a position where the best move is a capture
+/// or a pawn move with result `wdl`.
+const fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Loss => -1,
+        Wdl::BlessedLoss => -101,
+        Wdl::Draw => 0,
+        Wdl::CursedWin => 101,
+        Wdl::Win => 1,
+    }
+}
+
+/// How far a square is below the a1-h8 diagonal, negative above it.
+const fn off_a1h8(square: usize) -> i32 {
+    (square >> 3) as i32 - (square & 7) as i32
+}
+
+/// The tables that turn piece placements into table indices.
+struct Indices {
+    /// Squares below the a1-h8 diagonal.
+    map_b1h1h7: [u64; 64],
+    /// Squares of the a1-d1-d4 triangle, those off the diagonal first.
+    map_a1d1d4: [usize; 64],
+    /// The two kings, the first in the a1-d1-d4 triangle.
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; 6],
+    /// Pawn squares, ordered so that the leading pawn sorts last.
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indices {
+    fn new() -> Self {
+        let mut indices = Self {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                indices.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut code = 0;
+        let mut diagonal = Vec::new();
+        for square in 0..28 {
+            if off_a1h8(square) < 0 && square & 7 <= 3 {
+                indices.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 && square & 7 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            indices.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // kings next to each other are left out, and so is the second king
+        // above the diagonal while the first is on it.
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for idx in 0..10 {
+            for (s1, &attacks) in BB_KING_ATTACKS.iter().enumerate().take(28) {
+                if indices.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    if (attacks | 1 << s1).test(s2) || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        indices.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            indices.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        indices.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                indices.binomial[k][n] = if k > 0 { indices.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { indices.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 48;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        available -= 1;
+                        indices.map_pawns[square] = available;
+                        available -= 1;
+                        indices.map_pawns[square ^ 7] = available;
+                    }
+                    indices.lead_pawn_idx[lead_pawns][square] = idx;
+                    idx += indices.binomial[lead_pawns - 1][indices.map_pawns[square]];
+                }
+                indices.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+        indices
+    }
+}
+
+static INDICES: LazyLock<Indices> = LazyLock::new(Indices::new);
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64_be(bytes: &[u8], offset: usize) -> Option<u64> {
+    bytes.get(offset..offset + 8).and_then(|b| b.try_into().ok()).map(u64::from_be_bytes)
+}
+
+/// The compressed values of a table for one side to move and leading file,
+/// as offsets into the file.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    block_size: usize,
+    span: u64,
+    num_blocks: usize,
+    max_sym_len: u8,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    btree: usize,
+    block_lengths: usize,
+    block_lengths_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    /// The smallest code of each length, left-aligned.
+    base64: Vec<u64>,
+    /// How many values each symbol stands for, less one.
+    symlen: Vec<u8>,
+    /// The pieces in the order the table indexes them.
+    pieces: [u8; TB_PIECES],
+    /// The pieces indexed together, from the first piece on.
+    group_len: [usize; TB_PIECES + 1],
+    group_idx: [u64; TB_PIECES + 1],
+    /// Where the values for each result start in a DTZ table's map.
+    map_idx: [usize; 4],
+}
+
+impl PairsData {
+    /// Reads the sizes of the compressed data starting at `offset`,
+    /// returning the offset after them.
+    fn set_sizes(&mut self, bytes: &[u8], mut offset: usize) -> Option<usize> {
+        self.flags = *bytes.get(offset)?;
+        offset += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = *bytes.get(offset)?;
+            return Some(offset + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let tb_size = self.group_idx[groups];
+        self.block_size = 1 << bytes.get(offset)?;
+        self.span = 1 << bytes.get(offset + 1)?;
+        self.sparse_index_size = tb_size.div_ceil(self.span) as usize;
+        let padding = usize::from(*bytes.get(offset + 2)?);
+        self.num_blocks = read_u32(bytes, offset + 3)? as usize;
+        self.block_lengths_size = self.num_blocks + padding;
+        self.max_sym_len = *bytes.get(offset + 7)?;
+        self.min_sym_len = *bytes.get(offset + 8)?;
+        offset += 9;
+        self.lowest_sym = offset;
+
+        let lengths = usize::from(self.max_sym_len.checked_sub(self.min_sym_len)?) + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = u64::from(read_u16(bytes, offset + 2 * i)?);
+            let next_lowest = u64::from(read_u16(bytes, offset + 2 * i + 2)?);
+            self.base64[i] = self.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base.checked_shl((64 - i - usize::from(self.min_sym_len)) as u32).unwrap_or(0);
+        }
+        offset += 2 * lengths;
+
+        let symbols = usize::from(read_u16(bytes, offset)?);
+        offset += 2;
+        self.btree = offset;
+        if bytes.len() < offset + 3 * symbols {
+            return None;
+        }
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.symlen[symbol] = self.set_symlen(bytes, symbol, &mut visited)?;
+            }
+        }
+        Some(offset + 3 * symbols + (symbols & 1))
+    }
+
+    fn set_symlen(&mut self, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[symbol] = true;
+        let right = self.right(bytes, symbol);
+        if right == 0xFFF {
+            return Some(0);
+        }
+        let left = self.left(bytes, symbol);
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.symlen[child] = self.set_symlen(bytes, child, visited)?;
+            }
+        }
+        Some(self.symlen[left].wrapping_add(self.symlen[right]).wrapping_add(1))
+    }
+
+    /// The first symbol of a pair, or the value of a leaf.
+    fn left(&self, bytes: &[u8], symbol: usize) -> usize {
+        let entry = &bytes[self.btree + 3 * symbol..];
+        usize::from(entry[1] & 0xF) << 8 | usize::from(entry[0])
+    }
+
+    /// The second symbol of a pair, or 0xFFF for a leaf.
+    fn right(&self, bytes: &[u8], symbol: usize) -> usize {
+        let entry = &bytes[self.btree + 3 * symbol..];
+        usize::from(entry[2]) << 4 | usize::from(entry[1] >> 4)
+    }
+
+    fn block_length(&self, bytes: &[u8], block: usize) -> Option<i64> {
+        read_u16(bytes, self.block_lengths + 2 * block).map(i64::from)
+    }
+
+    /// The value at `idx`: the blocks are Huffman coded symbols, each
+    /// standing for a value or a pair of symbols, and a sparse index gives
+    /// the block and offset of every `span`th value.
+    fn decompress(&self, bytes: &[u8], idx: u64) -> Option<usize> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(usize::from(self.min_sym_len));
+        }
+
+        let entry = self.sparse_index + 6 * (idx / self.span) as usize;
+        let mut block = read_u32(bytes, entry)? as usize;
+        let mut offset = i64::from(read_u16(bytes, entry + 4)?) + (idx % self.span) as i64 - (self.span / 2) as i64;
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += self.block_length(bytes, block)? + 1;
+        }
+        while offset > self.block_length(bytes, block)? {
+            offset -= self.block_length(bytes, block)? + 1;
+            block += 1;
+        }
+
+        let mut ptr = self.data + block * self.block_size;
+        let mut buf = read_u64_be(bytes, ptr)?;
+        ptr += 8;
+        let mut buf_size = 64;
+        let min_sym_len = usize::from(self.min_sym_len);
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buf < *self.base64.get(len)? {
+                len += 1;
+            }
+            symbol = ((buf - self.base64[len]).checked_shr((64 - len - min_sym_len) as u32).unwrap_or(0)) as usize;
+            symbol += usize::from(read_u16(bytes, self.lowest_sym + 2 * len)?);
+            let length = i64::from(*self.symlen.get(symbol)?) + 1;
+            if offset < length {
+                break;
+            }
+            offset -= length;
+            len += min_sym_len;
+            buf = buf.checked_shl(len as u32).unwrap_or(0);
+            buf_size -= len;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= u64::from(read_u32_be(bytes, ptr)?) << (64 - buf_size);
+                ptr += 4;
+            }
+        }
+
+        while self.symlen[symbol] != 0 {
+            let left = self.left(bytes, symbol);
+            let length = i64::from(self.symlen[left]) + 1;
+            if offset < length {
+                symbol = left;
+            } else {
+                offset -= length;
+                symbol = self.right(bytes, symbol);
+            }
+        }
+        Some(self.left(bytes, symbol))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Wdl,
+    Dtz,
+}
+
+/// A table file, loaded the first time it is probed.
+struct Table {
+    kind: Kind,
+    path: PathBuf,
+    /// The material key with the pieces as named, and with the colours
+    /// swapped.
+    key: u64,
+    key2: u64,
+    piece_count: usize,
+    has_pawns: bool,
+    /// Whether some side has a lone piece other than the king.
+    has_unique_pieces: bool,
+    /// The pawns of the side whose pawns lead, then of the other side.
+    pawn_count: [usize; 2],
+    data: OnceLock<Option<TableData>>,
+}
+
+/// The contents of a table file.
+struct TableData {
+    bytes: Vec<u8>,
+    /// By side to move, then by the file of the leading pawn.
+    items: Vec<Vec<PairsData>>,
+    /// Where the map from values to distances of a DTZ table starts.
+    map: usize,
+}
+
+impl TableData {
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let side = &self.items[stm % self.items.len()];
+        &side[file % side.len()]
+    }
+
+    /// The distance to zeroing in plies for a stored DTZ value.
+    fn map_dtz(&self, file: usize, wdl: Wdl, value: usize) -> Option<i32> {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let item = self.get(0, file);
+        let mut value = value as i32;
+        if item.flags & FLAG_MAPPED != 0 {
+            let idx = item.map_idx[WDL_MAP[(wdl as i32 + 2) as usize]] + value as usize;
+            value = if item.flags & FLAG_WIDE != 0 {
+                i32::from(read_u16(&self.bytes, self.map + 2 * idx)?)
+            } else {
+                i32::from(*self.bytes.get(self.map + idx)?)
+            };
+        }
+        if (wdl == Wdl::Win && item.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && item.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+/// The material key of the pieces on each side, as `Bitboard::material_key`.
+pub fn material_key(white: &[PieceType], black: &[PieceType]) -> u64 {
+    let key = |pieces: &[PieceType], colour: usize| {
+        pieces.iter().map(|&piece_type| 1 << (4 * (6 * colour + piece_type as usize - 1))).sum::<u64>()
+    };
+    key(white, 0) + key(black, 1)
+}
+
+/// The pieces on each side for a table named like `KRPvKR`.
+pub fn parse_material(name: &str) -> Option<[Vec<PieceType>; 2]> {
+    let side = |pieces: &str| {
+        let pieces = pieces
+            .chars()
+            .map(|c| match c {
+                'P' => Some(PieceType::Pawn),
+                'N' => Some(PieceType::Knight),
+                'B' => Some(PieceType::Bishop),
+                'R' => Some(PieceType::Rook),
+                'Q' => Some(PieceType::Queen),
+                'K' => Some(PieceType::King),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        (pieces.iter().filter(|&&piece_type| piece_type == PieceType::King).count() == 1).then_some(pieces)
+    };
+    let (white, black) = name.split_once('v')?;
+    let material = [side(white)?, side(black)?];
+    (material[0].len() + material[1].len() <= TB_PIECES).then_some(material)
+}
+
+impl Table {
+    fn new(kind: Kind, path: PathBuf, [white, black]: &[Vec<PieceType>; 2]) -> Self {
+        let pawns = |pieces: &[PieceType]| pieces.iter().filter(|&&piece_type| piece_type == PieceType::Pawn).count();
+        let (white_pawns, black_pawns) = (pawns(white), pawns(black));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let unique = |pieces: &[PieceType]| {
+            [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen]
+                .iter()
+                .any(|piece_type| pieces.iter().filter(|&p| p == piece_type).count() == 1)
+        };
+        Self {
+            kind,
+            path,
+            key: material_key(white, black),
+            key2: material_key(black, white),
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: unique(white) || unique(black),
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            data: OnceLock::new(),
+        }
+    }
+
+    /// The contents of the file, or `None` if it cannot be read.
+    fn data(&self) -> Option<&TableData> {
+        self.data.get_or_init(|| self.read()).as_ref()
+    }
+
+    fn read(&self) -> Option<TableData> {
+        let bytes = fs::read(&self.path).ok()?;
+        let magic = if self.kind == Kind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.get(..4)? != magic {
+            return None;
+        }
+        let header = *bytes.get(4)?;
+        if (header & HEADER_HAS_PAWNS != 0) != self.has_pawns
+            || (self.kind == Kind::Wdl && (header & HEADER_SPLIT != 0) != (self.key != self.key2))
+        {
+            return None;
+        }
+        let mut offset = 5;
+
+        let sides = if self.kind == Kind::Wdl && self.key != self.key2 { 2 } else { 1 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+        for file in 0..files {
+            let first = *bytes.get(offset)?;
+            let second = if pawns_on_both_sides { *bytes.get(offset + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            offset += 1 + usize::from(pawns_on_both_sides);
+            for k in 0..self.piece_count {
+                let byte = *bytes.get(offset)?;
+                for (side, item) in items.iter_mut().enumerate() {
+                    item[file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xF };
+                }
+                offset += 1;
+            }
+            for (side, item) in items.iter_mut().enumerate() {
+                self.set_groups(&mut item[file], order[side], file);
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for item in &mut items {
+                offset = item[file].set_sizes(&bytes, offset)?;
+            }
+        }
+        let map = offset;
+        if self.kind == Kind::Dtz {
+            for item in &mut items[0] {
+                if item.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if item.flags & FLAG_WIDE != 0 {
+                    offset += offset & 1;
+                    for i in 0..4 {
+                        item.map_idx[i] = (offset - map) / 2 + 1;
+                        offset += 2 * usize::from(read_u16(&bytes, offset)?) + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        item.map_idx[i] = offset - map + 1;
+                        offset += usize::from(*bytes.get(offset)?) + 1;
+                    }
+                }
+            }
+            offset += offset & 1;
+        }
+
+        for file in 0..files {
+            for item in &mut items {
+                item[file].sparse_index = offset;
+                offset += 6 * item[file].sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for item in &mut items {
+                item[file].block_lengths = offset;
+                offset += 2 * item[file].block_lengths_size;
+            }
+        }
+        for file in 0..files {
+            for item in &mut items {
+                offset = (offset + 0x3F) & !0x3F;
+                item[file].data = offset;
+                offset += item[file].num_blocks * item[file].block_size;
+                if item[file].flags & FLAG_SINGLE_VALUE == 0 && offset > bytes.len() {
+                    return None;
+                }
+            }
+        }
+        Some(TableData { bytes, items, map })
+    }
+
+    /// Splits the pieces into groups indexed together, and works out what
+    /// each group's index is multiplied by, in the order the file gives.
+    fn set_groups(&self, item: &mut PairsData, order: [u8; 2], file: usize) {
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        item.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || item.pieces[i] == item.pieces[i - 1] {
+                item.group_len[n] += 1;
+            } else {
+                n += 1;
+                item.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        item.group_len[n] = 0;
+
+        let pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if pawns_on_both_sides { 2 } else { 1 };
+        let mut free = 64 - item.group_len[0] - if pawns_on_both_sides { item.group_len[1] } else { 0 };
+        let mut idx = 1;
+        let mut k = 0;
+        while next < n || k == usize::from(order[0]) || k == usize::from(order[1]) {
+            if k == usize::from(order[0]) {
+                item.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    INDICES.lead_pawns_size[item.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == usize::from(order[1]) {
+                item.group_idx[1] = idx;
+                idx *= INDICES.binomial[item.group_len[1]][48 - item.group_len[0]];
+            } else {
+                item.group_idx[next] = idx;
+                idx *= INDICES.binomial[item.group_len[next]][free];
+                free -= item.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        item.group_idx[n] = idx;
+    }
+
+    /// The value stored for `board`, as a result for a WDL table and as a
+    /// distance to zeroing for a DTZ table, which may only have the other
+    /// side to move: `Err(())` then.
+    #[allow(clippy::too_many_lines)]
+    fn probe(&self, board: &Board, wdl: Wdl) -> Option<Result<i32, ()>> {
+        let data = self.data()?;
+        let state = board.state();
+        let turn = board.turn_as_idx();
+
+        // the tables have the side named first as white, and symmetric
+        // tables only white to move.
+        let flip = (self.key == self.key2 && turn == BLACK) || board.material_key() != self.key;
+        let flip_colour = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = usize::from(flip) ^ turn;
+
+        let mut squares = [0; TB_PIECES];
+        let mut pieces = [0; TB_PIECES];
+        let mut size = 0;
+        let (lead_pawns, file) = if self.has_pawns {
+            let colour = usize::from((data.get(0, 0).pieces[0] ^ flip_colour) >> 3);
+            let lead_pawns = state.pawns & state.occupied_co[colour];
+            for square in lead_pawns.iter_bits() {
+                squares[size] = square ^ flip_squares;
+                size += 1;
+            }
+            let leading = (0..size).fold(0, |best, i| {
+                if INDICES.map_pawns[squares[i]] > INDICES.map_pawns[squares[best]] { i } else { best }
+            });
+            squares.swap(0, leading);
+            (lead_pawns, (squares[0] & 7).min(7 - (squares[0] & 7)))
+        } else {
+            (0, 0)
+        };
+        let lead_pawns_count = size;
+
+        if self.kind == Kind::Dtz
+            && usize::from(data.get(stm, file).flags & FLAG_STM) != stm
+            && (self.key != self.key2 || self.has_pawns)
+        {
+            return Some(Err(()));
+        }
+
+        for square in (state.occupied() ^ lead_pawns).iter_bits() {
+            if size == TB_PIECES {
+                return None;
+            }
+            let colour = u8::from(state.occupied_co[BLACK].test(square));
+            squares[size] = square ^ flip_squares;
+            pieces[size] = (state.piece_type_at(square) as u8 | colour << 3) ^ flip_colour;
+            size += 1;
+        }
+        if size != self.piece_count {
+            return None;
+        }
+
+        // the pieces go in the order of the table.
+        let item = data.get(stm, file);
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| item.pieces[i] == pieces[j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // the leading piece goes on the a-d files, and for tables without
+        // pawns in the a1-d1-d4 triangle.
+        if squares[0] & 7 > 3 {
+            for square in &mut squares[..size] {
+                *square ^= 7;
+            }
+        }
+        let mut idx;
+        if self.has_pawns {
+            idx = INDICES.lead_pawn_idx[lead_pawns_count][squares[0]];
+            squares[1..lead_pawns_count].sort_by_key(|&square| INDICES.map_pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
+                idx += INDICES.binomial[i][INDICES.map_pawns[square]];
+            }
+        } else {
+            if squares[0] >> 3 > 3 {
+                for square in &mut squares[..size] {
+                    *square ^= 0x38;
+                }
+            }
+            if let Some(i) = squares[..item.group_len[0]].iter().position(|&square| off_a1h8(square) != 0) {
+                if off_a1h8(squares[i]) > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = ((*square >> 3) | (*square << 3)) & 0x3F;
+                    }
+                }
+            }
+
+            let [s0, s1, s2] = [squares[0], squares[1], squares[2]];
+            if self.has_unique_pieces {
+                let adjust1 = usize::from(s1 > s0);
+                let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+                let (rank0, rank1, rank2) = (s0 >> 3, s1 >> 3, s2 >> 3);
+                idx = if off_a1h8(s0) != 0 {
+                    ((INDICES.map_a1d1d4[s0] * 63 + s1 - adjust1) * 62 + s2 - adjust2) as u64
+                } else if off_a1h8(s1) != 0 {
+                    ((6 * 63 + rank0 as u64 * 28 + INDICES.map_b1h1h7[s1]) * 62) + (s2 - adjust2) as u64
+                } else if off_a1h8(s2) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62 + rank0 * 7 * 28 + (rank1 - adjust1) * 28) as u64 + INDICES.map_b1h1h7[s2]
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank0 * 7 * 6 + (rank1 - adjust1) * 6 + rank2 - adjust2) as u64
+                };
+            } else {
+                idx = INDICES.map_kk[INDICES.map_a1d1d4[s0]][s1];
+            }
+        }
+
+        // the other groups are each indexed as a combination of the squares
+        // the groups before them leave free.
+        idx *= item.group_idx[0];
+        let mut group_start = item.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while item.group_len[next] != 0 {
+            let len = item.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| square > s).count();
+                n += INDICES.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+            remaining_pawns = false;
+            idx += n * item.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        let value = item.decompress(&data.bytes, idx)?;
+        Some(Ok(match self.kind {
+            Kind::Wdl => value as i32 - 2,
+            Kind::Dtz => data.map_dtz(file, wdl, value)?,
+        }))
+    }
+}
+
+/// A set of Syzygy tables, from the directories given to `Tablebase::new`.
+pub struct Tablebase {
+    /// Tables by the material key of both colourings.
+    wdl: HashMap<u64, Arc<Table>>,
+    dtz: HashMap<u64, Arc<Table>>,
+    max_pieces: usize,
+}
+
+impl Debug for Tablebase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tablebase")
+            .field("wdl", &self.wdl.len())
+            .field("dtz", &self.dtz.len())
+            .field("max_pieces", &self.max_pieces)
+            .finish()
+    }
+}
+
+impl Tablebase {
+    /// Finds the tables in `paths`, a list of directories separated as in
+    /// `PATH`. The files are only read once they are probed, and then
+    /// whole.
+    pub fn new(paths: &str) -> Result<Self, String> {
+        let mut tablebase = Self { wdl: HashMap::new(), dtz: HashMap::new(), max_pieces: 0 };
+        for dir in env::split_paths(paths).filter(|dir| !dir.as_os_str().is_empty()) {
+            let entries = fs::read_dir(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+            for entry in entries {
+                let path = entry.map_err(|e| format!("{}: {e}", dir.display()))?.path();
+                let (Some(name), Some(extension)) = (path.file_stem().and_then(|s| s.to_str()), path.extension()) else {
+                    continue;
+                };
+                let kind = match extension.to_str() {
+                    Some("rtbw") => Kind::Wdl,
+                    Some("rtbz") => Kind::Dtz,
+                    _ => continue,
+                };
+                let Some(material) = parse_material(name) else {
+                    continue;
+                };
+                let table = Arc::new(Table::new(kind, path.clone(), &material));
+                let tables = match kind {
+                    Kind::Wdl => {
+                        tablebase.max_pieces = tablebase.max_pieces.max(table.piece_count);
+                        &mut tablebase.wdl
+                    }
+                    Kind::Dtz => &mut tablebase.dtz,
+                };
+                tables.insert(table.key2, Arc::clone(&table));
+                tables.insert(table.key, table);
+            }
+        }
+        Ok(tablebase)
+    }
+
+    /// The most pieces of any WDL table found.
+    pub const fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the position is small enough to be in the tables. Positions
+    /// with castling rights never are.
+    pub fn covers(&self, board: &Board) -> bool {
+        let state = board.state();
+        state.castling_rights == 0 && state.occupied().popcount() <= self.max_pieces
+    }
+
+    /// The result of `board` with best play, assuming the fifty-move counter
+    /// has just been reset, or `None` if a table needed is missing.
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.probe_wdl_in_place(&mut board.clone())
+    }
+
+    /// As `probe_wdl`, making and unmaking captures on `board` rather than
+    /// a copy, for the search.
+    pub fn probe_wdl_in_place(&self, board: &mut Board) -> Option<Wdl> {
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    /// The plies to the next capture or pawn move with best play, negative
+    /// when losing and over 100 for cursed wins and blessed losses. Like
+    /// the tables, it may be a ply or so more than the shortest distance.
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.dtz(&mut board.clone())
+    }
+
+    /// The root moves that keep the best result reachable given the
+    /// fifty-move counter, of which winning moves are those that zero it
+    /// soonest. `None` if the position is not in the tables.
+    pub fn root_moves(&self, board: &Board) -> Option<Vec<Move>> {
+        if !self.covers(board) {
+            return None;
+        }
+        let mut board = board.clone();
+        let halfmove_clock = i32::from(board.halfmove_clock());
+        let mut ranked = Vec::new();
+        for &m in &board.legal_moves() {
+            board.make(m);
+            let dtz = if board.halfmove_clock() == 0 {
+                self.probe_wdl_in_place(&mut board).map(|wdl| dtz_before_zeroing(-wdl))
+            } else if board.halfmove_clock() >= 100 || board.is_repetition() {
+                Some(0)
+            } else {
+                self.dtz(&mut board).map(|dtz| -dtz + (-dtz).signum())
+            };
+            let mate = board.is_check() && board.legal_moves().len() == 0;
+            board.unmake();
+            let dtz = if mate && dtz == Some(2) { 1 } else { dtz? };
+
+            let rank = if dtz > 0 && dtz + halfmove_clock <= 99 {
+                2 * MAX_DTZ - dtz
+            } else if dtz > 0 {
+                MAX_DTZ - (dtz + halfmove_clock)
+            } else if dtz < 0 && -dtz * 2 + halfmove_clock < 100 {
+                -2 * MAX_DTZ
+            } else if dtz < 0 {
+                -MAX_DTZ + (-dtz + halfmove_clock)
+            } else {
+                0
+            };
+            ranked.push((m, rank));
+        }
+        let best = ranked.iter().map(|&(_, rank)| rank).max()?;
+        Some(ranked.into_iter().filter(|&(_, rank)| rank == best).map(|(m, _)| m).collect())
+    }
+
+    fn probe_table(&self, kind: Kind, board: &Board, wdl: Wdl) -> Option<Result<i32, ()>> {
+        if board.state().occupied().popcount() == 2 {
+            return Some(Ok(0));
+        }
+        let tables = if kind == Kind::Wdl { &self.wdl } else { &self.dtz };
+        tables.get(&board.material_key())?.probe(board, wdl)
+    }
+
+    /// The result of `board`, searching captures (and with `zeroing`, pawn
+    /// moves) as the tables may leave them out. Also whether the best move
+    /// is one of those moves.
+    fn search(&self, board: &mut Board, zeroing: bool) -> Option<(Wdl, bool)> {
+        let moves = board.legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for &m in &moves {
+            if !board.is_capture(m) && (!zeroing || board.state().piece_type_at(m.from_sq()) != PieceType::Pawn) {
+                continue;
+            }
+            searched += 1;
+            board.make(m);
+            let wdl = self.search(board, false);
+            board.unmake();
+            let wdl = -wdl?.0;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+
+        let all_searched = searched > 0 && searched == moves.len();
+        let wdl = if all_searched {
+            best
+        } else {
+            Wdl::from_value(self.probe_table(Kind::Wdl, board, Wdl::Draw)?.ok()?)?
+        };
+        if best >= wdl {
+            Some((best, best > Wdl::Draw || all_searched))
+        } else {
+            Some((wdl, false))
+        }
+    }
+
+    fn dtz(&self, board: &mut Board) -> Option<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        if let Ok(dtz) = self.probe_table(Kind::Dtz, board, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // the table only has the other side to move, so look a move ahead.
+        let mut best = i32::MAX;
+        for &m in &board.legal_moves() {
+            let zeroing = board.is_capture(m) || board.state().piece_type_at(m.from_sq()) == PieceType::Pawn;
+            board.make(m);
+            let dtz = if zeroing {
+                self.search(board, false).map(|(wdl, _)| -dtz_before_zeroing(wdl))
+            } else {
+                self.dtz(board).map(Neg::neg)
+            };
+            let mate = board.is_check() && board.legal_moves().len() == 0;
+            board.unmake();
+            let mut dtz = dtz?;
+            if dtz == 1 && mate {
+                best = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < best && dtz.signum() == wdl.signum() {
+                best = dtz;
+            }
+        }
+        Some(if best == i32::MAX { -1 } else { best })
+    }
+}
+
+#[cfg(test)]
+mod writer;
+
+#[cfg(test)]
+mod tablebase_tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use crate::board::Board;
+    use crate::params::SearchParams;
+    use crate::search::{SearchLimits, Searcher, SharedState, MATE_BOUND};
+    use crate::tablebase::{parse_material, Kind, PairsData, Table, Tablebase, Wdl, INDICES, WDL_MAGIC};
+    use crate::transposition::TranspositionTable;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("istus-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn indices() {
+        let mut codes = INDICES.map_kk.iter().flatten().copied().collect::<Vec<_>>();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), 462);
+        assert_eq!(codes.last(), Some(&461));
+
+        let mut triangle = (0..28).filter(|&s| s & 7 <= 3 && s >> 3 <= s & 7).map(|s| INDICES.map_a1d1d4[s]).collect::<Vec<_>>();
+        triangle.sort_unstable();
+        assert_eq!(triangle, (0..10).collect::<Vec<_>>());
+
+        assert_eq!(INDICES.lead_pawns_size[1], [6; 4]);
+        assert_eq!(INDICES.binomial[3][10], 120);
+        assert_eq!(INDICES.map_pawns[8], 47);
+        assert_eq!(INDICES.map_pawns[15], 46);
+    }
+
+    #[test]
+    fn material() {
+        let [white, black] = parse_material("KRPvKN").unwrap();
+        assert_eq!((white.len(), black.len()), (3, 2));
+        assert!(parse_material("KRvR").is_none());
+        assert!(parse_material("KXvK").is_none());
+
+        let table = Table::new(Kind::Wdl, PathBuf::new(), &parse_material("KRPvKN").unwrap());
+        let board = Board::from_fen("8/8/3n4/8/2k5/8/1P6/KR6 w - - 0 1").unwrap();
+        assert_eq!(table.key, board.material_key());
+        let board = Board::from_fen("kr6/1p6/8/2K5/8/3N4/8/8 w - - 0 1").unwrap();
+        assert_eq!(table.key2, board.material_key());
+        assert!(table.has_pawns && table.has_unique_pieces);
+        assert_eq!(table.pawn_count, [1, 0]);
+    }
+
+    #[test]
+    fn single_value_tables() {
+        assert!(Tablebase::new("/nonexistent/tables").is_err());
+
+        // KRvK with white to move won and black to move lost everywhere:
+        // the header, piece order, and a single value for each side.
+        let dir = temp_dir("syzygy");
+        let mut bytes = WDL_MAGIC.to_vec();
+        bytes.extend([0x01, 0x00, 0x66, 0x44, 0xEE, 0x00, 0x80, 4, 0x80, 0]);
+        fs::write(dir.join("KRvK.rtbw"), bytes).unwrap();
+        fs::write(dir.join("README"), "not a table").unwrap();
+        let tablebase = Tablebase::new(dir.to_str().unwrap()).unwrap();
+        assert_eq!(tablebase.max_pieces(), 3);
+
+        let probe = |fen| tablebase.probe_wdl(&Board::from_fen(fen).unwrap());
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KR6 w - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KR6 b - - 0 1"), Some(Wdl::Loss));
+        assert_eq!(probe("kr6/8/8/8/4K3/8/8/8 w - - 0 1"), Some(Wdl::Loss));
+        assert_eq!(probe("kr6/8/8/8/4K3/8/8/8 b - - 0 1"), Some(Wdl::Win));
+        // taking the rook draws.
+        assert_eq!(probe("8/8/8/8/8/8/2k5/1R5K b - - 0 1"), Some(Wdl::Draw));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/K7 w - - 0 1"), Some(Wdl::Draw));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KQ6 w - - 0 1"), None);
+        assert_eq!(probe("8/8/8/4k3/8/8/8/RK5R w - - 0 1"), None);
+
+        // the search scores taking the knight, into a won table position,
+        // just below mate.
+        let shared = SharedState::new().with_tablebase(Some(Arc::new(tablebase)));
+        let tt = TranspositionTable::new(1);
+        let mut board = Board::from_fen("8/8/8/1n2k3/8/8/8/KR6 w - - 0 1").unwrap();
+        let result = Searcher::new(SearchLimits::depth(3), SearchParams::new(), &tt, &shared).search(&mut board, |_| {});
+        assert!(result.score > MATE_BOUND - 10 && result.score < MATE_BOUND, "{}", result.score);
+        assert_eq!(result.best_move, board.parse_uci("b1b5").unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn decompression() {
+        // two one-bit codes: 0 for a single value 3, and 1 for a pair
+        // standing for 7 then 3. The block holds 1, 0, 1: 7 3 3 7 3.
+        let mut bytes = vec![0x00, 5, 3, 0, 1, 0, 0, 0, 1, 1, 1, 0, 3, 0];
+        bytes.extend([7, 0xF0, 0xFF, 3, 0xF0, 0xFF, 0, 0x10, 0, 0]);
+        bytes.extend([0, 0, 0, 0, 4, 0, 4, 0]);
+        bytes.push(0xA0);
+        bytes.resize(64, 0);
+
+        let mut item = PairsData::default();
+        item.group_len[0] = 1;
+        item.group_idx[1] = 5;
+        assert_eq!(item.set_sizes(&bytes, 0), Some(24));
+        assert_eq!(item.symlen, [0, 0, 1]);
+        item.sparse_index = 24;
+        item.block_lengths = 30;
+        item.data = 32;
+        let values = (0..5).map(|idx| item.decompress(&bytes, idx)).collect::<Vec<_>>();
+        assert_eq!(values, [Some(7), Some(3), Some(3), Some(7), Some(3)]);
+    }
+
+    /// Probes the tables in `syzygy/`: `KRvK` and `KPvK`, with the tables
+    /// their captures and promotions lead to.
+    /// `writer` makes them from a brute-force solve.
+    #[test]
+    fn real_tables() {
+        let tablebase = Tablebase::new(concat!(env!("CARGO_MANIFEST_DIR"), "/syzygy")).unwrap();
+        assert_eq!(tablebase.max_pieces(), 3);
+        let probe = |fen| {
+            let board = Board::from_fen(fen).unwrap();
+            (tablebase.probe_wdl(&board), tablebase.probe_dtz(&board))
+        };
+        let root_moves = |fen| {
+            let board = Board::from_fen(fen).unwrap();
+            let mut moves = tablebase.root_moves(&board).unwrap().iter().map(ToString::to_string).collect::<Vec<_>>();
+            moves.sort();
+            moves
+        };
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KQ6 w - - 0 1").0, Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KQ6 b - - 0 1").0, Some(Wdl::Loss));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KN6 w - - 0 1").0, Some(Wdl::Draw));
+
+        // the DTZ table only has the side with the rook to move, so the
+        // other side looks a move ahead.
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KR6 w - - 0 1"), (Some(Wdl::Win), Some(29)));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/KR6 b - - 0 1"), (Some(Wdl::Loss), Some(-30)));
+        assert_eq!(probe("kr6/8/8/8/4K3/8/8/8 b - - 0 1"), (Some(Wdl::Win), Some(29)));
+        assert_eq!(root_moves("8/8/8/4k3/8/8/8/KR6 w - - 0 1"), ["a1b2", "b1e1"]);
+        assert_eq!(probe("k7/8/1K6/8/8/8/8/7R w - - 0 1"), (Some(Wdl::Win), Some(1)));
+        assert_eq!(probe("k6R/8/1K6/8/8/8/8/8 b - - 0 1"), (Some(Wdl::Loss), Some(-1)));
+        assert_eq!(root_moves("k7/8/1K6/8/8/8/8/7R w - - 0 1"), ["h1h8"]);
+
+        // pawns on each file, for either side.
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), (Some(Wdl::Win), Some(3)));
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), (Some(Wdl::Loss), Some(-4)));
+        assert_eq!(probe("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), (Some(Wdl::Win), Some(9)));
+        assert_eq!(probe("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        assert_eq!(probe("4k3/4p3/8/8/8/8/8/4K3 b - - 0 1"), (Some(Wdl::Win), Some(9)));
+        assert_eq!(probe("8/8/8/8/3k4/8/3P4/3K4 b - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        assert_eq!(probe("k7/8/K7/P7/8/8/8/8 w - - 0 1"), (Some(Wdl::Draw), Some(0)));
+        assert_eq!(probe("8/8/8/8/8/1k6/7P/7K w - - 0 1"), (Some(Wdl::Win), Some(1)));
+        // only the double step keeps the king out of the pawn's square.
+        assert_eq!(root_moves("8/8/8/8/8/1k6/7P/7K w - - 0 1"), ["h2h4"]);
+        assert_eq!(root_moves("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), ["e6d6", "e6f6"]);
+        assert_eq!(root_moves("8/8/8/8/8/k7/4p3/K7 b - - 0 1"), ["e2e1q", "e2e1r"]);
+    }
+}
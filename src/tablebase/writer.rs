@@ -0,0 +1,758 @@
+#![allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+
+//! Writes the test tables in `syzygy/`: a king and one white piece against
+//! a lone king, solved by brute force and compressed the way the prober
+//! reads them. The `fixtures` test regenerates them and checks they are
+//! unchanged; `official_tables` checks the solve against the official
+//! tables, if `SYZYGY_PATH` has them.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bitmethods::Bithackable;
+use crate::board::Board;
+use crate::colour::{Colour, BLACK};
+use crate::piece::PieceType;
+use crate::tablebase::{
+    off_a1h8, parse_material, Kind, PairsData, Table, TableData, DTZ_MAGIC, FLAG_LOSS_PLIES, FLAG_MAPPED,
+    FLAG_WIN_PLIES, HEADER_HAS_PAWNS, HEADER_SPLIT, INDICES, TB_PIECES, WDL_MAGIC,
+};
+
+/// The extra white piece of each table, `None` for the bare kings.
+const X_TYPES: [PieceType; 6] =
+    [PieceType::None, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::Pawn];
+
+/// The letter of the extra piece in table names.
+fn letter(x: PieceType) -> char {
+    match x {
+        PieceType::Pawn => 'P',
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        _ => unreachable!(),
+    }
+}
+
+/// The position at `idx`, indexed as `(white king, piece, black king,
+/// side to move)`, if it is legal.
+fn board_of(x: PieceType, idx: usize) -> Option<Board> {
+    let stm = idx & 1;
+    let bk = (idx >> 1) & 63;
+    let xs = (idx >> 7) & 63;
+    let wk = idx >> 13;
+    if x == PieceType::None && xs != 0 {
+        return None;
+    }
+    let mut cells = ['.'; 64];
+    cells[wk] = 'K';
+    if cells[bk] != '.' {
+        return None;
+    }
+    cells[bk] = 'k';
+    if x != PieceType::None {
+        if cells[xs] != '.' {
+            return None;
+        }
+        if x == PieceType::Pawn && !(8..56).contains(&xs) {
+            return None;
+        }
+        cells[xs] = letter(x);
+    }
+    if (wk as i32 / 8 - bk as i32 / 8).abs() <= 1 && (wk as i32 % 8 - bk as i32 % 8).abs() <= 1 {
+        return None;
+    }
+    let mut fen = String::new();
+    for rank in (0..8).rev() {
+        let mut empty = 0;
+        for file in 0..8 {
+            let c = cells[rank * 8 + file];
+            if c == '.' {
+                empty += 1;
+            } else {
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(c);
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if rank > 0 {
+            fen.push('/');
+        }
+    }
+    fen.push_str(if stm == 0 { " w - - 0 1" } else { " b - - 0 1" });
+    let board = Board::from_fen(&fen).ok()?;
+    // the side not to move must not be in check.
+    let them = board.state().occupied_co[stm ^ 1] & board.state().kings;
+    let us = if stm == 0 { Colour::White } else { Colour::Black };
+    if board.attackers(us, them.lsb()) != 0 {
+        return None;
+    }
+    Some(board)
+}
+
+/// The extra piece and index of a position `board_of` can produce.
+fn index_of(board: &Board) -> (PieceType, usize) {
+    let state = board.state();
+    let mut x = PieceType::None;
+    let (mut wk, mut xs, mut bk) = (0, 0, 0);
+    for square in state.occupied().iter_bits() {
+        let piece_type = state.piece_type_at(square);
+        let black = state.occupied_co[BLACK].test(square);
+        match (piece_type, black) {
+            (PieceType::King, false) => wk = square,
+            (PieceType::King, true) => bk = square,
+            (p, false) => {
+                x = p;
+                xs = square;
+            }
+            _ => panic!("black piece"),
+        }
+    }
+    (x, ((wk * 64 + xs) * 64 + bk) * 2 + board.turn_as_idx())
+}
+
+/// A solved table, indexed as in `board_of`. `wdl` is -2..=2 from the side
+/// to move, and `dtz` the exact distance to zeroing of wins and losses.
+struct Solved {
+    legal: Vec<bool>,
+    wdl: Vec<i8>,
+    dtz: Vec<i32>,
+}
+
+/// A move, leading to position `idx` of the table for `x`.
+#[derive(Clone, Copy)]
+struct Edge {
+    x: PieceType,
+    idx: usize,
+    zeroing: bool,
+    mated: bool,
+}
+
+/// Solves the table for `x` by retrograde iteration, with the tables its
+/// captures and promotions lead to already in `solved`.
+#[allow(clippy::too_many_lines)]
+fn solve(x: PieceType, solved: &HashMap<u8, Solved>) -> Solved {
+    const N: usize = 64 * 64 * 64 * 2;
+    let mut legal = vec![false; N];
+    let mut edges: Vec<Vec<Edge>> = vec![Vec::new(); N];
+    let mut wdl = vec![0i8; N];
+    let mut known = vec![false; N];
+    let mut mated = vec![false; N];
+    for idx in 0..N {
+        let Some(mut board) = board_of(x, idx) else { continue };
+        legal[idx] = true;
+        let moves = board.legal_moves();
+        if moves.len() == 0 {
+            known[idx] = true;
+            wdl[idx] = if board.is_check() { -2 } else { 0 };
+            mated[idx] = board.is_check();
+            continue;
+        }
+        for &m in &moves {
+            let zeroing = board.is_capture(m) || board.state().piece_type_at(m.from_sq()) == PieceType::Pawn;
+            board.make(m);
+            let (cx, cidx) = index_of(&board);
+            let is_mated = board.is_check() && board.legal_moves().len() == 0;
+            board.unmake();
+            edges[idx].push(Edge { x: cx, idx: cidx, zeroing, mated: is_mated });
+        }
+    }
+    if x == PieceType::None {
+        return Solved { legal, wdl, dtz: vec![0; N] };
+    }
+    let child_wdl = |wdl: &[i8], known: &[bool], e: &Edge| -> Option<i8> {
+        if e.x == x {
+            known[e.idx].then(|| wdl[e.idx])
+        } else {
+            Some(solved[&(e.x as u8)].wdl[e.idx])
+        }
+    };
+    loop {
+        let mut changed = false;
+        for idx in 0..N {
+            if !legal[idx] || known[idx] {
+                continue;
+            }
+            let mut all_win = true;
+            let mut win = false;
+            for e in &edges[idx] {
+                match child_wdl(&wdl, &known, e) {
+                    Some(-2) => win = true,
+                    Some(2) => {}
+                    _ => all_win = false,
+                }
+            }
+            if win {
+                wdl[idx] = 2;
+                known[idx] = true;
+                changed = true;
+            } else if all_win {
+                wdl[idx] = -2;
+                known[idx] = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    // WDL in -2..2 as stored: rest drawn.
+    let mut dtz = vec![0i32; N];
+    for idx in 0..N {
+        if legal[idx] && mated[idx] {
+            dtz[idx] = -1;
+        }
+    }
+    let child_dtz = |dtz: &[i32], e: &Edge| -> i32 {
+        if e.x == x { dtz[e.idx] } else { 0 }
+    };
+    for k in 1..1000 {
+        let mut left = 0;
+        // wins at level k
+        let mut assign = Vec::new();
+        for idx in 0..N {
+            if !legal[idx] || wdl[idx] != 2 || dtz[idx] != 0 {
+                continue;
+            }
+            left += 1;
+            let mut best = i32::MAX;
+            for e in &edges[idx] {
+                let w = if e.x == x { wdl[e.idx] } else { solved[&(e.x as u8)].wdl[e.idx] };
+                if w != -2 {
+                    continue;
+                }
+                let v = if e.zeroing || e.mated {
+                    1
+                } else {
+                    let d = child_dtz(&dtz, e);
+                    if d == 0 {
+                        continue;
+                    }
+                    1 - d
+                };
+                best = best.min(v);
+            }
+            if best == k {
+                assign.push(idx);
+            }
+        }
+        for idx in assign {
+            dtz[idx] = k;
+        }
+        let mut assign = Vec::new();
+        for idx in 0..N {
+            if !legal[idx] || wdl[idx] != -2 || dtz[idx] != 0 {
+                continue;
+            }
+            left += 1;
+            let mut worst = 0;
+            let mut all = true;
+            for e in &edges[idx] {
+                let v = if e.zeroing {
+                    1
+                } else {
+                    let d = child_dtz(&dtz, e);
+                    if d == 0 {
+                        all = false;
+                        break;
+                    }
+                    d + 1
+                };
+                worst = worst.max(v);
+            }
+            if all && worst == k {
+                assign.push(idx);
+            }
+        }
+        for idx in assign {
+            dtz[idx] = -k;
+        }
+        if left == 0 {
+            break;
+        }
+    }
+    Solved { legal, wdl, dtz }
+}
+
+/// The side, file and index of `board` in `data`, computed as
+/// `Table::probe` does.
+fn index(table: &Table, data: &TableData, board: &Board) -> (usize, usize, u64) {
+    let state = board.state();
+    let turn = board.turn_as_idx();
+    let flip = (table.key == table.key2 && turn == BLACK) || board.material_key() != table.key;
+    assert!(!flip);
+    let stm = turn;
+    let mut squares = [0; TB_PIECES];
+    let mut pieces = [0u8; TB_PIECES];
+    let mut size = 0;
+    let (lead_pawns, file) = if table.has_pawns {
+        let lead_pawns = state.pawns & state.occupied_co[0];
+        for square in lead_pawns.iter_bits() {
+            squares[size] = square;
+            size += 1;
+        }
+        (lead_pawns, (squares[0] & 7).min(7 - (squares[0] & 7)))
+    } else {
+        (0, 0)
+    };
+    let lead_pawns_count = size;
+    for square in (state.occupied() ^ lead_pawns).iter_bits() {
+        let colour = u8::from(state.occupied_co[BLACK].test(square));
+        squares[size] = square;
+        pieces[size] = state.piece_type_at(square) as u8 | colour << 3;
+        size += 1;
+    }
+    let item = data.get(stm, file);
+    for i in lead_pawns_count..size.saturating_sub(1) {
+        if let Some(j) = (i + 1..size).find(|&j| item.pieces[i] == pieces[j]) {
+            pieces.swap(i, j);
+            squares.swap(i, j);
+        }
+    }
+    if squares[0] & 7 > 3 {
+        for square in &mut squares[..size] {
+            *square ^= 7;
+        }
+    }
+    let mut idx = if table.has_pawns {
+        INDICES.lead_pawn_idx[lead_pawns_count][squares[0]]
+    } else {
+        if squares[0] >> 3 > 3 {
+            for square in &mut squares[..size] {
+                *square ^= 0x38;
+            }
+        }
+        if let Some(i) = squares[..item.group_len[0]].iter().position(|&square| off_a1h8(square) != 0) {
+            if off_a1h8(squares[i]) > 0 {
+                for square in &mut squares[i..size] {
+                    *square = ((*square >> 3) | (*square << 3)) & 0x3F;
+                }
+            }
+        }
+        let [s0, s1, s2] = [squares[0], squares[1], squares[2]];
+        let adjust1 = usize::from(s1 > s0);
+        let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+        let (rank0, rank1, rank2) = (s0 >> 3, s1 >> 3, s2 >> 3);
+        if off_a1h8(s0) != 0 {
+            ((INDICES.map_a1d1d4[s0] * 63 + s1 - adjust1) * 62 + s2 - adjust2) as u64
+        } else if off_a1h8(s1) != 0 {
+            ((6 * 63 + rank0 as u64 * 28 + INDICES.map_b1h1h7[s1]) * 62) + (s2 - adjust2) as u64
+        } else if off_a1h8(s2) != 0 {
+            (6 * 63 * 62 + 4 * 28 * 62 + rank0 * 7 * 28 + (rank1 - adjust1) * 28) as u64 + INDICES.map_b1h1h7[s2]
+        } else {
+            (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank0 * 7 * 6 + (rank1 - adjust1) * 6 + rank2 - adjust2) as u64
+        }
+    };
+    idx *= item.group_idx[0];
+    let mut group_start = item.group_len[0];
+    let mut next = 1;
+    while item.group_len[next] != 0 {
+        let len = item.group_len[next];
+        squares[group_start..group_start + len].sort_unstable();
+        let mut n = 0;
+        for i in 0..len {
+            let square = squares[group_start + i];
+            let adjust = squares[..group_start].iter().filter(|&&s| square > s).count();
+            n += INDICES.binomial[i + 1][square - adjust];
+        }
+        idx += n * item.group_idx[next];
+        group_start += len;
+        next += 1;
+    }
+    (stm, file, idx)
+}
+
+/// Huffman code lengths for the frequencies.
+fn code_lengths(freqs: &[u64]) -> Vec<u32> {
+    let n = freqs.len();
+    let mut nodes: Vec<(u64, Option<(usize, usize)>)> = freqs.iter().map(|&f| (f, None)).collect();
+    let mut live: Vec<usize> = (0..n).collect();
+    while live.len() > 1 {
+        live.sort_by_key(|&i| std::cmp::Reverse(nodes[i].0));
+        let a = live.pop().unwrap();
+        let b = live.pop().unwrap();
+        nodes.push((nodes[a].0 + nodes[b].0, Some((a, b))));
+        live.push(nodes.len() - 1);
+    }
+    let mut lengths = vec![0; n];
+    let mut stack = vec![(live[0], 0)];
+    while let Some((node, depth)) = stack.pop() {
+        match nodes[node].1 {
+            Some((a, b)) => {
+                stack.push((a, depth + 1));
+                stack.push((b, depth + 1));
+            }
+            None => lengths[node] = depth,
+        }
+    }
+    lengths
+}
+
+/// Blocks of 32 bytes, with a sparse index entry every 1024 values.
+const BLOCK_LOG: u8 = 5;
+const SPAN_LOG: u8 = 10;
+
+/// An encoded item: header bytes, sparse index, block lengths, data.
+struct Encoded {
+    header: Vec<u8>,
+    sparse: Vec<u8>,
+    lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+/// Compresses `values` into one table item, with runs of equal values
+/// as Huffman-coded symbols.
+#[allow(clippy::too_many_lines, clippy::many_single_char_names)]
+fn encode(flags: u8, values: &[u8]) -> Encoded {
+    if values.iter().all(|&v| v == values[0]) {
+        return Encoded { header: vec![flags | 0x80, values[0]], sparse: vec![], lengths: vec![], data: vec![] };
+    }
+    // runs of 2^k equal values, k <= 8.
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < values.len() {
+        let v = values[i];
+        let mut j = i;
+        while j < values.len() && values[j] == v {
+            j += 1;
+        }
+        let mut len = j - i;
+        while len > 0 {
+            let k = (usize::BITS - 1 - len.leading_zeros()).min(8);
+            runs.push((v, k));
+            len -= 1 << k;
+        }
+        i = j;
+    }
+    let mut symbols: Vec<(u8, u32)> = Vec::new();
+    for &(v, k) in &runs {
+        for j in 0..=k {
+            if !symbols.contains(&(v, j)) {
+                symbols.push((v, j));
+            }
+        }
+    }
+    let mut freqs = vec![1u64; symbols.len()];
+    for run in &runs {
+        freqs[symbols.iter().position(|s| s == run).unwrap()] += 1;
+    }
+    let lengths = code_lengths(&freqs);
+    assert!(lengths.iter().all(|&l| (1..=32).contains(&l)));
+    let max = *lengths.iter().max().unwrap();
+    let min = *lengths.iter().min().unwrap();
+    // ids: longest codes first.
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|&s| std::cmp::Reverse(lengths[s]));
+    let mut id = vec![0; symbols.len()];
+    for (n, &s) in order.iter().enumerate() {
+        id[s] = n;
+    }
+    let levels = (max - min + 1) as usize;
+    let count = |i: usize| lengths.iter().filter(|&&l| l == min + i as u32).count() as u64;
+    let mut lowest = vec![0u64; levels];
+    let mut base = vec![0u64; levels];
+    for i in (0..levels - 1).rev() {
+        lowest[i] = lowest[i + 1] + count(i + 1);
+        base[i] = base[i + 1].midpoint(count(i + 1));
+    }
+    let code = |s: usize| {
+        let i = (lengths[s] - min) as usize;
+        (base[i] + id[s] as u64 - lowest[i], lengths[s])
+    };
+
+    let mut header = vec![flags, BLOCK_LOG, SPAN_LOG, 0];
+    let block_bits = 8usize << BLOCK_LOG;
+    let span = 1usize << SPAN_LOG;
+    let max_values = 65536 - span;
+    let mut data = Vec::new();
+    let mut block_values = Vec::new();
+    let mut bits: Vec<bool> = Vec::new();
+    let mut in_block = 0;
+    let flush = |bits: &mut Vec<bool>, data: &mut Vec<u8>| {
+        let mut block = vec![0u8; block_bits / 8];
+        for (n, &bit) in bits.iter().enumerate() {
+            if bit {
+                block[n / 8] |= 0x80 >> (n % 8);
+            }
+        }
+        data.extend(block);
+        bits.clear();
+    };
+    for run in &runs {
+        let s = symbols.iter().position(|x| x == run).unwrap();
+        let (c, l) = code(s);
+        let n = 1usize << run.1;
+        if bits.len() + l as usize > block_bits || in_block + n > max_values {
+            flush(&mut bits, &mut data);
+            block_values.push(in_block);
+            in_block = 0;
+        }
+        for b in (0..l).rev() {
+            bits.push(c >> b & 1 == 1);
+        }
+        in_block += n;
+    }
+    flush(&mut bits, &mut data);
+    block_values.push(in_block);
+
+    header.extend((block_values.len() as u32).to_le_bytes());
+    header.push(max as u8);
+    header.push(min as u8);
+    for &l in &lowest {
+        header.extend((l as u16).to_le_bytes());
+    }
+    header.extend((symbols.len() as u16).to_le_bytes());
+    let mut btree = vec![[0u8; 3]; symbols.len()];
+    for (s, &(v, k)) in symbols.iter().enumerate() {
+        let (left, right) = if k == 0 {
+            (usize::from(v), 0xFFF)
+        } else {
+            let child = id[symbols.iter().position(|&x| x == (v, k - 1)).unwrap()];
+            (child, child)
+        };
+        btree[id[s]] = [(left & 0xFF) as u8, ((left >> 8) & 0xF) as u8 | ((right & 0xF) << 4) as u8, (right >> 4) as u8];
+    }
+    for entry in btree {
+        header.extend(entry);
+    }
+    if symbols.len() & 1 == 1 {
+        header.push(0);
+    }
+
+    let mut lengths = Vec::new();
+    for &n in &block_values {
+        lengths.extend(((n - 1) as u16).to_le_bytes());
+    }
+    let mut starts = vec![0];
+    for &n in &block_values {
+        starts.push(starts.last().unwrap() + n);
+    }
+    let mut sparse = Vec::new();
+    for i in 0..values.len().div_ceil(span) {
+        let p = i * span + span / 2;
+        let block = if p < values.len() { starts.iter().rposition(|&s| s <= p).unwrap() } else { block_values.len() - 1 };
+        sparse.extend((block as u32).to_le_bytes());
+        sparse.extend(((p - starts[block]) as u16).to_le_bytes());
+    }
+    Encoded { header, sparse, lengths, data }
+}
+
+/// Fills the values no position reaches from their neighbours, for long runs.
+fn fill(values: &[Option<u8>]) -> Vec<u8> {
+    let first = values.iter().find_map(|&v| v).unwrap_or(0);
+    let mut last = first;
+    values
+        .iter()
+        .map(|&v| {
+            if let Some(v) = v {
+                last = v;
+            }
+            last
+        })
+        .collect()
+}
+
+/// Writes the WDL or DTZ table for `x` to `dir`.
+#[allow(clippy::too_many_lines)]
+fn write(x: PieceType, kind: Kind, solved: &Solved, dir: &Path) {
+    let name = format!("K{}vK", letter(x));
+    let ext = if kind == Kind::Wdl { "rtbw" } else { "rtbz" };
+    let table = Table::new(kind, PathBuf::default(), &parse_material(&name).unwrap());
+    let files = if table.has_pawns { 4 } else { 1 };
+    let sides = if kind == Kind::Wdl { 2 } else { 1 };
+    let piece_list: Vec<u8> = if table.has_pawns { vec![0x1, 0x6, 0xE] } else { vec![0x6, x as u8, 0xE] };
+    let mut items = vec![vec![PairsData::default(); files]; sides];
+    for file in 0..files {
+        for item in &mut items {
+            item[file].pieces[..3].copy_from_slice(&piece_list);
+            table.set_groups(&mut item[file], [0, 0xF], file);
+        }
+    }
+    let data = TableData { bytes: vec![], items, map: 0 };
+    let item_sizes: Vec<Vec<usize>> = (0..sides)
+        .map(|side| {
+            (0..files)
+                .map(|file| {
+                    let item = data.get(side, file);
+                    let groups = item.group_len.iter().position(|&l| l == 0).unwrap();
+                    item.group_idx[groups] as usize
+                })
+                .collect()
+        })
+        .collect();
+    let mut values: Vec<Vec<Vec<Option<u8>>>> = item_sizes.iter().map(|s| s.iter().map(|&n| vec![None; n]).collect()).collect();
+    // DTZ: distinct win distances per file.
+    let mut maps: Vec<Vec<i32>> = vec![Vec::new(); files];
+    let mut entries = Vec::new();
+    for idx in 0..solved.legal.len() {
+        if !solved.legal[idx] {
+            continue;
+        }
+        let board = board_of(x, idx).unwrap();
+        let (stm, file, i) = index(&table, &data, &board);
+        if kind == Kind::Dtz && (stm != 0 || solved.wdl[idx] != 2) {
+            continue;
+        }
+        entries.push((stm, file, i as usize, idx));
+        if kind == Kind::Dtz {
+            maps[file].push(solved.dtz[idx] - 1);
+        }
+    }
+    for map in &mut maps {
+        map.sort_unstable();
+        map.dedup();
+    }
+    for (stm, file, i, idx) in entries {
+        let v = match kind {
+            Kind::Wdl => (solved.wdl[idx] + 2) as u8,
+            Kind::Dtz => maps[file].binary_search(&(solved.dtz[idx] - 1)).unwrap() as u8,
+        };
+        let slot = &mut values[stm][file][i];
+        assert!(slot.is_none() || *slot == Some(v), "{name} {}", board_of(x, idx).unwrap().fen());
+        *slot = Some(v);
+    }
+
+    let mut bytes = if kind == Kind::Wdl { WDL_MAGIC.to_vec() } else { DTZ_MAGIC.to_vec() };
+    let mut header = 0;
+    if kind == Kind::Wdl {
+        header |= HEADER_SPLIT;
+    }
+    if table.has_pawns {
+        header |= HEADER_HAS_PAWNS;
+    }
+    bytes.push(header);
+    // per file: the piece orders, then the pieces as both sides see them.
+    let file_pieces: Vec<u8> = std::iter::once(0).chain(piece_list.iter().map(|&p| p | p << 4)).collect();
+    for _ in 0..files {
+        bytes.extend(&file_pieces);
+    }
+    if bytes.len() & 1 == 1 {
+        bytes.push(0);
+    }
+    let flags = if kind == Kind::Dtz { FLAG_MAPPED | FLAG_WIN_PLIES | FLAG_LOSS_PLIES } else { 0 };
+    let mut encoded = Vec::new();
+    for file in 0..files {
+        for side in &values {
+            let e = encode(flags, &fill(&side[file]));
+            bytes.extend(&e.header);
+            encoded.push(e);
+        }
+    }
+    if kind == Kind::Dtz {
+        for map in &maps {
+            // win, loss, cursed win, blessed loss.
+            bytes.push(map.len() as u8);
+            bytes.extend(map.iter().map(|&v| v as u8));
+            bytes.extend([0, 0, 0]);
+        }
+        if bytes.len() & 1 == 1 {
+            bytes.push(0);
+        }
+    }
+    for e in &encoded {
+        bytes.extend(&e.sparse);
+    }
+    for e in &encoded {
+        bytes.extend(&e.lengths);
+    }
+    for e in &encoded {
+        if e.data.is_empty() {
+            continue;
+        }
+        while bytes.len() % 64 != 0 {
+            bytes.push(0);
+        }
+        bytes.extend(&e.data);
+    }
+    bytes.extend([0; 16]);
+    fs::write(dir.join(format!("{name}.{ext}")), bytes).unwrap();
+}
+
+
+/// Solves every table and writes the ones in `syzygy/` to `dir`: WDL and
+/// DTZ for `KRvK` and `KPvK`, and WDL for the tables pawn promotions lead
+/// to.
+fn write_fixtures(dir: &Path) -> HashMap<u8, Solved> {
+    let mut solved = HashMap::new();
+    for x in X_TYPES {
+        let s = solve(x, &solved);
+        solved.insert(x as u8, s);
+    }
+    for x in [PieceType::Rook, PieceType::Pawn] {
+        write(x, Kind::Wdl, &solved[&(x as u8)], dir);
+        write(x, Kind::Dtz, &solved[&(x as u8)], dir);
+    }
+    for x in [PieceType::Queen, PieceType::Bishop, PieceType::Knight] {
+        write(x, Kind::Wdl, &solved[&(x as u8)], dir);
+    }
+    solved
+}
+
+#[cfg(test)]
+mod writer_tests {
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use crate::piece::PieceType;
+    use crate::tablebase::writer::{board_of, solve, write_fixtures, Solved, X_TYPES};
+    use crate::tablebase::{Tablebase, Wdl};
+
+    /// Checks the probes of every `KRvK` and `KPvK` position against the
+    /// solve. Official DTZ tables may round a distance up by one where the
+    /// fifty-move rule can't tell.
+    fn check(tablebase: &Tablebase, solved: &HashMap<u8, Solved>, dtz_slack: i32) {
+        for x in [PieceType::Rook, PieceType::Pawn] {
+            let s = &solved[&(x as u8)];
+            for idx in (0..s.legal.len()).filter(|&idx| s.legal[idx]) {
+                let board = board_of(x, idx).unwrap();
+                let fen = board.fen();
+                assert_eq!(tablebase.probe_wdl(&board).map(|wdl| wdl as i8), Some(s.wdl[idx]), "{fen}");
+                let dtz = tablebase.probe_dtz(&board).unwrap();
+                let expected = if s.wdl[idx] == Wdl::Draw as i8 { 0 } else { s.dtz[idx] };
+                assert!(dtz.signum() == expected.signum() && (dtz - expected).abs() <= dtz_slack, "{fen}: {dtz}");
+            }
+        }
+    }
+
+    /// The tables in `syzygy/` are what the writer makes, and probe as
+    /// solved. Run with `cargo test --release -- --ignored fixtures`.
+    #[test]
+    #[ignore = "solves every table, which takes minutes in a debug build"]
+    fn fixtures() {
+        let dir = env::temp_dir().join(format!("istus-writer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let solved = write_fixtures(&dir);
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("syzygy");
+        for entry in fs::read_dir(&dir).unwrap() {
+            let name = entry.unwrap().file_name();
+            assert_eq!(fs::read(dir.join(&name)).unwrap(), fs::read(fixtures.join(&name)).unwrap(), "{name:?}");
+        }
+        assert_eq!(fs::read_dir(&fixtures).unwrap().count(), 7);
+        check(&Tablebase::new(dir.to_str().unwrap()).unwrap(), &solved, 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Checks the solve against official tables, if `SYZYGY_PATH` has them.
+    #[test]
+    fn official_tables() {
+        let Ok(path) = env::var("SYZYGY_PATH") else { return };
+        let tablebase = Tablebase::new(&path).unwrap();
+        if tablebase.max_pieces() < 3 {
+            return;
+        }
+        let mut solved = HashMap::new();
+        for x in X_TYPES {
+            let s = solve(x, &solved);
+            solved.insert(x as u8, s);
+        }
+        check(&tablebase, &solved, 1);
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use crate::cmove::Move;
+
+pub const DEFAULT_MOVE_OVERHEAD_MS: u64 = 30;
+pub const MAX_MOVE_OVERHEAD_MS: u64 = 5000;
+
+/// Without `movestogo`, the time left is shared out as if this many moves
+/// remained.
+const SUDDEN_DEATH_MOVES: u32 = 30;
+const MAX_MOVES_TO_GO: u32 = 50;
+/// The hard limit is at most this many times the soft limit...
+const HARD_FACTOR: u32 = 4;
+/// ...and never more than this share of the clock, in percent.
+const MAX_CLOCK_SHARE: u32 = 80;
+
+/// The soft and hard time limits for a move, given the time left on the
+/// clock, the increment, the moves until the next time control (none for
+/// sudden death or increment controls) and the time lost to communication
+/// on every move.
+pub fn allocate(time: Duration, inc: Duration, movestogo: Option<u64>, overhead: Duration) -> (Duration, Duration) {
+    let available = time.saturating_sub(overhead).max(Duration::from_millis(1));
+    let moves = movestogo.map_or(SUDDEN_DEATH_MOVES, |moves| {
+        u32::try_from(moves).unwrap_or(MAX_MOVES_TO_GO).clamp(1, MAX_MOVES_TO_GO)
+    });
+
+    let soft = available / moves + inc * 3 / 4;
+    let hard = (soft * HARD_FACTOR).min(available * MAX_CLOCK_SHARE / 100);
+    (soft.min(hard), hard)
+}
+
+/// Decides after every iteration whether to start another, stretching the
+/// soft limit while the best move keeps changing or the score is falling,
+/// and cutting it short once the best move has settled.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeManager {
+    soft: Duration,
+    hard: Duration,
+    best_move: Move,
+    /// How many iterations in a row the best move has stayed the same.
+    stability: usize,
+    previous_score: Option<i32>,
+    /// How far the soft limit is stretched, in percent.
+    scale: u32,
+}
+
+impl TimeManager {
+    pub const fn new(soft: Duration, hard: Duration) -> Self {
+        Self { soft, hard, best_move: Move::null(), stability: 0, previous_score: None, scale: 100 }
+    }
+
+    /// Takes in the result of a completed iteration.
+    pub fn update(&mut self, best_move: Move, score: i32) {
+        if best_move == self.best_move {
+            self.stability += 1;
+        } else {
+            self.best_move = best_move;
+            self.stability = 0;
+        }
+
+        let stability_scale = match self.stability {
+            0 => 150,
+            1 => 120,
+            2 => 100,
+            3 => 85,
+            _ => 70,
+        };
+        let drop = self.previous_score.map_or(0, |previous| previous - score).clamp(0, 100);
+        self.scale = stability_scale * (100 + drop.unsigned_abs()) / 100;
+        self.previous_score = Some(score);
+    }
+
+    /// The soft limit as stretched or cut by the last update.
+    pub fn target(&self) -> Duration {
+        (self.soft * self.scale / 100).min(self.hard)
+    }
+
+    pub fn should_stop(&self, elapsed: Duration) -> bool {
+        elapsed >= self.target()
+    }
+}
+
+#[cfg(test)]
+mod timeman_tests {
+    use std::time::Duration;
+
+    use crate::cmove::Move;
+    use crate::timeman::{allocate, TimeManager};
+
+    const fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn allocation() {
+        // sudden death.
+        assert_eq!(allocate(ms(60_000), ms(0), None, ms(0)), (ms(2000), ms(8000)));
+        // increment, with overhead.
+        assert_eq!(allocate(ms(30_030), ms(1000), None, ms(30)), (ms(1750), ms(7000)));
+        // moves to go: the last move before the control may use most of
+        // the clock, but never all of it.
+        assert_eq!(allocate(ms(10_000), ms(0), Some(10), ms(0)), (ms(1000), ms(4000)));
+        assert_eq!(allocate(ms(10_000), ms(0), Some(1), ms(0)), (ms(8000), ms(8000)));
+        // almost out of time.
+        let (soft, hard) = allocate(ms(20), ms(2000), None, ms(30));
+        assert!(soft <= hard && hard <= ms(1));
+    }
+
+    #[test]
+    fn stability() {
+        let e4 = Move::from_uci("e2e4").unwrap();
+        let d4 = Move::from_uci("d2d4").unwrap();
+        let mut tm = TimeManager::new(ms(1000), ms(4000));
+
+        tm.update(e4, 20);
+        assert_eq!(tm.target(), ms(1500));
+        for _ in 0..4 {
+            tm.update(e4, 20);
+        }
+        assert_eq!(tm.target(), ms(700));
+        assert!(tm.should_stop(ms(700)));
+        assert!(!tm.should_stop(ms(699)));
+
+        // a new best move, and a falling score, buy more time.
+        tm.update(d4, 20);
+        assert_eq!(tm.target(), ms(1500));
+        tm.update(d4, -30);
+        assert_eq!(tm.target(), ms(1800));
+        tm.update(d4, -500);
+        assert_eq!(tm.target(), ms(2000));
+    }
+}
@@ -0,0 +1,309 @@
+#![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::cmove::Move;
+use crate::search::MATE_BOUND;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 65536;
+
+const ENTRIES_PER_BUCKET: usize = 4;
+/// Generations wrap around in the six bits left beside the bound.
+const AGE_MASK: u8 = 0x3F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Bound {
+    None,
+    /// The score is at most this (the search failed low).
+    Upper,
+    /// The score is at least this (the search failed high).
+    Lower,
+    Exact,
+}
+
+/// What the table knows about a position, with scores relative to the
+/// current ply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtHit {
+    pub m: Move,
+    pub score: i32,
+    pub eval: i32,
+    pub depth: usize,
+    pub bound: Bound,
+}
+
+/// Packs everything but the key into 64 bits:
+/// bit  0-15: move
+/// bit 16-31: score
+/// bit 32-47: static eval
+/// bit 48-55: depth
+/// bit 56-57: bound
+/// bit 58-63: age
+fn pack(m: Move, score: i32, eval: i32, depth: usize, bound: Bound, age: u8) -> u64 {
+    u64::from(m.as_u16())
+        | u64::from(score as i16 as u16) << 16
+        | u64::from(eval as i16 as u16) << 32
+        | (depth.min(255) as u64) << 48
+        | (bound as u64) << 56
+        | u64::from(age & AGE_MASK) << 58
+}
+
+const fn unpack_move(data: u64) -> Move {
+    Move::from_u16(data as u16)
+}
+
+const fn unpack_score(data: u64) -> i32 {
+    (data >> 16) as u16 as i16 as i32
+}
+
+const fn unpack_eval(data: u64) -> i32 {
+    (data >> 32) as u16 as i16 as i32
+}
+
+const fn unpack_depth(data: u64) -> usize {
+    ((data >> 48) & 0xFF) as usize
+}
+
+const fn unpack_bound(data: u64) -> Bound {
+    match (data >> 56) & 3 {
+        1 => Bound::Upper,
+        2 => Bound::Lower,
+        3 => Bound::Exact,
+        _ => Bound::None,
+    }
+}
+
+const fn unpack_age(data: u64) -> u8 {
+    (data >> 58) as u8 & AGE_MASK
+}
+
+/// An entry is stored as the key xor the data alongside the data, so a
+/// torn write from two threads storing at once makes the key check fail
+/// rather than returning another position's data.
+#[derive(Default)]
+struct Entry {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Entry {
+    fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key_xor_data.load(Ordering::Relaxed) ^ data;
+        (key, data)
+    }
+
+    fn store(&self, key: u64, data: u64) {
+        self.key_xor_data.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    entries: [Entry; ENTRIES_PER_BUCKET],
+}
+
+/// A transposition table that can be shared between search threads
+/// without locking.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8,
+}
+
+/// Mate scores are stored relative to the position rather than the root.
+const fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+const fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+impl TranspositionTable {
+    /// Creates a table of `megabytes` megabytes.
+    pub fn new(megabytes: usize) -> Self {
+        let mut table = Self { buckets: Vec::new(), age: AtomicU8::new(0) };
+        table.resize(megabytes);
+        table
+    }
+
+    /// Resizes the table, clearing it.
+    pub fn resize(&mut self, megabytes: usize) {
+        let bytes = megabytes.clamp(1, MAX_HASH_MB) * 1024 * 1024;
+        let len = bytes / std::mem::size_of::<Bucket>();
+        self.buckets = Vec::new();
+        self.buckets.resize_with(len, Bucket::default);
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            for entry in &bucket.entries {
+                entry.store(0, 0);
+            }
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Marks the start of a new search, so that older entries are replaced
+    /// first.
+    pub fn new_search(&self) {
+        let age = self.age.load(Ordering::Relaxed);
+        self.age.store((age + 1) & AGE_MASK, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        let index = (u128::from(key) * self.buckets.len() as u128) >> 64;
+        &self.buckets[index as usize]
+    }
+
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtHit> {
+        self.bucket(key).entries.iter().find_map(|entry| {
+            let (entry_key, data) = entry.load();
+            let bound = unpack_bound(data);
+            (entry_key == key && bound != Bound::None).then(|| TtHit {
+                m: unpack_move(data),
+                score: score_from_tt(unpack_score(data), ply),
+                eval: unpack_eval(data),
+                depth: unpack_depth(data),
+                bound,
+            })
+        })
+    }
+
+    /// Stores a search result. An existing entry for the position is
+    /// overwritten, keeping its move if `m` is null; otherwise the entry
+    /// with the least depth, counting older searches as shallower, goes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn store(&self, key: u64, m: Move, score: i32, eval: i32, depth: usize, bound: Bound, ply: usize) {
+        let age = self.age.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+
+        let mut replace = &bucket.entries[0];
+        let mut worst = i32::MAX;
+        for entry in &bucket.entries {
+            let (entry_key, data) = entry.load();
+            if entry_key == key || unpack_bound(data) == Bound::None {
+                replace = entry;
+                break;
+            }
+            let relative_age = i32::from(age.wrapping_sub(unpack_age(data)) & AGE_MASK);
+            let worth = unpack_depth(data) as i32 - 8 * relative_age;
+            if worth < worst {
+                worst = worth;
+                replace = entry;
+            }
+        }
+
+        let (old_key, old_data) = replace.load();
+        let m = if m == Move::null() && old_key == key { unpack_move(old_data) } else { m };
+        replace.store(key, pack(m, score_to_tt(score, ply), eval, depth, bound, age));
+    }
+
+    /// How full the table is with entries from the current search, in
+    /// permille, estimated from the first thousand entries.
+    pub fn hashfull(&self) -> usize {
+        let age = self.age.load(Ordering::Relaxed);
+        self.buckets
+            .iter()
+            .take(1000 / ENTRIES_PER_BUCKET)
+            .flat_map(|bucket| &bucket.entries)
+            .filter(|entry| {
+                let (_, data) = entry.load();
+                unpack_bound(data) != Bound::None && unpack_age(data) == age
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod transposition_tests {
+    use crate::cmove::Move;
+    use crate::search::MATE;
+    use crate::transposition::{Bound, TranspositionTable, TtHit};
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let m = Move::from_uci("e2e4").unwrap();
+        assert_eq!(tt.probe(0xDEAD_BEEF, 0), None);
+
+        tt.store(0xDEAD_BEEF, m, -123, 45, 7, Bound::Lower, 3);
+        let hit = TtHit { m, score: -123, eval: 45, depth: 7, bound: Bound::Lower };
+        assert_eq!(tt.probe(0xDEAD_BEEF, 0), Some(hit));
+        assert_eq!(tt.probe(0xDEAD_BEEE, 0), None);
+
+        // a null move keeps the move already stored for the position.
+        tt.store(0xDEAD_BEEF, Move::null(), 10, 45, 8, Bound::Upper, 0);
+        assert_eq!(tt.probe(0xDEAD_BEEF, 0).unwrap().m, m);
+    }
+
+    #[test]
+    fn mate_scores_are_relative() {
+        let tt = TranspositionTable::new(1);
+        // mate in 5 plies from the root, found at ply 3.
+        tt.store(1, Move::null(), MATE - 5, 0, 2, Bound::Exact, 3);
+        // reached again at ply 1, the mate is 2 plies closer.
+        assert_eq!(tt.probe(1, 1).unwrap().score, MATE - 3);
+        tt.store(2, Move::null(), -MATE + 4, 0, 2, Bound::Exact, 4);
+        assert_eq!(tt.probe(2, 0).unwrap().score, -MATE);
+    }
+
+    #[test]
+    fn replacement() {
+        let tt = TranspositionTable::new(1);
+        // keys in [0, 2^64 / buckets) all map to the first bucket.
+        let keys = [1, 2, 3, 4, 5];
+        for (depth, &key) in keys[..4].iter().enumerate() {
+            tt.store(key, Move::null(), 0, 0, 10 - depth, Bound::Exact, 0);
+        }
+        tt.store(keys[4], Move::null(), 0, 0, 1, Bound::Exact, 0);
+        assert_eq!(tt.probe(keys[3], 0), None);
+        assert!(keys.iter().filter(|&&key| tt.probe(key, 0).is_some()).count() == 4);
+
+        // entries from previous searches count as shallower.
+        tt.new_search();
+        tt.store(6, Move::null(), 0, 0, 1, Bound::Exact, 0);
+        assert_eq!(tt.probe(keys[4], 0), None);
+        tt.store(7, Move::null(), 0, 0, 1, Bound::Exact, 0);
+        assert_eq!(tt.probe(keys[2], 0), None);
+        assert!(tt.probe(6, 0).is_some());
+        assert!(tt.probe(7, 0).is_some());
+    }
+
+    #[test]
+    fn hashfull_and_clear() {
+        let mut tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+        for i in 0..100_000_u64 {
+            tt.store(i.wrapping_mul(0x9E37_79B9_7F4A_7C15), Move::null(), 0, 0, 1, Bound::Exact, 0);
+        }
+        assert!(tt.hashfull() > 500);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+
+        tt.store(7, Move::null(), 0, 0, 1, Bound::Exact, 0);
+        tt.clear();
+        assert_eq!(tt.probe(7, 0), None);
+
+        tt.resize(2);
+        assert_eq!(tt.probe(7, 0), None);
+        assert_eq!(tt.buckets.len(), 2 * 1024 * 1024 / 64);
+    }
+}
@@ -0,0 +1,310 @@
+#![allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+
+use std::fs;
+use std::num::NonZeroUsize;
+use std::thread;
+
+use crate::board::Board;
+use crate::endgame::{self, SCALE_NORMAL};
+use crate::evaluation::{trace, EvalParams, MAX_PHASE};
+
+const DEFAULT_EPOCHS: usize = 1000;
+const DEFAULT_RATE: f64 = 1.0;
+/// Adam's decay rates for the mean and the variance of the gradient.
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+const REPORT_INTERVAL: usize = 50;
+
+/// A labelled position as the tuner sees it: the evaluation as a sum of
+/// weights.
+struct Position {
+    /// The weights that appear, with how many times they are added for
+    /// white less for black.
+    counts: Vec<(u16, i16)>,
+    /// How much of the midgame weights count, from 0 to 1.
+    mg: f64,
+    /// How much of the endgame weights count: what the midgame weights
+    /// leave, less in endgames that are hard to win.
+    eg: f64,
+    /// From white's point of view: 1 for a win, 0.5 for a draw.
+    result: f64,
+}
+
+/// Parses a position labelled with the result of the game it is from, as
+/// a FEN followed by the result in any of the usual forms: `1-0`, `[1.0]`,
+/// `"1/2-1/2";`, `0`.
+pub fn parse_line(line: &str) -> Result<(Board, f64), String> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    if fields.len() < 5 {
+        return Err(format!("expected a FEN and a result: {line}"));
+    }
+
+    // the move counters are optional, but the last field is the result.
+    let counters = fields[4..].iter().take(2).take_while(|field| field.parse::<u32>().is_ok()).count();
+    let fen_length = 4 + counters.min(fields.len() - 5);
+    let board = Board::from_fen(&fields[..fen_length].join(" "))?;
+
+    let result = fields[fen_length..]
+        .iter()
+        .rev()
+        .find_map(|field| match field.trim_matches(|c| matches!(c, '[' | ']' | '"' | ';')) {
+            "1-0" | "1" | "1.0" => Some(1.0),
+            "0-1" | "0" | "0.0" => Some(0.0),
+            "1/2-1/2" | "0.5" => Some(0.5),
+            _ => None,
+        })
+        .ok_or_else(|| format!("no result: {line}"))?;
+    Ok((board, result))
+}
+
+/// The expected score for an evaluation of `eval` centipawns.
+fn sigmoid(k: f64, eval: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+}
+
+/// Texel tuning: fits the evaluation weights to the results of the games
+/// the positions come from, by gradient descent on the squared error of
+/// the expected scores.
+pub struct Tuner {
+    positions: Vec<Position>,
+    /// The midgame and endgame value of every weight, in the order of
+    /// `EvalParams::weights_mut`.
+    weights: Vec<[f64; 2]>,
+    threads: usize,
+    /// Scales evaluations to expected scores.
+    k: f64,
+}
+
+impl Tuner {
+    pub fn new(data: &[(Board, f64)], params: &EvalParams, threads: usize) -> Self {
+        // the endgames with evaluations of their own are no sums of weights.
+        let positions = data
+            .iter()
+            .filter(|(board, _)| endgame::evaluate(board).is_none())
+            .map(|(board, result)| {
+                let (counts, phase, scale) = trace(board, params);
+                let counts = counts
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(_, count)| count != 0)
+                    .map(|(i, count)| (i as u16, count as i16))
+                    .collect();
+                let mg = f64::from(phase.min(MAX_PHASE)) / f64::from(MAX_PHASE);
+                let eg = (1.0 - mg) * f64::from(scale) / f64::from(SCALE_NORMAL);
+                Position { counts, mg, eg, result: *result }
+            })
+            .collect();
+        let weights = params.clone().weights_mut().into_iter().map(|w| [f64::from(w.mg), f64::from(w.eg)]).collect();
+        Self { positions, weights, threads: threads.max(1), k: 1.0 }
+    }
+
+    fn eval(&self, position: &Position) -> f64 {
+        position.counts.iter().fold(0.0, |eval, &(i, count)| {
+            let [mg, eg] = self.weights[usize::from(i)];
+            f64::from(count).mul_add(mg.mul_add(position.mg, eg * position.eg), eval)
+        })
+    }
+
+    /// Applies `f` to the positions in a chunk for every thread.
+    fn map_chunks<T: Send>(&self, f: impl Fn(&[Position]) -> T + Sync) -> Vec<T> {
+        let chunk_size = self.positions.len().div_ceil(self.threads).max(1);
+        thread::scope(|scope| {
+            let handles = self.positions.chunks(chunk_size).map(|chunk| scope.spawn(|| f(chunk))).collect::<Vec<_>>();
+            handles.into_iter().map(|handle| handle.join().expect("tuning thread panicked")).collect()
+        })
+    }
+
+    /// The mean squared error of the expected scores with scaling `k`.
+    pub fn error(&self, k: f64) -> f64 {
+        let total: f64 = self
+            .map_chunks(|chunk| chunk.iter().map(|p| (p.result - sigmoid(k, self.eval(p))).powi(2)).sum::<f64>())
+            .into_iter()
+            .sum();
+        total / self.positions.len().max(1) as f64
+    }
+
+    /// The scaling that best fits the current weights, narrowed down one
+    /// decimal place at a time.
+    pub fn optimal_k(&self) -> f64 {
+        let (mut best, mut step) = (1.0, 1.0);
+        for _ in 0..5 {
+            let candidates = (-10..=10).map(|i| f64::from(i).mul_add(step, best)).filter(|&k| k > 0.0);
+            best = candidates.min_by(|&a, &b| self.error(a).total_cmp(&self.error(b))).unwrap_or(best);
+            step /= 10.0;
+        }
+        best
+    }
+
+    /// The gradient of the error with respect to every weight.
+    fn gradient(&self) -> Vec<[f64; 2]> {
+        let partials = self.map_chunks(|chunk| {
+            let mut gradient = vec![[0.0; 2]; self.weights.len()];
+            for position in chunk {
+                let expected = sigmoid(self.k, self.eval(position));
+                let slope = (expected - position.result) * expected * (1.0 - expected);
+                for &(i, count) in &position.counts {
+                    let term = slope * f64::from(count);
+                    gradient[usize::from(i)][0] += term * position.mg;
+                    gradient[usize::from(i)][1] += term * position.eg;
+                }
+            }
+            gradient
+        });
+
+        // the constant factors of the derivative are left to the rate.
+        let mut gradient = vec![[0.0; 2]; self.weights.len()];
+        for partial in partials {
+            for (total, part) in gradient.iter_mut().zip(partial) {
+                total[0] += part[0];
+                total[1] += part[1];
+            }
+        }
+        gradient
+    }
+
+    /// Tunes the weights with the Adam optimiser, calling `report` with the
+    /// error every few epochs.
+    pub fn tune(&mut self, epochs: usize, rate: f64, mut report: impl FnMut(usize, f64)) {
+        self.k = self.optimal_k();
+        let mut mean = vec![[0.0; 2]; self.weights.len()];
+        let mut variance = vec![[0.0; 2]; self.weights.len()];
+        for epoch in 1..=epochs {
+            let gradient = self.gradient();
+            let t = i32::try_from(epoch).unwrap_or(i32::MAX);
+            for i in 0..self.weights.len() {
+                for phase in 0..2 {
+                    let g = gradient[i][phase];
+                    mean[i][phase] = BETA1.mul_add(mean[i][phase], (1.0 - BETA1) * g);
+                    variance[i][phase] = BETA2.mul_add(variance[i][phase], (1.0 - BETA2) * g * g);
+                    let mean_hat = mean[i][phase] / (1.0 - BETA1.powi(t));
+                    let variance_hat = variance[i][phase] / (1.0 - BETA2.powi(t));
+                    self.weights[i][phase] -= rate * mean_hat / (variance_hat.sqrt() + EPSILON);
+                }
+            }
+            if epoch % REPORT_INTERVAL == 0 || epoch == epochs {
+                report(epoch, self.error(self.k));
+            }
+        }
+    }
+
+    /// The tuned weights, rounded to whole centipawns.
+    pub fn params(&self) -> EvalParams {
+        let mut params = EvalParams::new();
+        for (weight, &[mg, eg]) in params.weights_mut().into_iter().zip(&self.weights) {
+            weight.mg = mg.round() as i32;
+            weight.eg = eg.round() as i32;
+        }
+        params
+    }
+}
+
+/// Runs the `tune` subcommand: `tune <file> [--epochs N] [--rate R]
+/// [--threads N]`. Progress goes to stderr, and the tuned weights to
+/// stdout as Rust source.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: tune <file> [--epochs N] [--rate R] [--threads N]";
+    let path = args.first().ok_or(usage)?;
+    let mut epochs = DEFAULT_EPOCHS;
+    let mut rate = DEFAULT_RATE;
+    let mut threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or(usage)?;
+        let invalid = || format!("invalid value for {option}: {value}");
+        match option.as_str() {
+            "--epochs" => epochs = value.parse().map_err(|_| invalid())?,
+            "--rate" => rate = value.parse().map_err(|_| invalid())?,
+            "--threads" => threads = value.parse().map_err(|_| invalid())?,
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let data = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| parse_line(line).map_err(|e| format!("{path}:{}: {e}", n + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tuner = Tuner::new(&data, &EvalParams::new(), threads);
+    let k = tuner.optimal_k();
+    eprintln!("{} positions, k {k:.4}, error {:.6}", data.len(), tuner.error(k));
+    tuner.tune(epochs, rate, |epoch, error| eprintln!("epoch {epoch}: error {error:.6}"));
+    println!("{}", tuner.params().to_source());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tune_tests {
+    use crate::board::Board;
+    use crate::colour::WHITE;
+    use crate::evaluation::{evaluate, s, EvalParams};
+    use crate::tune::{parse_line, Tuner};
+
+    #[test]
+    fn labelled_positions() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        for (line, result) in [
+            (format!("{fen} 1-0"), 1.0),
+            (format!("{fen} [0.5]"), 0.5),
+            (format!("{fen} c9 \"0-1\";"), 0.0),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - [1.0]".to_string(), 1.0),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 3 1/2-1/2".to_string(), 0.5),
+        ] {
+            let (board, parsed) = parse_line(&line).unwrap();
+            assert!((parsed - result).abs() < f64::EPSILON, "{line}");
+            assert!(board.state() == Board::from_fen(fen).unwrap().state());
+        }
+        assert!(parse_line("4k3/8/8/8/8/8/8/R3K3 w - -").is_err());
+        assert!(parse_line("4k3/8/8/8/8/8/8/R3K3 w - - 0 1 draw").is_err());
+        assert!(parse_line("4k3/8/8 w - - 1-0").is_err());
+    }
+
+    #[test]
+    fn tuning_fits_results() {
+        // a side a knight up wins, but the weights start with knights
+        // worth little. A lone knight would not win, and is scaled down.
+        let mut data = Vec::new();
+        for (fen, result) in [
+            ("4k3/4p3/8/8/8/8/4P3/1N2K3 w - - 0 1", 1.0),
+            ("1n2k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1", 0.0),
+            ("4k3/4p3/8/8/8/8/4P3/4K1N1 b - - 0 1", 1.0),
+            ("4k1n1/4p3/8/8/8/8/4P3/4K3 b - - 0 1", 0.0),
+            ("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1", 0.5),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", 0.5),
+        ] {
+            data.push((Board::from_fen(fen).unwrap(), result));
+        }
+        let mut params = EvalParams::new();
+        params.material[2] = s(50, 50);
+
+        let mut tuner = Tuner::new(&data, &params, 2);
+        let before = tuner.error(tuner.optimal_k());
+        let mut errors = Vec::new();
+        tuner.tune(200, 5.0, |_, error| errors.push(error));
+        assert!(errors.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(errors.last().unwrap() < &(before / 2.0));
+        assert!(tuner.params().material[2].eg > 50);
+    }
+
+    #[test]
+    fn traced_evaluations() {
+        // the tuner's evaluation is the engine's, but for rounding, and it
+        // leaves out the endgames with evaluations of their own.
+        let data = [
+            "2r2rk1/pp1b1ppp/4pn2/q2p4/3P4/P1PB1N2/2Q2PPP/R4RK1 b - - 0 16",
+            "8/5p2/4kb2/8/8/1P6/P1B5/K7 w - - 0 1",
+            "8/8/8/4k3/8/8/8/KB6 b - - 0 1",
+            "8/8/8/4k3/8/8/8/KR6 w - - 0 1",
+        ]
+        .map(|fen| (Board::from_fen(fen).unwrap(), 0.5));
+        let tuner = Tuner::new(&data, &EvalParams::new(), 1);
+        assert_eq!(tuner.positions.len(), 3);
+        for ((board, _), position) in data.iter().zip(&tuner.positions) {
+            let eval = if board.turn_as_idx() == WHITE { evaluate(board) } else { -evaluate(board) };
+            assert!((tuner.eval(position) - f64::from(eval)).abs() < 2.0, "{}", board.fen());
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::colour::Colour;
+use crate::dtm::Tables;
+#[cfg(feature = "nnue")]
+use crate::nnue::Network;
+use crate::params::SearchParams;
+use crate::search::{is_mate_score, mate_in, SearchLimits, SearchResult, SharedState, MAX_MULTIPV};
+use crate::smp::{lazy_smp, DEFAULT_THREADS, MAX_THREADS};
+use crate::tablebase::Tablebase;
+use crate::timeman::{self, DEFAULT_MOVE_OVERHEAD_MS, MAX_MOVE_OVERHEAD_MS};
+use crate::transposition::{TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB};
+
+const NAME: &str = "Istus";
+const AUTHOR: &str = "Cosmo Bobak";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The parameters of a `go` command. Times are in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub mate: Option<usize>,
+    pub infinite: bool,
+    pub ponder: bool,
+}
+
+impl GoParams {
+    /// Parses the arguments following `go`.
+    pub fn parse(args: &str) -> Result<Self, String> {
+        fn value<T: std::str::FromStr>(name: &str, token: Option<&str>) -> Result<T, String> {
+            let token = token.ok_or_else(|| format!("missing value for {name}"))?;
+            token.parse().map_err(|_| format!("invalid value for {name}: {token}"))
+        }
+
+        let mut params = Self::default();
+        let mut tokens = args.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "wtime" => params.wtime = Some(value(token, tokens.next())?),
+                "btime" => params.btime = Some(value(token, tokens.next())?),
+                "winc" => params.winc = Some(value(token, tokens.next())?),
+                "binc" => params.binc = Some(value(token, tokens.next())?),
+                "movestogo" => params.movestogo = Some(value(token, tokens.next())?),
+                "depth" => params.depth = Some(value(token, tokens.next())?),
+                "nodes" => params.nodes = Some(value(token, tokens.next())?),
+                "movetime" => params.movetime = Some(value(token, tokens.next())?),
+                "mate" => params.mate = Some(value(token, tokens.next())?),
+                "infinite" => params.infinite = true,
+                "ponder" => params.ponder = true,
+                _ => return Err(format!("unknown go parameter: {token}")),
+            }
+        }
+        Ok(params)
+    }
+
+    /// The search limits for `turn` to move, allowing `overhead` for
+    /// communication on every move.
+    pub fn limits(&self, turn: Colour, overhead: Duration) -> SearchLimits {
+        let (time, inc) = match turn {
+            Colour::White => (self.wtime, self.winc),
+            Colour::Black => (self.btime, self.binc),
+        };
+
+        let mut limits = SearchLimits { depth: self.depth, nodes: self.nodes, mate: self.mate, ..SearchLimits::new() };
+        if self.infinite {
+            return limits;
+        }
+        if let Some(movetime) = self.movetime {
+            let movetime = Duration::from_millis(movetime);
+            limits.time = Some(movetime.saturating_sub(overhead).max(Duration::from_millis(1)));
+        } else if let Some(time) = time {
+            let inc = Duration::from_millis(inc.unwrap_or(0));
+            let (soft, hard) = timeman::allocate(Duration::from_millis(time), inc, self.movestogo, overhead);
+            limits.soft_time = Some(soft);
+            limits.time = Some(hard);
+        }
+        limits
+    }
+}
+
+/// Formats a completed iteration as UCI `info` lines, one for every line
+/// searched.
+fn info_lines(result: &SearchResult, hashfull: usize) -> Vec<String> {
+    let millis = result.elapsed.as_millis();
+    let nps = u128::from(result.nodes) * 1000 / millis.max(1);
+    result
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let score = if is_mate_score(line.score) {
+                format!("mate {}", mate_in(line.score))
+            } else {
+                format!("cp {}", line.score)
+            };
+            let pv = line.pv.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ");
+            format!(
+                "info depth {} seldepth {} multipv {} score {score} nodes {} nps {nps} hashfull {hashfull} time {millis} pv {pv}",
+                line.depth,
+                line.seldepth,
+                i + 1,
+                result.nodes,
+            )
+        })
+        .collect()
+}
+
+/// Parses the arguments following `position` into a board.
+fn parse_position(args: &str) -> Result<Board, String> {
+    let (setup, moves) = match args.split_once("moves") {
+        Some((setup, moves)) => (setup.trim(), moves),
+        None => (args.trim(), ""),
+    };
+
+    let mut board = if setup == "startpos" {
+        Board::new()
+    } else if let Some(fen) = setup.strip_prefix("fen") {
+        Board::from_fen(fen.trim())?
+    } else {
+        return Err(format!("invalid position: {setup}"));
+    };
+
+    for uci in moves.split_whitespace() {
+        let m = board.parse_uci(uci).map_err(|e| format!("{e}: {uci}"))?;
+        board.make(m);
+    }
+    Ok(board)
+}
+
+/// Splits the arguments following `setoption` into the option name and
+/// its value, if any.
+fn parse_setoption(args: &str) -> Result<(&str, Option<&str>), String> {
+    let args = args.trim().strip_prefix("name").ok_or("expected setoption name <id> [value <x>]")?;
+    Ok(match args.split_once(" value ") {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (args.trim(), None),
+    })
+}
+
+/// A search running in the background.
+struct ActiveSearch {
+    shared: Arc<SharedState>,
+    handle: JoinHandle<io::Result<()>>,
+}
+
+/// The state of a UCI session, writing responses to `W`. Searches run on
+/// a background thread, so that `stop` and `ponderhit` can be read while
+/// they do.
+pub struct Uci<W: Write + Send + 'static> {
+    board: Board,
+    tt: Arc<TranspositionTable>,
+    params: SearchParams,
+    threads: usize,
+    multipv: usize,
+    move_overhead: Duration,
+    #[cfg(feature = "nnue")]
+    network: Option<Arc<Network>>,
+    tablebase: Option<Arc<Tablebase>>,
+    dtm: Option<Arc<Tables>>,
+    out: Arc<Mutex<W>>,
+    search: Option<ActiveSearch>,
+}
+
+/// Writes a line to a shared output and flushes it.
+fn send_line(out: &Mutex<impl Write>, line: impl Display) -> io::Result<()> {
+    let mut out = out.lock().unwrap_or_else(PoisonError::into_inner);
+    writeln!(out, "{line}")?;
+    out.flush()
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            board: Board::new(),
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            params: SearchParams::new(),
+            threads: DEFAULT_THREADS,
+            multipv: 1,
+            move_overhead: Duration::from_millis(DEFAULT_MOVE_OVERHEAD_MS),
+            #[cfg(feature = "nnue")]
+            network: None,
+            tablebase: None,
+            dtm: None,
+            out: Arc::new(Mutex::new(out)),
+            search: None,
+        }
+    }
+
+    pub const fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn send(&self, line: impl Display) -> io::Result<()> {
+        send_line(&self.out, line)
+    }
+
+    /// Replaces the board, evaluating it with the current network.
+    fn set_board(&mut self, board: Board) {
+        self.board = board;
+        #[cfg(feature = "nnue")]
+        self.board.set_network(self.network.clone());
+    }
+
+    /// Handles a single line of input. Returns `false` once the session
+    /// should end.
+    pub fn handle(&mut self, line: &str) -> io::Result<bool> {
+        let line = line.trim();
+        let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match command {
+            "uci" => {
+                self.send(format_args!("id name {NAME} {VERSION}"))?;
+                self.send(format_args!("id author {AUTHOR}"))?;
+                self.send(format_args!("option name Hash type spin default {DEFAULT_HASH_MB} min 1 max {MAX_HASH_MB}"))?;
+                self.send(format_args!("option name Threads type spin default {DEFAULT_THREADS} min 1 max {MAX_THREADS}"))?;
+                self.send(format_args!("option name MultiPV type spin default 1 min 1 max {MAX_MULTIPV}"))?;
+                self.send(format_args!(
+                    "option name Move Overhead type spin default {DEFAULT_MOVE_OVERHEAD_MS} min 0 max {MAX_MOVE_OVERHEAD_MS}"
+                ))?;
+                #[cfg(feature = "nnue")]
+                self.send("option name EvalFile type string default <empty>")?;
+                self.send("option name SyzygyPath type string default <empty>")?;
+                self.send("option name DtmPath type string default <empty>")?;
+                for option in self.params.uci_options() {
+                    self.send(option)?;
+                }
+                self.send("uciok")?;
+            }
+            "isready" => self.send("readyok")?,
+            "setoption" => {
+                self.wait()?;
+                if let Err(e) = parse_setoption(args).and_then(|(name, value)| self.set_option(name, value)) {
+                    self.send(format_args!("info string {e}"))?;
+                }
+            }
+            "ucinewgame" => {
+                self.wait()?;
+                self.set_board(Board::new());
+                self.tt.clear();
+            }
+            "position" => match parse_position(args) {
+                Ok(board) => self.set_board(board),
+                Err(e) => self.send(format_args!("info string {e}"))?,
+            },
+            "go" => match GoParams::parse(args) {
+                Ok(params) => self.go(&params)?,
+                Err(e) => self.send(format_args!("info string {e}"))?,
+            },
+            "stop" => self.stop()?,
+            "ponderhit" => {
+                if let Some(search) = &self.search {
+                    search.shared.pondering.store(false, Ordering::Relaxed);
+                }
+            }
+            "" => {}
+            "quit" => {
+                self.stop()?;
+                return Ok(false);
+            }
+            "d" => self.send(self.board.to_string().trim_end())?,
+            _ => self.send(format_args!("info string unknown command: {command}"))?,
+        }
+        Ok(true)
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let value = value.ok_or_else(|| format!("missing value for option {name}"))?;
+        match name.to_ascii_lowercase().as_str() {
+            "hash" => {
+                let megabytes = value.parse().map_err(|_| format!("invalid value for Hash: {value}"))?;
+                // no search is running, so nothing else holds the table.
+                match Arc::get_mut(&mut self.tt) {
+                    Some(tt) => tt.resize(megabytes),
+                    None => self.tt = Arc::new(TranspositionTable::new(megabytes)),
+                }
+            }
+            "threads" => {
+                let threads: usize = value.parse().map_err(|_| format!("invalid value for Threads: {value}"))?;
+                self.threads = threads.clamp(1, MAX_THREADS);
+            }
+            "multipv" => {
+                let multipv: usize = value.parse().map_err(|_| format!("invalid value for MultiPV: {value}"))?;
+                self.multipv = multipv.clamp(1, MAX_MULTIPV);
+            }
+            "move overhead" => {
+                let millis: u64 = value.parse().map_err(|_| format!("invalid value for Move Overhead: {value}"))?;
+                self.move_overhead = Duration::from_millis(millis.min(MAX_MOVE_OVERHEAD_MS));
+            }
+            #[cfg(feature = "nnue")]
+            "evalfile" => {
+                self.network = if value == "<empty>" { None } else { Some(Arc::new(Network::load(value)?)) };
+                self.board.set_network(self.network.clone());
+            }
+            "syzygypath" => {
+                self.tablebase = if value == "<empty>" { None } else { Some(Arc::new(Tablebase::new(value)?)) };
+            }
+            "dtmpath" => {
+                self.dtm = if value == "<empty>" { None } else { Some(Arc::new(Tables::new(value)?)) };
+            }
+            _ => return self.params.set_option(name, value).unwrap_or_else(|| Err(format!("unknown option: {name}"))),
+        }
+        Ok(())
+    }
+
+    /// Starts a search in the background. While pondering or analysing
+    /// infinitely, the best move is held back until `ponderhit` or `stop`,
+    /// even if the search ends before then.
+    fn go(&mut self, params: &GoParams) -> io::Result<()> {
+        self.stop()?;
+
+        let board = self.board.clone();
+        let limits = params.limits(board.turn(), self.move_overhead);
+        let search_params = self.params;
+        let threads = self.threads;
+        let multipv = self.multipv;
+        let tt = Arc::clone(&self.tt);
+        let out = Arc::clone(&self.out);
+        let shared = Arc::new(SharedState::new().with_tablebase(self.tablebase.clone()).with_dtm(self.dtm.clone()));
+        shared.pondering.store(params.ponder, Ordering::Relaxed);
+        let infinite = params.infinite;
+
+        let search_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || {
+            let shared = search_shared;
+            let mut written = Ok(());
+            let result = lazy_smp(&board, limits, search_params, threads, multipv, &tt, &shared, |result| {
+                for line in info_lines(result, tt.hashfull()) {
+                    if written.is_ok() {
+                        written = send_line(&out, line);
+                    }
+                }
+            });
+            written?;
+
+            while !shared.stop.load(Ordering::Relaxed) && (infinite || shared.pondering.load(Ordering::Relaxed)) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            match result.pv.get(1) {
+                Some(ponder) => send_line(&out, format_args!("bestmove {} ponder {ponder}", result.best_move)),
+                None => send_line(&out, format_args!("bestmove {}", result.best_move)),
+            }
+        });
+        self.search = Some(ActiveSearch { shared, handle });
+        Ok(())
+    }
+
+    /// Stops the search, if there is one, and waits for its best move.
+    fn stop(&mut self) -> io::Result<()> {
+        if let Some(search) = &self.search {
+            search.shared.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait()
+    }
+
+    /// Waits for the search, if there is one, to finish by itself.
+    pub fn wait(&mut self) -> io::Result<()> {
+        match self.search.take() {
+            Some(search) => search.handle.join().unwrap_or_else(|_| Err(io::Error::other("search thread panicked"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or the end of input.
+    pub fn run(&mut self, input: impl BufRead) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.handle(&line?)? {
+                return Ok(());
+            }
+        }
+        self.wait()
+    }
+}
+
+#[cfg(test)]
+mod uci_tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::colour::Colour;
+    use crate::search::SearchLimits;
+    use crate::uci::{GoParams, Uci};
+
+    /// Collects what the session writes, from whichever thread.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Output {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn session() -> (Uci<Output>, Output) {
+        let out = Output::default();
+        (Uci::new(out.clone()), out)
+    }
+
+    /// Sends `line`, waiting for the search it starts to finish, if any.
+    fn send(uci: &mut Uci<Output>, out: &Output, line: &str) -> String {
+        uci.handle(line).unwrap();
+        if line.starts_with("go") {
+            uci.wait().unwrap();
+        }
+        out.take()
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut uci, out) = session();
+        let response = send(&mut uci, &out, "uci");
+        assert!(response.starts_with("id name Istus"));
+        assert!(response.contains("option name Hash type spin default 16 "));
+        assert!(response.contains("option name LMR type check default true\n"));
+        assert!(response.ends_with("uciok\n"));
+        assert_eq!(send(&mut uci, &out, "isready"), "readyok\n");
+        assert!(!uci.handle("quit").unwrap());
+    }
+
+    #[test]
+    fn position_commands() {
+        let (mut uci, out) = session();
+        send(&mut uci, &out, "position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(uci.board().fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        send(&mut uci, &out, "position fen 4k3/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8q");
+        assert_eq!(uci.board().fen(), "Q3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        let response = send(&mut uci, &out, "position startpos moves e2e5");
+        assert!(response.starts_with("info string"));
+
+        let response = send(&mut uci, &out, "position startpos moves e2e4 0000");
+        assert_eq!(response.trim(), "info string null move: 0000");
+        assert_eq!(uci.board().fen(), "Q3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+        send(&mut uci, &out, "ucinewgame");
+        assert_eq!(uci.board().fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    }
+
+    #[test]
+    fn set_options() {
+        let (mut uci, out) = session();
+        assert_eq!(send(&mut uci, &out, "setoption name Hash value 1"), "");
+        assert_eq!(send(&mut uci, &out, "setoption name hash value 2"), "");
+        assert!(send(&mut uci, &out, "setoption name Hash value lots").starts_with("info string invalid value"));
+        assert!(send(&mut uci, &out, "setoption name Hash").starts_with("info string missing value"));
+        assert!(send(&mut uci, &out, "setoption name Contempt value 10").starts_with("info string unknown option"));
+        assert_eq!(send(&mut uci, &out, "setoption name NullMove value false"), "");
+        assert!(!uci.params.null_move);
+        assert_eq!(send(&mut uci, &out, "setoption name Threads value 4"), "");
+        assert_eq!(uci.threads, 4);
+        assert_eq!(send(&mut uci, &out, "setoption name Move Overhead value 100"), "");
+        assert_eq!(uci.move_overhead, Duration::from_millis(100));
+        assert!(send(&mut uci, &out, "setoption name RfpMargin value -1").starts_with("info string invalid value"));
+        assert!(send(&mut uci, &out, "setoption name SyzygyPath value /nonexistent/tables").starts_with("info string /nonexistent"));
+        assert!(uci.tablebase.is_none());
+        assert_eq!(send(&mut uci, &out, &format!("setoption name SyzygyPath value {}", env!("CARGO_MANIFEST_DIR"))), "");
+        assert_eq!(uci.tablebase.as_ref().map(|tablebase| tablebase.max_pieces()), Some(0));
+        assert_eq!(send(&mut uci, &out, "setoption name SyzygyPath value <empty>"), "");
+        assert!(uci.tablebase.is_none());
+        assert!(send(&mut uci, &out, "setoption name DtmPath value /nonexistent/tables").starts_with("info string /nonexistent"));
+        assert_eq!(send(&mut uci, &out, &format!("setoption name DtmPath value {}", env!("CARGO_MANIFEST_DIR"))), "");
+        assert_eq!(uci.dtm.as_ref().map(|dtm| dtm.max_pieces()), Some(0));
+        assert_eq!(send(&mut uci, &out, "setoption name DtmPath value <empty>"), "");
+        assert!(uci.dtm.is_none());
+        assert!(send(&mut uci, &out, "setoption Hash 1").starts_with("info string expected"));
+    }
+
+    #[test]
+    fn go_params() {
+        let params = GoParams::parse("wtime 300000 btime 299000 winc 2000 binc 2000 movestogo 40").unwrap();
+        assert_eq!(params.wtime, Some(300_000));
+        assert_eq!(params.btime, Some(299_000));
+        assert_eq!(params.winc, Some(2000));
+        assert_eq!(params.movestogo, Some(40));
+        assert_eq!(params.depth, None);
+
+        let white = params.limits(Colour::White, Duration::ZERO);
+        assert_eq!(white.soft_time, Some(Duration::from_secs(9)));
+        assert_eq!(white.time, Some(Duration::from_secs(36)));
+        let black = params.limits(Colour::Black, Duration::ZERO);
+        assert_eq!(black.soft_time, Some(Duration::from_millis(8975)));
+
+        // a fixed time per move has no soft limit, but still allows for
+        // the move overhead.
+        let params = GoParams::parse("wtime 1000 movetime 500").unwrap();
+        let limits = params.limits(Colour::White, Duration::from_millis(30));
+        assert_eq!((limits.soft_time, limits.time), (None, Some(Duration::from_millis(470))));
+
+        let params = GoParams::parse("infinite").unwrap();
+        assert!(params.infinite);
+        assert_eq!(params.limits(Colour::White, Duration::from_millis(30)), SearchLimits::new());
+        assert!(GoParams::parse("depth").is_err());
+        assert!(GoParams::parse("nodes many").is_err());
+    }
+
+    #[test]
+    fn go_returns_legal_move() {
+        let (mut uci, out) = session();
+        send(&mut uci, &out, "position fen 7k/8/8/8/8/8/8/K7 w - - 0 1");
+        let response = send(&mut uci, &out, "go depth 3");
+        assert!(response.starts_with("info depth 1 seldepth 1 multipv 1 score cp "));
+        let bestmove = response.lines().last().unwrap().split_whitespace().collect::<Vec<_>>();
+        assert_eq!(bestmove[0], "bestmove");
+        assert!(uci.board().parse_uci(bestmove[1]).is_ok());
+
+        // every line searched gets an info line of its own.
+        send(&mut uci, &out, "setoption name MultiPV value 2");
+        let response = send(&mut uci, &out, "go depth 2");
+        let depth_two = response.lines().filter(|line| line.starts_with("info depth 2 ")).collect::<Vec<_>>();
+        assert_eq!(depth_two.len(), 2);
+        assert!(depth_two[1].contains(" multipv 2 score "));
+        send(&mut uci, &out, "setoption name MultiPV value 1");
+
+        send(&mut uci, &out, "position fen 7k/6Q1/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(send(&mut uci, &out, "go"), "bestmove 0000\n");
+
+        send(&mut uci, &out, "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let response = send(&mut uci, &out, "go mate 1");
+        assert!(response.contains("score mate 1 "));
+        assert!(response.ends_with("bestmove a1a8\n"));
+    }
+
+    #[cfg(feature = "nnue")]
+    #[test]
+    fn eval_file() {
+        let (mut uci, out) = session();
+        assert!(send(&mut uci, &out, "uci").contains("option name EvalFile type string default <empty>\n"));
+        assert!(send(&mut uci, &out, "setoption name EvalFile value nonexistent.nnue").starts_with("info string nonexistent.nnue: "));
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/nets/test.nnue");
+        assert_eq!(send(&mut uci, &out, &format!("setoption name EvalFile value {path}")), "");
+        send(&mut uci, &out, "position startpos moves e2e4 e7e5");
+        assert!(uci.board().accumulators().network().is_some());
+        let response = send(&mut uci, &out, "go depth 3");
+        let bestmove = response.lines().last().unwrap().split_whitespace().collect::<Vec<_>>();
+        assert!(uci.board().parse_uci(bestmove[1]).is_ok());
+
+        send(&mut uci, &out, "setoption name EvalFile value <empty>");
+        assert!(uci.board().accumulators().network().is_none());
+    }
+
+    #[test]
+    fn stop_and_ponder() {
+        let (mut uci, out) = session();
+
+        // an infinite search runs until stopped, and holds back its best
+        // move even once it has searched as deep as it can.
+        uci.handle("position fen 7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        uci.handle("go infinite").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        uci.handle("isready").unwrap();
+        assert!(out.take().ends_with("readyok\n"));
+        uci.handle("stop").unwrap();
+        let response = out.take();
+        assert!(response.lines().last().unwrap().starts_with("bestmove "));
+        assert!(send(&mut uci, &out, "stop").is_empty());
+
+        let (mut uci, out) = session();
+        uci.handle("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        uci.handle("go ponder depth 2").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!out.take().contains("bestmove"));
+        uci.handle("ponderhit").unwrap();
+        uci.wait().unwrap();
+        assert!(out.take().ends_with("bestmove a1a8\n"));
+
+        // the clock only starts on a ponder hit.
+        uci.handle("position startpos").unwrap();
+        uci.handle("go ponder movetime 10").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(!out.take().contains("bestmove"));
+        uci.handle("ponderhit").unwrap();
+        uci.wait().unwrap();
+        assert!(out.take().contains("bestmove"));
+
+        uci.handle("go infinite").unwrap();
+        assert!(!uci.handle("quit").unwrap());
+        assert!(out.take().contains("bestmove"));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::bitboards::Bitboard;
+use crate::piece::PieceType;
+
+/// Random keys for Zobrist hashing, generated at compile time.
+pub struct ZobristKeys {
+    /// Indexed by colour, piece type (from pawn) and square.
+    pub pieces: [[[u64; 64]; 6]; 2],
+    /// Indexed by the square of a rook with castling rights.
+    pub castling: [u64; 64],
+    /// Indexed by the file of the en passant square.
+    pub ep_file: [u64; 8],
+    /// Present when black is to move.
+    pub black_to_move: u64,
+}
+
+/// Advances `state`, returning it with the next pseudo-random number.
+pub const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys() -> ZobristKeys {
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 6]; 2],
+        castling: [0; 64],
+        ep_file: [0; 8],
+        black_to_move: 0,
+    };
+    let mut state = 0x1234_5678_9ABC_DEF0;
+    let mut key;
+
+    let mut colour = 0;
+    while colour < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut square = 0;
+            while square < 64 {
+                (state, key) = splitmix64(state);
+                keys.pieces[colour][piece][square] = key;
+                square += 1;
+            }
+            piece += 1;
+        }
+        colour += 1;
+    }
+
+    let mut square = 0;
+    while square < 64 {
+        (state, key) = splitmix64(state);
+        keys.castling[square] = key;
+        square += 1;
+    }
+
+    let mut file = 0;
+    while file < 8 {
+        (state, key) = splitmix64(state);
+        keys.ep_file[file] = key;
+        file += 1;
+    }
+
+    (_, key) = splitmix64(state);
+    keys.black_to_move = key;
+    keys
+}
+
+pub static KEYS: ZobristKeys = generate_keys();
+
+pub const fn piece_key(colour: usize, piece_type: PieceType, square: usize) -> u64 {
+    KEYS.pieces[colour][piece_type as usize - PieceType::Pawn as usize][square]
+}
+
+/// The combined key of the castling rights in `castling_rights`.
+pub const fn castling_key(castling_rights: u64) -> u64 {
+    let mut rights = castling_rights;
+    let mut key = 0;
+    while rights != 0 {
+        key ^= KEYS.castling[rights.trailing_zeros() as usize];
+        rights &= rights - 1;
+    }
+    key
+}
+
+pub const fn ep_key(ep_square: u64) -> u64 {
+    if ep_square == 0 {
+        0
+    } else {
+        KEYS.ep_file[ep_square.trailing_zeros() as usize % 8]
+    }
+}
+
+/// Computes the Zobrist key of a position from scratch.
+pub const fn hash(state: &Bitboard, turn_idx: usize) -> u64 {
+    let piece_bbs = [state.pawns, state.knights, state.bishops, state.rooks, state.queens, state.kings];
+
+    let mut key = castling_key(state.castling_rights) ^ ep_key(state.ep_square);
+    if turn_idx == 1 {
+        key ^= KEYS.black_to_move;
+    }
+
+    let mut colour = 0;
+    while colour < 2 {
+        let mut piece = 0;
+        while piece < 6 {
+            let mut bb = piece_bbs[piece] & state.occupied_co[colour];
+            while bb != 0 {
+                key ^= KEYS.pieces[colour][piece][bb.trailing_zeros() as usize];
+                bb &= bb - 1;
+            }
+            piece += 1;
+        }
+        colour += 1;
+    }
+    key
+}
+
+/// Computes the key of the pawns alone from scratch, for the pawn hash
+/// table.
+pub const fn pawn_hash(state: &Bitboard) -> u64 {
+    let mut key = 0;
+    let mut colour = 0;
+    while colour < 2 {
+        let mut bb = state.pawns & state.occupied_co[colour];
+        while bb != 0 {
+            key ^= KEYS.pieces[colour][0][bb.trailing_zeros() as usize];
+            bb &= bb - 1;
+        }
+        colour += 1;
+    }
+    key
+}
+
+#[cfg(test)]
+mod zobrist_tests {
+    use crate::board::Board;
+    use crate::zobrist::{hash, pawn_hash};
+
+    fn check_keys(board: &mut Board, depth: usize) {
+        assert_eq!(board.key(), hash(board.state(), board.turn_as_idx()), "{}", board.fen());
+        assert_eq!(board.pawn_key(), pawn_hash(board.state()), "{}", board.fen());
+        if depth == 0 {
+            return;
+        }
+        for &m in &board.legal_moves() {
+            let (key, pawn_key) = (board.key(), board.pawn_key());
+            board.make(m);
+            check_keys(board, depth - 1);
+            board.unmake();
+            assert_eq!((board.key(), board.pawn_key()), (key, pawn_key));
+        }
+    }
+
+    #[test]
+    fn incremental_keys() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1",
+        ];
+        for fen in fens {
+            check_keys(&mut Board::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn transpositions() {
+        let mut a = Board::new();
+        let mut b = Board::new();
+        for uci in ["g1f3", "g8f6", "b1c3"] {
+            a.make_uci(uci).unwrap();
+        }
+        for uci in ["b1c3", "g8f6", "g1f3"] {
+            b.make_uci(uci).unwrap();
+        }
+        assert_eq!(a.key(), b.key());
+        assert_ne!(a.key(), Board::new().key());
+
+        // castling rights and the side to move are part of the key.
+        let kq = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let k = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1").unwrap();
+        let black = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_ne!(kq.key(), k.key());
+        assert_ne!(kq.key(), black.key());
+    }
+
+    #[test]
+    fn repetition() {
+        let mut board = Board::new();
+        for uci in ["g1f3", "g8f6", "f3g1"] {
+            board.make_uci(uci).unwrap();
+            assert!(!board.is_repetition());
+        }
+        board.make_uci("f6g8").unwrap();
+        assert!(board.is_repetition());
+        board.make_uci("e2e4").unwrap();
+        assert!(!board.is_repetition());
+    }
+}